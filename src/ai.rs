@@ -0,0 +1,86 @@
+#![allow(dead_code)]
+
+use bitbybit::bitfield;
+
+const AI_BASE_REG: usize = 0xA450_0000u32 as i32 as usize;
+const AI_DRAM_ADDR: *mut u32 = (AI_BASE_REG + 0x0) as *mut u32;
+const AI_LEN: *mut u32 = (AI_BASE_REG + 0x4) as *mut u32;
+const AI_CONTROL: *mut u32 = (AI_BASE_REG + 0x8) as *mut u32;
+const AI_STATUS: *mut u32 = (AI_BASE_REG + 0xC) as *mut u32;
+const AI_DACRATE: *mut u32 = (AI_BASE_REG + 0x10) as *mut u32;
+const AI_BITRATE: *mut u32 = (AI_BASE_REG + 0x14) as *mut u32;
+
+/// Clock that drives the audio DAC on NTSC consoles
+pub const VI_NTSC_CLOCK: u32 = 48_681_812;
+
+#[bitfield(u32, default: 0)]
+#[derive(Eq, PartialEq, Debug)]
+pub struct AiStatusRead {
+    /// Both entries of the DMA FIFO are in use
+    #[bit(31, rw)]
+    pub full: bool,
+
+    /// A buffer is currently being played
+    #[bit(30, rw)]
+    pub busy: bool,
+
+    #[bit(25, rw)]
+    pub enabled: bool,
+
+    /// Mirror of bit 31
+    #[bit(0, rw)]
+    pub full2: bool,
+}
+
+#[bitfield(u32, default: 0)]
+pub struct AiControl {
+    #[bit(0, rw)]
+    pub dma_enable: bool,
+}
+
+pub struct Ai {}
+
+impl Ai {
+    pub fn set_dram_address(value: u32) {
+        unsafe { AI_DRAM_ADDR.write_volatile(value) }
+    }
+
+    /// Queues a buffer into the DMA FIFO. The address has to be written before
+    pub fn set_length(value: u32) {
+        unsafe { AI_LEN.write_volatile(value) }
+    }
+
+    /// Returns the number of bytes that are left to play in the current buffer
+    pub fn length() -> u32 {
+        unsafe { AI_LEN.read_volatile() }
+    }
+
+    pub fn set_control(value: AiControl) {
+        unsafe { AI_CONTROL.write_volatile(value.raw_value()) }
+    }
+
+    pub fn status() -> AiStatusRead {
+        AiStatusRead::new_with_raw_value(unsafe { AI_STATUS.read_volatile() })
+    }
+
+    /// Any write to AI_STATUS acknowledges the AI interrupt
+    pub fn clear_interrupt() {
+        unsafe { AI_STATUS.write_volatile(0) }
+    }
+
+    pub fn set_dac_rate(value: u32) {
+        unsafe { AI_DACRATE.write_volatile(value) }
+    }
+
+    pub fn set_bit_rate(value: u32) {
+        unsafe { AI_BITRATE.write_volatile(value) }
+    }
+
+    /// Programs DACRATE and BITRATE for the given sample frequency (in Hz)
+    pub fn set_frequency(frequency: u32) {
+        let dac_rate = ((VI_NTSC_CLOCK + frequency / 2) / frequency) - 1;
+        let bit_rate = (dac_rate / 66).clamp(1, 16);
+        Self::set_dac_rate(dac_rate);
+        Self::set_bit_rate(bit_rate - 1);
+    }
+}
@@ -1,7 +1,7 @@
 use alloc::format;
 use alloc::string::{String, ToString};
 use core::arch::asm;
-use arbitrary_int::{u19, u2, u27, u31, u41};
+use arbitrary_int::{u19, u2, u27, u29, u31, u41};
 use bitbybit::{bitenum, bitfield};
 use crate::exception_handler::expect_exception;
 
@@ -116,28 +116,28 @@ pub struct Status {
     #[bit(18, rw)]
     cop0_condition : bool,
 
-    #[bit(15, r)]
+    #[bit(15, rw)]
     interrupt_mask_compare : bool,
 
-    #[bit(14, r)]
+    #[bit(14, rw)]
     interrupt_mask_int4 : bool,
 
-    #[bit(13, r)]
+    #[bit(13, rw)]
     interrupt_mask_int3 : bool,
 
-    #[bit(12, r)]
+    #[bit(12, rw)]
     interrupt_mask_int2 : bool,
 
-    #[bit(11, r)]
+    #[bit(11, rw)]
     interrupt_mask_int1 : bool,
 
-    #[bit(10, r)]
+    #[bit(10, rw)]
     interrupt_mask_int0 : bool,
 
-    #[bit(9, r)]
+    #[bit(9, rw)]
     interrupt_mask_sw2 : bool,
 
-    #[bit(8, r)]
+    #[bit(8, rw)]
     interrupt_mask_sw1 : bool,
 
     #[bit(7, rw)]
@@ -205,6 +205,29 @@ impl XContext {
     }
 }
 
+/// Bits 3..=31 of the watched physical address. The upper bits 32..=35 live in WatchHi
+#[bitfield(u32, default: 0)]
+#[derive(Debug, Eq, PartialEq)]
+pub struct WatchLo {
+    #[bits(3..=31, rw)]
+    paddr0: u29,
+
+    #[bit(1, rw)]
+    read: bool,
+
+    #[bit(0, rw)]
+    write: bool,
+}
+
+impl WatchLo {
+    pub fn from_physical_address(a: u32, read: bool, write: bool) -> Self {
+        Self::new()
+            .with_paddr0(u29::extract_u32(a, 3))
+            .with_read(read)
+            .with_write(write)
+    }
+}
+
 
 #[bitenum(u5, exhaustive: false)]
 #[derive(PartialEq, Eq, Debug)]
@@ -399,6 +422,17 @@ pub fn count() -> u32 {
     unsafe { read_cop0::<INDEX>() }
 }
 
+pub fn compare() -> u32 {
+    const INDEX: u32 = RegisterIndex::Compare as u32;
+    unsafe { read_cop0::<INDEX>() }
+}
+
+/// Writing Compare also acknowledges the timer interrupt (Cause.IP7)
+pub unsafe fn set_compare(value: u32) {
+    const INDEX: u32 = RegisterIndex::Compare as u32;
+    unsafe { write_cop0::<INDEX>(value) }
+}
+
 pub fn entry_hi() -> u64 {
     const INDEX: u32 = RegisterIndex::EntryHi as u32;
     unsafe { read_cop0_64::<INDEX>() }
@@ -409,6 +443,11 @@ pub unsafe fn set_entry_hi(value: u64) {
     unsafe { write_cop0_64::<INDEX>(value) }
 }
 
+pub fn cause() -> Cause {
+    const INDEX: u32 = RegisterIndex::Cause as u32;
+    Cause::new_with_raw_value(unsafe { read_cop0::<INDEX>() })
+}
+
 pub fn status() -> Status {
     const INDEX: u32 = RegisterIndex::Status as u32;
     Status::new_with_raw_value(unsafe { read_cop0::<INDEX>() })
@@ -469,6 +508,35 @@ pub unsafe fn set_lladdr(value: u64) {
     unsafe { write_cop0_64::<INDEX>(value) }
 }
 
+pub fn watch_lo() -> WatchLo {
+    const INDEX: u32 = RegisterIndex::WatchLo as u32;
+    WatchLo::new_with_raw_value(unsafe { read_cop0::<INDEX>() })
+}
+
+pub fn watch_lo_32() -> u32 {
+    watch_lo().raw_value()
+}
+
+/// A load or store to the watched doubleword raises a Watch exception (unless Status.EXL is set)
+pub unsafe fn set_watch_lo(value: WatchLo) {
+    unsafe { set_watch_lo_32(value.raw_value()) }
+}
+
+pub unsafe fn set_watch_lo_32(value: u32) {
+    const INDEX: u32 = RegisterIndex::WatchLo as u32;
+    unsafe { write_cop0::<INDEX>(value) }
+}
+
+pub fn watch_hi() -> u32 {
+    const INDEX: u32 = RegisterIndex::WatchHi as u32;
+    unsafe { read_cop0::<INDEX>() }
+}
+
+pub unsafe fn set_watch_hi(value: u32) {
+    const INDEX: u32 = RegisterIndex::WatchHi as u32;
+    unsafe { write_cop0::<INDEX>(value) }
+}
+
 pub fn xcontext() -> XContext {
     const INDEX: u32 = RegisterIndex::XContext as u32;
     XContext::new_with_raw_value(unsafe { read_cop0_64::<INDEX>() })
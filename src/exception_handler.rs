@@ -4,6 +4,7 @@ use core::ops::{Deref, DerefMut};
 
 use spinning_top::Spinlock;
 
+use crate::assembler::{Assembler, GPR};
 use crate::cop0::{Cause, CauseException, Context, XContext};
 use crate::cop1::FCSR;
 use crate::graphics::color::Color;
@@ -11,7 +12,7 @@ use crate::graphics::cursor::Cursor;
 use crate::graphics::font::Font;
 use crate::graphics::system_font::FONT_GENEVA_9;
 use crate::graphics::vi::PixelType;
-use crate::VIDEO;
+use crate::{MemoryMap, VIDEO};
 use crate::emux;
 
 use super::cop0;
@@ -20,7 +21,8 @@ static EXCEPTION_SKIP: Spinlock<Option<u64>> = Spinlock::new(None);
 
 #[derive(Copy, Clone)]
 struct ExceptionReturnOverride {
-    return_to: u64,
+    /// If None, the return address is determined as usual (by skipping instructions)
+    return_to: Option<u64>,
     status: u32,
 }
 
@@ -257,7 +259,9 @@ extern "C" fn exception_handler_compiled(stackpointer: usize) -> usize {
 
         let mut return_override = EXCEPTION_RETURN_OVERRIDE.lock();
         if let Some(override_data) = *return_override {
-            context.return_to = override_data.return_to;
+            if let Some(return_to) = override_data.return_to {
+                context.return_to = return_to;
+            }
             context.status = override_data.status;
             *return_override = None;
         }
@@ -290,7 +294,15 @@ extern "C" fn exception_handler_compiled(stackpointer: usize) -> usize {
 
 pub fn set_exception_return_override(return_to: u64, status: u32) {
     let mut guard = EXCEPTION_RETURN_OVERRIDE.lock();
-    *guard = Some(ExceptionReturnOverride { return_to, status });
+    *guard = Some(ExceptionReturnOverride { return_to: Some(return_to), status });
+}
+
+/// Returns from the next exception with the given Status, but at the usual address. This is
+/// needed for interrupts: Returning with the original Status would immediately take the
+/// still-pending interrupt again.
+pub fn set_exception_status_override(status: u32) {
+    let mut guard = EXCEPTION_RETURN_OVERRIDE.lock();
+    *guard = Some(ExceptionReturnOverride { return_to: None, status });
 }
 
 pub fn clear_exception_return_override() {
@@ -405,6 +417,43 @@ pub fn install_exception_handlers() {
     cop0::icache_invalidate_all();
 }
 
+/// Reported as k0_exception_vector for exceptions that were taken with Status.BEV set (see
+/// [install_bootstrap_trampoline])
+pub const BOOTSTRAP_TRAMPOLINE_VECTOR: u64 = 0xFFFFFFFF_BFC007C0;
+
+/// With Status.BEV set, exceptions go to 0xBFC00200 (TLB refill), 0xBFC00280 (XTLB refill) and 0xBFC00380
+/// (everything else). Those are in PIF ROM, which is locked after boot and reads back as zero. The CPU therefore
+/// runs through NOPs until it reaches PIFRAM at 0xBFC007C0. This puts a few instructions at the start of PIFRAM that
+/// jump into the regular handler (using k1, as k0 is reported as exception vector).
+/// Returns the overwritten PIFRAM contents, which should be given to [uninstall_bootstrap_trampoline]. The PIF
+/// command byte at the end of PIFRAM isn't touched
+pub fn install_bootstrap_trampoline() -> [u32; 5] {
+    let mut saved = [0u32; 5];
+    for (i, word) in saved.iter_mut().enumerate() {
+        *word = unsafe { MemoryMap::uncached_pifram_address::<u32>(i << 2).read_volatile() };
+    }
+
+    let handler = exception_handler_generic as usize as u32;
+    let trampoline = [
+        Assembler::make_lui(GPR::K1, (handler >> 16) as u16),
+        Assembler::make_ori(GPR::K1, GPR::K1, handler as u16),
+        Assembler::make_lui(GPR::K0, (BOOTSTRAP_TRAMPOLINE_VECTOR >> 16) as u16),
+        Assembler::make_jr(GPR::K1),
+        Assembler::make_ori(GPR::K0, GPR::K0, BOOTSTRAP_TRAMPOLINE_VECTOR as u16),  // delay slot
+    ];
+    for (i, instruction) in trampoline.iter().enumerate() {
+        unsafe { MemoryMap::uncached_pifram_address::<u32>(i << 2).write_volatile(*instruction); }
+    }
+
+    saved
+}
+
+pub fn uninstall_bootstrap_trampoline(saved: [u32; 5]) {
+    for (i, word) in saved.iter().enumerate() {
+        unsafe { MemoryMap::uncached_pifram_address::<u32>(i << 2).write_volatile(*word); }
+    }
+}
+
 /// Attempts to take over video and show various cop0 registers.
 fn show_bluescreen_of_death(context: &ExceptionContext) -> ! {
     let font = &Font::from_data(&FONT_GENEVA_9).unwrap();
@@ -1,5 +1,5 @@
 use core::fmt::{Debug, Formatter};
-use arbitrary_int::u5;
+use arbitrary_int::{u4, u5};
 use bitbybit::bitfield;
 
 pub trait Color {
@@ -142,3 +142,136 @@ impl Color for ARGB8888 {
         ARGB8888::with_alpha(self, field_value)
     }
 }
+
+/// Intensity (greyscale) of an RGB color. Simply the average of the three channels
+const fn intensity(value: ARGB8888) -> u8 {
+    ((value.red() as u16 + value.green() as u16 + value.blue() as u16) / 3) as u8
+}
+
+/// 8 bit intensity without alpha
+#[bitfield(u8)]
+#[derive(PartialEq, Eq)]
+pub struct I8 {
+    #[bits(0..=7, rw)]
+    intensity: u8,
+}
+
+impl I8 {
+    pub const fn new(intensity: u8) -> Self {
+        Self::new_with_raw_value(intensity)
+    }
+
+    pub const fn from_argb8888(value: ARGB8888) -> Self {
+        Self::new(intensity(value))
+    }
+}
+
+impl Color for I8 {
+    const WHITE: Self = Self::from_argb8888(ARGB8888::WHITE);
+    const BLACK: Self = Self::from_argb8888(ARGB8888::BLACK);
+
+    const RED: Self = Self::from_argb8888(ARGB8888::RED);
+    const GREEN: Self = Self::from_argb8888(ARGB8888::GREEN);
+    const BLUE: Self = Self::from_argb8888(ARGB8888::BLUE);
+
+    fn with_alpha(&self, _field_value: u8) -> Self {
+        // No alpha to set
+        *self
+    }
+}
+
+impl Debug for I8 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        // Simply pass-through to raw_value for the most compact representation
+        self.raw_value.fmt(f)
+    }
+}
+
+/// 8 bit intensity followed by 8 bit alpha
+#[bitfield(u16)]
+#[derive(PartialEq, Eq)]
+pub struct IA88 {
+    #[bits(8..=15, rw)]
+    intensity: u8,
+
+    #[bits(0..=7, rw)]
+    alpha: u8,
+}
+
+impl IA88 {
+    pub const fn new(intensity: u8, alpha: u8) -> Self {
+        Self::new_with_raw_value(0)
+            .with_intensity(intensity)
+            .with_alpha(alpha)
+    }
+
+    pub const fn from_argb8888(value: ARGB8888) -> Self {
+        Self::new(intensity(value), value.alpha())
+    }
+}
+
+impl Color for IA88 {
+    const WHITE: Self = Self::from_argb8888(ARGB8888::WHITE);
+    const BLACK: Self = Self::from_argb8888(ARGB8888::BLACK);
+
+    const RED: Self = Self::from_argb8888(ARGB8888::RED);
+    const GREEN: Self = Self::from_argb8888(ARGB8888::GREEN);
+    const BLUE: Self = Self::from_argb8888(ARGB8888::BLUE);
+
+    fn with_alpha(&self, field_value: u8) -> Self {
+        IA88::with_alpha(self, field_value)
+    }
+}
+
+impl Debug for IA88 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        // Simply pass-through to raw_value for the most compact representation
+        self.raw_value.fmt(f)
+    }
+}
+
+/// 4 bit intensity followed by 4 bit alpha
+#[bitfield(u8)]
+#[derive(PartialEq, Eq)]
+pub struct IA44 {
+    #[bits(4..=7, rw)]
+    intensity: u4,
+
+    #[bits(0..=3, rw)]
+    alpha: u4,
+}
+
+impl IA44 {
+    pub const fn new(intensity: u4, alpha: u4) -> Self {
+        Self::new_with_raw_value(0)
+            .with_intensity(intensity)
+            .with_alpha(alpha)
+    }
+
+    pub const fn from_argb8888(value: ARGB8888) -> Self {
+        Self::new(
+            u4::extract_u8(intensity(value), 4),
+            u4::extract_u8(value.alpha(), 4),
+        )
+    }
+}
+
+impl Color for IA44 {
+    const WHITE: Self = Self::from_argb8888(ARGB8888::WHITE);
+    const BLACK: Self = Self::from_argb8888(ARGB8888::BLACK);
+
+    const RED: Self = Self::from_argb8888(ARGB8888::RED);
+    const GREEN: Self = Self::from_argb8888(ARGB8888::GREEN);
+    const BLUE: Self = Self::from_argb8888(ARGB8888::BLUE);
+
+    fn with_alpha(&self, field_value: u8) -> Self {
+        IA44::with_alpha(self, u4::extract_u8(field_value, 4))
+    }
+}
+
+impl Debug for IA44 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        // Simply pass-through to raw_value for the most compact representation
+        self.raw_value.fmt(f)
+    }
+}
@@ -19,7 +19,8 @@ pub struct Video {
 }
 
 #[allow(dead_code)]
-enum RegisterOffset {
+#[derive(Copy, Clone, Debug)]
+pub enum RegisterOffset {
     Status = 0x00,
     DRAMAddress = 0x04,
     HWidth = 0x08,
@@ -85,6 +86,16 @@ impl Video {
         }
     }
 
+    /// Reads a VI register directly
+    pub fn register(reg: RegisterOffset) -> u32 {
+        unsafe { VI_BASE_REG.add(reg as usize >> 2).read_volatile() }
+    }
+
+    /// Writes a VI register directly. Use with care: this affects the display
+    pub fn set_register(reg: RegisterOffset, value: u32) {
+        unsafe { VI_BASE_REG.add(reg as usize >> 2).write_volatile(value) }
+    }
+
     pub fn framebuffers(&self) -> &FramebufferImages<PixelType> { &self.framebuffers }
 
     pub fn alloc_framebuffer(&self) {
@@ -3,6 +3,7 @@ use crate::pi::Pi;
 const WRITE_LEN: *mut u32 = 0xB3FF0014 as *mut u32;
 const BUF: *mut u32 = 0xB3FF0020 as *mut u32;
 const CHUNK: usize = 0x200;
+const TEST_FILTER: *const u32 = 0xB3FF1000 as *const u32;
 
 #[inline(always)]
 fn pi_wait() {
@@ -48,3 +49,18 @@ pub(crate) fn text_out(s: &str) {
         unsafe { WRITE_LEN.write_volatile(chunk.len() as u32) };
     }
 }
+
+/// Reads the test filter block that an emulator can provide within the ISViewer memory.
+/// Returns None if there is no ISViewer. The caller is expected to validate the contents.
+pub(crate) fn read_test_filter<const SIZE: usize>() -> Option<[u8; SIZE]> {
+    if !detect() {
+        return None;
+    }
+    let mut result = [0u8; SIZE];
+    for (i, chunk) in result.chunks_mut(4).enumerate() {
+        pi_wait();
+        let word = unsafe { TEST_FILTER.add(i).read_volatile() };
+        chunk.copy_from_slice(&word.to_be_bytes()[..chunk.len()]);
+    }
+    Some(result)
+}
@@ -28,6 +28,7 @@ use crate::graphics::vi::Video;
 use crate::memory_map::MemoryMap;
 use crate::rsp::spmem::SPMEM;
 
+mod ai;
 mod allocator;
 mod assembler;
 mod cop0;
@@ -37,6 +38,7 @@ mod exception_handler;
 mod graphics;
 mod isviewer;
 mod sc64;
+mod si;
 mod math;
 mod memory_map;
 mod mi;
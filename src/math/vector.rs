@@ -90,3 +90,141 @@ impl Default for Vector {
     fn default() -> Self { Self::new() }
 }
 
+
+/// The three flag registers of the vector unit. For VCO and VCC, the low byte holds one bit per lane and the high
+/// byte holds another bit per lane. VCE only has one bit per lane
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct VectorFlags {
+    pub vco: u16,
+    pub vcc: u16,
+    pub vce: u8,
+}
+
+/// The flags of a single lane, using the names from the VCH/VCL descriptions
+#[derive(Copy, Clone)]
+struct LaneFlags {
+    /// VCO low: Sign of vs and vt differ
+    sign: bool,
+    /// VCO high: Not equal
+    not_equal: bool,
+    /// VCC low: Less than or equal (result of the compare for VLT/VEQ/VNE/VGE)
+    less_equal: bool,
+    /// VCC high: Greater than or equal
+    greater_equal: bool,
+    /// VCE: vs + vt == -1 (only written by VCH)
+    vce: bool,
+}
+
+impl VectorFlags {
+    fn lane(&self, i: usize) -> LaneFlags {
+        LaneFlags {
+            sign: ((self.vco >> i) & 1) != 0,
+            not_equal: ((self.vco >> (8 + i)) & 1) != 0,
+            less_equal: ((self.vcc >> i) & 1) != 0,
+            greater_equal: ((self.vcc >> (8 + i)) & 1) != 0,
+            vce: ((self.vce >> i) & 1) != 0,
+        }
+    }
+
+    fn set_lane(&mut self, i: usize, lane: LaneFlags) {
+        let mask16 = (1u16 << i) | (1u16 << (8 + i));
+        self.vco = (self.vco & !mask16) | ((lane.sign as u16) << i) | ((lane.not_equal as u16) << (8 + i));
+        self.vcc = (self.vcc & !mask16) | ((lane.less_equal as u16) << i) | ((lane.greater_equal as u16) << (8 + i));
+        self.vce = (self.vce & !(1u8 << i)) | ((lane.vce as u8) << i);
+    }
+}
+
+/// The select and clip instructions, which are the ones that both read and write the flags
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VectorCompareOp {
+    VLT,
+    VEQ,
+    VNE,
+    VGE,
+    VCL,
+    VCH,
+    VCR,
+}
+
+impl VectorCompareOp {
+    /// Software model of the instruction. The element specifier is applied to vt. Returns the result vector (which
+    /// the RSP also writes into the low 16 bits of the accumulator) and the new flags
+    pub fn compute(&self, vs: &Vector, vt: &Vector, e: Element, flags: VectorFlags) -> (Vector, VectorFlags) {
+        let mut result = Vector::new();
+        let mut new_flags = flags;
+        for i in 0..8 {
+            let (value, lane) = self.compute_lane(vs.get16(i), vt.get16(e.get_effective_element_index(i)), flags.lane(i));
+            result.set16(i, value);
+            new_flags.set_lane(i, lane);
+        }
+        (result, new_flags)
+    }
+
+    fn compute_lane(&self, s: u16, t: u16, flags: LaneFlags) -> (u16, LaneFlags) {
+        let si = s as i16 as i32;
+        let ti = t as i16 as i32;
+        let equal = s == t;
+        match self {
+            // The selects only set VCC low. VCO and VCC high are cleared, VCE is left alone
+            VectorCompareOp::VLT | VectorCompareOp::VEQ | VectorCompareOp::VNE | VectorCompareOp::VGE => {
+                let (condition, value) = match self {
+                    VectorCompareOp::VLT => {
+                        let lt = (si < ti) || (equal && flags.sign && flags.not_equal);
+                        (lt, if lt { s } else { t })
+                    }
+                    VectorCompareOp::VEQ => (equal && !flags.not_equal, t),
+                    VectorCompareOp::VNE => (!equal || flags.not_equal, s),
+                    _ => {
+                        let ge = (si > ti) || (equal && !(flags.sign && flags.not_equal));
+                        (ge, if ge { s } else { t })
+                    }
+                };
+                (value, LaneFlags { sign: false, not_equal: false, less_equal: condition, greater_equal: false, vce: flags.vce })
+            }
+            // VCL continues a VCH: It uses the flags that VCH left behind and only updates VCC where VCO high is clear
+            VectorCompareOp::VCL => {
+                let mut less_equal = flags.less_equal;
+                let mut greater_equal = flags.greater_equal;
+                let value = if flags.sign {
+                    if !flags.not_equal {
+                        let (sum, carry) = s.overflowing_add(t);
+                        less_equal = if flags.vce { (sum == 0) || !carry } else { (sum == 0) && !carry };
+                    }
+                    if less_equal { (t as i16).wrapping_neg() as u16 } else { s }
+                } else {
+                    if !flags.not_equal {
+                        greater_equal = s >= t;
+                    }
+                    if greater_equal { t } else { s }
+                };
+                (value, LaneFlags { sign: false, not_equal: false, less_equal, greater_equal, vce: false })
+            }
+            VectorCompareOp::VCH => {
+                let sign = (si ^ ti) < 0;
+                if sign {
+                    let sum = si + ti;
+                    let less_equal = sum <= 0;
+                    let value = if less_equal { (-ti) as u16 } else { s };
+                    (value, LaneFlags { sign, not_equal: (sum != 0) && (s != !t), less_equal, greater_equal: ti < 0, vce: sum == -1 })
+                } else {
+                    let difference = si - ti;
+                    let greater_equal = difference >= 0;
+                    let value = if greater_equal { t } else { s };
+                    (value, LaneFlags { sign, not_equal: difference != 0, less_equal: ti < 0, greater_equal, vce: false })
+                }
+            }
+            // Like VCH, but for one's complement. Doesn't leave anything behind for VCL
+            VectorCompareOp::VCR => {
+                if (si ^ ti) < 0 {
+                    let less_equal = (si + ti) < 0;
+                    let value = if less_equal { !t } else { s };
+                    (value, LaneFlags { sign: false, not_equal: false, less_equal, greater_equal: ti < 0, vce: false })
+                } else {
+                    let greater_equal = (si - ti) >= 0;
+                    let value = if greater_equal { t } else { s };
+                    (value, LaneFlags { sign: false, not_equal: false, less_equal: ti < 0, greater_equal, vce: false })
+                }
+            }
+        }
+    }
+}
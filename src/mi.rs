@@ -13,61 +13,62 @@ enum RegisterOffset {
 #[bitfield(u32, default: 0)]
 pub struct InterruptMaskWrite {
     #[bit(11, w)]
-    setDP: bool,
+    pub setDP: bool,
 
     #[bit(10, w)]
-    clearDP: bool,
+    pub clearDP: bool,
 
     #[bit(9, w)]
-    setPI: bool,
+    pub setPI: bool,
 
     #[bit(8, w)]
-    clearPI: bool,
+    pub clearPI: bool,
 
     #[bit(7, w)]
-    setVI: bool,
+    pub setVI: bool,
 
     #[bit(6, w)]
-    clearVI: bool,
+    pub clearVI: bool,
 
     #[bit(5, w)]
-    setAI: bool,
+    pub setAI: bool,
 
     #[bit(4, w)]
-    clearAI: bool,
+    pub clearAI: bool,
 
     #[bit(3, w)]
-    setSI: bool,
+    pub setSI: bool,
 
     #[bit(2, w)]
-    clearSI: bool,
+    pub clearSI: bool,
 
     #[bit(1, w)]
-    setSP: bool,
+    pub setSP: bool,
 
     #[bit(0, w)]
-    clearSP: bool,
+    pub clearSP: bool,
 }
 
 #[bitfield(u32, default: 0)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct Interrupt {
     #[bit(5, rw)]
-    dp: bool,
+    pub dp: bool,
 
     #[bit(4, rw)]
-    pi: bool,
+    pub pi: bool,
 
     #[bit(3, rw)]
-    vi: bool,
+    pub vi: bool,
 
     #[bit(2, rw)]
-    ai: bool,
+    pub ai: bool,
 
     #[bit(1, rw)]
-    si: bool,
+    pub si: bool,
 
     #[bit(0, rw)]
-    sp: bool,
+    pub sp: bool,
 }
 
 
@@ -79,7 +80,7 @@ fn write(reg: RegisterOffset, value: u32) {
     unsafe { MI_BASE_REG.add(reg as usize >> 2).write_volatile(value) }
 }
 
-fn interrupt() -> Interrupt {
+pub fn interrupt() -> Interrupt {
     Interrupt::new_with_raw_value(read(RegisterOffset::Interrupt))
 }
 
@@ -98,10 +99,36 @@ pub fn clear_interrupt_mask() {
     );
 }
 
-#[allow(dead_code)]
 pub fn interrupt_mask() -> Interrupt {
     Interrupt::new_with_raw_value(read(RegisterOffset::InterruptMask))
 }
 
+/// Sets exactly the given set of interrupts in MI_INTR_MASK
+pub fn restore_interrupt_mask(mask: Interrupt) {
+    clear_interrupt_mask();
+    set_interrupt_mask(InterruptMaskWrite::new()
+        .with_setDP(mask.dp())
+        .with_setPI(mask.pi())
+        .with_setVI(mask.vi())
+        .with_setAI(mask.ai())
+        .with_setSI(mask.si())
+        .with_setSP(mask.sp())
+    );
+}
+
+/// The DP interrupt (raised by SYNC_FULL) is acknowledged through MI_MODE
+pub fn clear_dp_interrupt() {
+    write(RegisterOffset::Mode, 1 << 11);
+}
+
 pub fn is_sp_interrupt() -> bool { interrupt().sp() }
 
+pub fn is_dp_interrupt() -> bool { interrupt().dp() }
+
+pub fn is_pi_interrupt() -> bool { interrupt().pi() }
+
+pub fn is_ai_interrupt() -> bool { interrupt().ai() }
+
+pub fn is_si_interrupt() -> bool { interrupt().si() }
+
+pub fn is_vi_interrupt() -> bool { interrupt().vi() }
@@ -0,0 +1,254 @@
+//! Turns an RDP command list back into readable text. Failing RDP tests print the list that they ran, so that it can
+//! be replayed in an emulator's RDP without having to run the test ROM. Every line shows the index of the 64 bit
+//! word, its raw value and the decoded fields. Multi-word commands (triangles and texture rectangles) print one line
+//! per word.
+
+use alloc::format;
+use alloc::string::String;
+use core::fmt::Debug;
+
+use crate::math::bits::Bitmasks32;
+use crate::rdp::fixedpoint::{I11_5, I12_2, I16_16, I6_10, U10_2};
+use crate::rdp::modes::{CombineMode, Othermode, TileDescriptor};
+use crate::rdp::rdp_assembler::RDPCommand;
+use crate::MemoryMap;
+
+const fn bits(word: u64, shift: u32, mask: u32) -> u32 {
+    ((word >> shift) as u32) & mask
+}
+
+fn u10_2(word: u64, shift: u32) -> U10_2 {
+    U10_2::new_with_masked_value(bits(word, shift, Bitmasks32::M12))
+}
+
+fn i12_2(word: u64, shift: u32) -> I12_2 {
+    I12_2::new_with_masked_value(bits(word, shift, Bitmasks32::M14))
+}
+
+fn i16_16(word: u64, shift: u32) -> I16_16 {
+    I16_16::new_with_masked_value((word >> shift) as u32)
+}
+
+/// Shade and texture coefficients are split into two words: One with the integer parts, one with the fractions.
+/// Channel 0 is in the upper 16 bits
+fn coefficient(integers: u64, fractions: u64, channel: u32) -> I16_16 {
+    let shift = 48 - 16 * channel;
+    I16_16::new_with_masked_value((bits(integers, shift, Bitmasks32::M16) << 16) | bits(fractions, shift, Bitmasks32::M16))
+}
+
+fn coefficients(integers: u64, fractions: u64, names: &[&str; 4]) -> String {
+    format!("{}={:?} {}={:?} {}={:?} {}={:?}",
+            names[0], coefficient(integers, fractions, 0),
+            names[1], coefficient(integers, fractions, 1),
+            names[2], coefficient(integers, fractions, 2),
+            names[3], coefficient(integers, fractions, 3))
+}
+
+fn rgba(word: u64) -> String {
+    format!("rgba=0x{:08X}", word as u32)
+}
+
+/// The fields of a rectangle in the order SET_SCISSOR, SET_TILE_SIZE, LOAD_TILE and LOAD_TLUT use
+fn top_left_first(word: u64) -> String {
+    format!("left={:?} top={:?} right={:?} bottom={:?}", u10_2(word, 44), u10_2(word, 32), u10_2(word, 12), u10_2(word, 0))
+}
+
+/// The fields of a rectangle in the order FILL_RECTANGLE and TEXTURE_RECTANGLE use
+fn bottom_right_first(word: u64) -> String {
+    format!("left={:?} top={:?} right={:?} bottom={:?}", u10_2(word, 12), u10_2(word, 0), u10_2(word, 44), u10_2(word, 32))
+}
+
+fn image(word: u64) -> String {
+    format!("format={} size={} width={} address=0x{:06X}", bits(word, 53, Bitmasks32::M3), bits(word, 51, Bitmasks32::M2), bits(word, 32, Bitmasks32::M10) + 1, bits(word, 0, Bitmasks32::M26))
+}
+
+/// Fields that are declared as Option<> in a bitfield read back as Result. Invalid values are shown as a number
+fn option<T: Debug>(value: Result<T, u8>) -> String {
+    match value {
+        Ok(v) => format!("{:?}", v),
+        Err(raw) => format!("Invalid({})", raw),
+    }
+}
+
+fn othermode(word: u64) -> String {
+    let m = Othermode::new_with_raw_value(word & 0x00FF_FFFF_FFFF_FFFF);
+    format!("cycle_type={:?} atomic={} perspective={} detail={} sharpen={} lod={} tlut={} tlut_type={:?} sample_2x2={} mid_texel={} \
+             bi_lerp_0={} bi_lerp_1={} convert_one={} key={} rgb_dither={:?} alpha_dither={:?} \
+             blender_0=({:?} * {:?} + {:?} * {:?}) blender_1=({:?} * {:?} + {:?} * {:?}) \
+             force_blend={} alpha_coverage_select={} coverage_times_alpha={} z_mode={:?} coverage_mode={:?} \
+             color_on_coverage={} image_read={} z_update={} z_compare={} antialias={} z_source_primitive={} \
+             alpha_compare_dither={} alpha_compare={}",
+            m.cycle_type(), m.atomic_primitive(), m.perspective_texture(), m.detail_texture(), m.sharpen_texture(),
+            m.texture_lod(), m.tlut_enable(), m.tlut_type(), m.sample_2x2(), m.mid_texel(),
+            m.bi_lerp_0(), m.bi_lerp_1(), m.convert_one(), m.key_enable(), m.rgb_dither_mode(), m.alpha_dither_mode(),
+            m.blender_0p(), m.blender_0a(), m.blender_0m(), m.blender_0b(),
+            m.blender_1p(), m.blender_1a(), m.blender_1m(), m.blender_1b(),
+            m.force_blend(), m.alpha_coverage_select(), m.coverage_times_alpha(), m.z_mode(), m.coverage_mode(),
+            m.color_on_coverage(), m.image_read(), m.z_update(), m.z_compare(), m.antialias(), m.z_source_primitive(),
+            m.alpha_compare_dither(), m.alpha_compare())
+}
+
+fn combine_mode(word: u64) -> String {
+    let c = CombineMode::new_with_raw_value(word & 0x00FF_FFFF_FFFF_FFFF);
+    format!("rgb_0=({} - {}) * {} + {:?} alpha_0=({:?} - {:?}) * {:?} + {:?} \
+             rgb_1=({} - {}) * {} + {:?} alpha_1=({:?} - {:?}) * {:?} + {:?}",
+            option(c.rgb_0a()), option(c.rgb_0b()), option(c.rgb_0c()), c.rgb_0d(),
+            c.alpha_0a(), c.alpha_0b(), c.alpha_0c(), c.alpha_0d(),
+            option(c.rgb_1a()), option(c.rgb_1b()), option(c.rgb_1c()), c.rgb_1d(),
+            c.alpha_1a(), c.alpha_1b(), c.alpha_1c(), c.alpha_1d())
+}
+
+fn tile_descriptor(word: u64) -> String {
+    let t = TileDescriptor::new_with_raw_value(word & 0x00FF_FFFF_FFFF_FFFF);
+    format!("format={} size={:?} line={} tmem_address=0x{:03X} tile={} palette={} \
+             clamp_t={} mirror_t={} mask_t={} shift_t={} clamp_s={} mirror_s={} mask_s={} shift_s={}",
+            option(t.format()), t.pixel_size(), t.line().value(), t.tmem_address().value(), t.tile().value(), t.palette().value(),
+            t.clamp_t(), t.mirror_t(), t.mask_t().value(), t.shift_t().value(),
+            t.clamp_s(), t.mirror_s(), t.mask_s().value(), t.shift_s().value())
+}
+
+/// Number of 64 bit words that a command with the given id occupies
+pub fn word_count(id: u8) -> usize {
+    match RDPCommand::from_id(id) {
+        Some(RDPCommand::TexturedRectangle) | Some(RDPCommand::FlippedTexturedRectangle) => 2,
+        _ if (8..=15).contains(&id) => {
+            4 + if (id & 4) != 0 { 8 } else { 0 } + if (id & 2) != 0 { 8 } else { 0 } + if (id & 1) != 0 { 2 } else { 0 }
+        }
+        _ => 1,
+    }
+}
+
+/// Decodes the first word of a command
+fn decode_command(command: &RDPCommand, word: u64) -> String {
+    match command {
+        RDPCommand::FilledTriangle | RDPCommand::DepthFilledTriangle | RDPCommand::TexturedTriangle |
+        RDPCommand::TexturedDepthTriangle | RDPCommand::ShadedTriangle | RDPCommand::ShadedDepthTriangle |
+        RDPCommand::ShadedTexturedTriangle | RDPCommand::ShadedTexturedDepthTriangle => {
+            format!("right_major={} level={} tile={} yl={:?} ym={:?} yh={:?}",
+                    ((word >> 55) & 1) != 0, bits(word, 51, Bitmasks32::M3), bits(word, 48, Bitmasks32::M3),
+                    i12_2(word, 32), i12_2(word, 16), i12_2(word, 0))
+        }
+        RDPCommand::TexturedRectangle | RDPCommand::FlippedTexturedRectangle => {
+            format!("{} tile={}", bottom_right_first(word), bits(word, 24, Bitmasks32::M3))
+        }
+        RDPCommand::SyncLoad | RDPCommand::SyncPipe | RDPCommand::TileSync | RDPCommand::SyncFull => String::new(),
+        RDPCommand::SetKeyColorGreenBlue => {
+            format!("width_g=0x{:03X} width_b=0x{:03X} center_g={} scale_g={} center_b={} scale_b={}",
+                    bits(word, 44, Bitmasks32::M12), bits(word, 32, Bitmasks32::M12),
+                    bits(word, 24, Bitmasks32::M8), bits(word, 16, Bitmasks32::M8),
+                    bits(word, 8, Bitmasks32::M8), bits(word, 0, Bitmasks32::M8))
+        }
+        RDPCommand::SetKeyColorRed => {
+            format!("width_r=0x{:03X} center_r={} scale_r={}",
+                    bits(word, 16, Bitmasks32::M12), bits(word, 8, Bitmasks32::M8), bits(word, 0, Bitmasks32::M8))
+        }
+        RDPCommand::SetConvert => format!("value=0x{:014X}", word & 0x00FF_FFFF_FFFF_FFFF),
+        RDPCommand::SetScissor => {
+            format!("{} field={} odd={}", top_left_first(word), ((word >> 25) & 1) != 0, ((word >> 24) & 1) != 0)
+        }
+        RDPCommand::SetPrimitiveDepth => format!("z=0x{:04X} delta_z=0x{:04X}", bits(word, 16, Bitmasks32::M16), bits(word, 0, Bitmasks32::M16)),
+        RDPCommand::SetOtherMode => othermode(word),
+        RDPCommand::SetCombine => combine_mode(word),
+        RDPCommand::SetTile => tile_descriptor(word),
+        RDPCommand::LoadPalette | RDPCommand::SetTileSize | RDPCommand::LoadTile => {
+            format!("{} tile={}", top_left_first(word), bits(word, 24, Bitmasks32::M3))
+        }
+        RDPCommand::LoadBlock => {
+            format!("s=0x{:03X} t=0x{:03X} tile={} texels_minus_one={} dxt=0x{:03X}",
+                    bits(word, 44, Bitmasks32::M12), bits(word, 32, Bitmasks32::M12), bits(word, 24, Bitmasks32::M3),
+                    bits(word, 12, Bitmasks32::M12), bits(word, 0, Bitmasks32::M12))
+        }
+        RDPCommand::FilledRectangle => bottom_right_first(word),
+        RDPCommand::SetFillColor => format!("color=0x{:08X}", word as u32),
+        RDPCommand::SetFogColor | RDPCommand::SetBlendColor | RDPCommand::SetEnvColor => rgba(word),
+        RDPCommand::SetPrimitiveColor => {
+            format!("min_level={} lod_fraction={} {}", bits(word, 40, Bitmasks32::M5), bits(word, 32, Bitmasks32::M8), rgba(word))
+        }
+        RDPCommand::SetTextureImage | RDPCommand::SetFramebufferImage => image(word),
+        RDPCommand::SetDepthImage => format!("address=0x{:06X}", bits(word, 0, Bitmasks32::M26)),
+    }
+}
+
+/// Decodes the words that follow the first word of a command. `words` holds all words of the command
+fn decode_extra_word(id: u8, words: &[u64], index: usize) -> String {
+    let word = words[index];
+    if id == RDPCommand::TexturedRectangle as u8 || id == RDPCommand::FlippedTexturedRectangle as u8 {
+        return format!("s={:?} t={:?} dsdx={:?} dtdy={:?}",
+                       I11_5::new_with_masked_value(bits(word, 48, Bitmasks32::M16)),
+                       I11_5::new_with_masked_value(bits(word, 32, Bitmasks32::M16)),
+                       I6_10::new_with_masked_value(bits(word, 16, Bitmasks32::M16)),
+                       I6_10::new_with_masked_value(bits(word, 0, Bitmasks32::M16)));
+    }
+
+    // Triangles: Edge, shade, texture and depth coefficients in that order
+    let shade_start = 4;
+    let texture_start = shade_start + if (id & 4) != 0 { 8 } else { 0 };
+    let depth_start = texture_start + if (id & 2) != 0 { 8 } else { 0 };
+    match index {
+        1 => format!("xl={:?} dxldy={:?}", i16_16(word, 32), i16_16(word, 0)),
+        2 => format!("xh={:?} dxhdy={:?}", i16_16(word, 32), i16_16(word, 0)),
+        3 => format!("xm={:?} dxmdy={:?}", i16_16(word, 32), i16_16(word, 0)),
+        _ if index < depth_start => {
+            // Shade and texture coefficients share the same layout
+            let (start, name, names) = if index < texture_start {
+                (shade_start, "shade", ["r", "g", "b", "a"])
+            } else {
+                (texture_start, "texture", ["s", "t", "w", "unused"])
+            };
+            let labels = ["value", "d/dx", "(fractions)", "(fractions)", "d/de", "d/dy", "(fractions)", "(fractions)"];
+            let offset = index - start;
+            match offset {
+                2 | 3 | 6 | 7 => String::from(labels[offset]),
+                _ => format!("{} {}: {}", name, labels[offset], coefficients(word, words[index + 2], &names)),
+            }
+        }
+        _ if index == depth_start => format!("z={:?} dzdx={:?}", i16_16(word, 32), i16_16(word, 0)),
+        _ => format!("dzde={:?} dzdy={:?}", i16_16(word, 32), i16_16(word, 0)),
+    }
+}
+
+/// Disassembles `count` words. `read` returns the word with the given index
+pub fn disassemble<F: FnMut(usize) -> u64>(count: usize, mut read: F) -> String {
+    let mut result = String::new();
+    let mut index = 0;
+    while index < count {
+        let first = read(index);
+        let id = ((first >> 56) as u8) & 0x3F;
+        let length = word_count(id);
+        let mut words = [0u64; 22];
+        for i in 0..length.min(count - index) {
+            words[i] = read(index + i);
+        }
+
+        match RDPCommand::from_id(id) {
+            Some(command) => result.push_str(format!("{:4}: {:016X} {:?} {}\n", index, first, command, decode_command(&command, first)).as_str()),
+            None => result.push_str(format!("{:4}: {:016X} Unknown command 0x{:02X}\n", index, first, id).as_str()),
+        }
+        for i in 1..length {
+            if index + i >= count {
+                result.push_str("      (command list ends in the middle of the command)\n");
+                break;
+            }
+            result.push_str(format!("{:4}: {:016X}   {}\n", index + i, words[i], decode_extra_word(id, &words[..length], i)).as_str());
+        }
+        index += length;
+    }
+    result
+}
+
+/// Disassembles a command list in RDRAM. address is physical
+pub fn disassemble_rdram(address: usize, count: usize) -> String {
+    disassemble(count, |i| {
+        let p = MemoryMap::physical_to_uncached_mut::<u32>(address + (i << 3));
+        unsafe { ((p.read_volatile() as u64) << 32) | (p.add(1).read_volatile() as u64) }
+    })
+}
+
+/// Disassembles a command list in DMEM (as used with XBUS). 64 bit reads don't work on SPMEM, so this reads 32 bit
+/// at a time
+pub fn disassemble_dmem(offset: usize, count: usize) -> String {
+    disassemble(count, |i| {
+        let p = MemoryMap::uncached_spmem_address::<u32>((offset + (i << 3)) & 0xFFF);
+        unsafe { ((p.read_volatile() as u64) << 32) | (p.add(1).read_volatile() as u64) }
+    })
+}
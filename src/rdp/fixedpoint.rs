@@ -93,3 +93,5 @@ impl<const DIGITS_BEFORE: usize, const DIGITS_AFTER: usize> Debug for UnsignedFi
 pub type U10_2 = UnsignedFixedPoint<10, 2>;
 pub type I12_2 = SignedFixedPoint<12, 2>;
 pub type I16_16 = SignedFixedPoint<16, 16>;
+pub type I11_5 = SignedFixedPoint<11, 5>;
+pub type I6_10 = SignedFixedPoint<6, 10>;
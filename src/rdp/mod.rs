@@ -1,5 +1,7 @@
 pub mod rdp_assembler;
+pub mod disassembler;
 pub mod fixedpoint;
 pub mod modes;
 pub mod rdp;
+pub mod reference;
 
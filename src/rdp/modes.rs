@@ -1,6 +1,8 @@
+use arbitrary_int::{u3, u4, u9};
 use bitbybit::{bitenum, bitfield};
 
 #[bitenum(u2, exhaustive: true)]
+#[derive(Debug)]
 #[allow(dead_code)]
 pub enum CycleType {
     SingleCycle = 0,
@@ -10,6 +12,7 @@ pub enum CycleType {
 }
 
 #[bitenum(u2, exhaustive: true)]
+#[derive(Debug)]
 #[allow(dead_code)]
 pub enum CoverageMode {
     Clamp = 0,
@@ -19,6 +22,7 @@ pub enum CoverageMode {
 }
 
 #[bitenum(u3, exhaustive: false)]
+#[derive(Debug)]
 #[allow(dead_code)]
 pub enum Format {
     RGBA = 0,
@@ -29,6 +33,7 @@ pub enum Format {
 }
 
 #[bitenum(u2, exhaustive: true)]
+#[derive(Debug)]
 #[allow(dead_code)]
 pub enum PixelSize {
     Bits4 = 0,
@@ -38,6 +43,7 @@ pub enum PixelSize {
 }
 
 #[bitenum(u2, exhaustive: true)]
+#[derive(Debug)]
 #[allow(dead_code)]
 pub enum PM {
     CombineColor = 0,
@@ -47,6 +53,7 @@ pub enum PM {
 }
 
 #[bitenum(u2, exhaustive: true)]
+#[derive(Debug)]
 #[allow(dead_code)]
 pub enum A {
     CombineAlpha = 0,
@@ -56,6 +63,7 @@ pub enum A {
 }
 
 #[bitenum(u2, exhaustive: true)]
+#[derive(Debug)]
 #[allow(dead_code)]
 pub enum B {
     InverseA = 0,
@@ -64,11 +72,100 @@ pub enum B {
     Zero = 3
 }
 
+#[bitenum(u1, exhaustive: true)]
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum TLUTType {
+    RGBA16 = 0,
+    IA16 = 1,
+}
+
+#[bitenum(u2, exhaustive: true)]
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum RGBDitherMode {
+    MagicSquare = 0,
+    Bayer = 1,
+    Noise = 2,
+    None = 3,
+}
+
+#[bitenum(u2, exhaustive: true)]
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum AlphaDitherMode {
+    Pattern = 0,
+    InvertedPattern = 1,
+    Noise = 2,
+    None = 3,
+}
+
+#[bitenum(u2, exhaustive: true)]
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum ZMode {
+    Opaque = 0,
+    Interpenetrating = 1,
+    Transparent = 2,
+    Decal = 3,
+}
+
 #[bitfield(u64, default: 0)]
 pub struct Othermode {
+    /// If set, the RDP waits for the previous primitive to be written to memory before starting the next one
+    #[bit(55, rw)]
+    atomic_primitive: bool,
+
     #[bits(52..=53, rw)]
     cycle_type: CycleType,
 
+    #[bit(51, rw)]
+    perspective_texture: bool,
+
+    #[bit(50, rw)]
+    detail_texture: bool,
+
+    #[bit(49, rw)]
+    sharpen_texture: bool,
+
+    #[bit(48, rw)]
+    texture_lod: bool,
+
+    #[bit(47, rw)]
+    tlut_enable: bool,
+
+    #[bit(46, rw)]
+    tlut_type: TLUTType,
+
+    /// If set, four texels are sampled (bilinear filtering). Otherwise the nearest texel is used
+    #[bit(45, rw)]
+    sample_2x2: bool,
+
+    /// Together with sample_2x2, this averages the four texels (box filter) instead of interpolating
+    #[bit(44, rw)]
+    mid_texel: bool,
+
+    /// If set, texture filter 0 outputs RGB. If clear, it outputs YUV to be converted by the convert unit
+    #[bit(43, rw)]
+    bi_lerp_0: bool,
+
+    /// If set, texture filter 1 outputs RGB. If clear, it outputs YUV to be converted by the convert unit
+    #[bit(42, rw)]
+    bi_lerp_1: bool,
+
+    /// If set, the second texture convert stage uses the result of the first one (instead of texel 1)
+    #[bit(41, rw)]
+    convert_one: bool,
+
+    #[bit(40, rw)]
+    key_enable: bool,
+
+    #[bits(38..=39, rw)]
+    rgb_dither_mode: RGBDitherMode,
+
+    #[bits(36..=37, rw)]
+    alpha_dither_mode: AlphaDitherMode,
+
     #[bits(30..=31, rw)]
     blender_0p: PM,
 
@@ -93,9 +190,52 @@ pub struct Othermode {
     #[bits(16..=17, rw)]
     blender_1b: B,
 
+    /// If set, the blender equation is always evaluated and the result is not divided by (A + B). Otherwise
+    /// the blender is bypassed for fully covered pixels
+    #[bit(14, rw)]
+    force_blend: bool,
+
+    /// If set, coverage is used as alpha for the alpha compare (and blender). Otherwise alpha is used as-is
+    #[bit(13, rw)]
+    alpha_coverage_select: bool,
+
+    /// If set, coverage is multiplied by alpha
+    #[bit(12, rw)]
+    coverage_times_alpha: bool,
+
+    #[bits(10..=11, rw)]
+    z_mode: ZMode,
+
     #[bits(8..=9, rw)]
     coverage_mode: CoverageMode,
 
+    /// If set, color is only written if coverage overflows (used for decals)
+    #[bit(7, rw)]
+    color_on_coverage: bool,
+
+    /// If set, the framebuffer is read so that memory color and coverage are available to the blender
+    #[bit(6, rw)]
+    image_read: bool,
+
+    #[bit(5, rw)]
+    z_update: bool,
+
+    #[bit(4, rw)]
+    z_compare: bool,
+
+    #[bit(3, rw)]
+    antialias: bool,
+
+    /// If set, depth comes from SET_PRIM_DEPTH. Otherwise it is interpolated per pixel
+    #[bit(2, rw)]
+    z_source_primitive: bool,
+
+    /// If set, the alpha compare uses a random threshold instead of the blend color alpha
+    #[bit(1, rw)]
+    alpha_compare_dither: bool,
+
+    #[bit(0, rw)]
+    alpha_compare: bool,
 }
 
 impl Othermode {
@@ -107,7 +247,6 @@ impl Othermode {
             .with_blender_0b(value.b)
     }
 
-    #[allow(dead_code)]
     pub const fn with_blender_1(&self, value: Blender) -> Self {
         self
             .with_blender_1p(value.p)
@@ -117,6 +256,54 @@ impl Othermode {
     }
 }
 
+/// A tile descriptor as set by SET_TILE. Texture loads and texture sampling both go through a tile
+#[bitfield(u64, default: 0)]
+pub struct TileDescriptor {
+    #[bits(53..=55, rw)]
+    format: Option<Format>,
+
+    #[bits(51..=52, rw)]
+    pixel_size: PixelSize,
+
+    /// Size of a line in TMEM, in 64 bit words
+    #[bits(41..=49, rw)]
+    line: u9,
+
+    /// Address in TMEM, in 64 bit words
+    #[bits(32..=40, rw)]
+    tmem_address: u9,
+
+    #[bits(24..=26, rw)]
+    tile: u3,
+
+    #[bits(20..=23, rw)]
+    palette: u4,
+
+    #[bit(19, rw)]
+    clamp_t: bool,
+
+    #[bit(18, rw)]
+    mirror_t: bool,
+
+    #[bits(14..=17, rw)]
+    mask_t: u4,
+
+    #[bits(10..=13, rw)]
+    shift_t: u4,
+
+    #[bit(9, rw)]
+    clamp_s: bool,
+
+    #[bit(8, rw)]
+    mirror_s: bool,
+
+    #[bits(4..=7, rw)]
+    mask_s: u4,
+
+    #[bits(0..=3, rw)]
+    shift_s: u4,
+}
+
 pub struct Blender {
     a: A,
     p: PM,
@@ -126,4 +313,232 @@ pub struct Blender {
 
 impl Blender {
     pub fn new(a: A, p: PM, b: B, m: PM) -> Self { Self { a, p, b, m } }
-}
\ No newline at end of file
+}
+
+#[bitenum(u4, exhaustive: false)]
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum CombinerSubA {
+    Combined = 0,
+    Texel0 = 1,
+    Texel1 = 2,
+    Primitive = 3,
+    Shade = 4,
+    Environment = 5,
+    One = 6,
+    Noise = 7,
+    Zero = 8,
+}
+
+#[bitenum(u4, exhaustive: false)]
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum CombinerSubB {
+    Combined = 0,
+    Texel0 = 1,
+    Texel1 = 2,
+    Primitive = 3,
+    Shade = 4,
+    Environment = 5,
+    KeyCenter = 6,
+    ConvertK4 = 7,
+    Zero = 8,
+}
+
+#[bitenum(u5, exhaustive: false)]
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum CombinerMul {
+    Combined = 0,
+    Texel0 = 1,
+    Texel1 = 2,
+    Primitive = 3,
+    Shade = 4,
+    Environment = 5,
+    KeyScale = 6,
+    CombinedAlpha = 7,
+    Texel0Alpha = 8,
+    Texel1Alpha = 9,
+    PrimitiveAlpha = 10,
+    ShadeAlpha = 11,
+    EnvironmentAlpha = 12,
+    LODFraction = 13,
+    PrimitiveLODFraction = 14,
+    ConvertK5 = 15,
+    Zero = 16,
+}
+
+#[bitenum(u3, exhaustive: true)]
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum CombinerAdd {
+    Combined = 0,
+    Texel0 = 1,
+    Texel1 = 2,
+    Primitive = 3,
+    Shade = 4,
+    Environment = 5,
+    One = 6,
+    Zero = 7,
+}
+
+/// Alpha combiner inputs for a, b and d
+#[bitenum(u3, exhaustive: true)]
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum AlphaCombinerAddSub {
+    Combined = 0,
+    Texel0 = 1,
+    Texel1 = 2,
+    Primitive = 3,
+    Shade = 4,
+    Environment = 5,
+    One = 6,
+    Zero = 7,
+}
+
+#[bitenum(u3, exhaustive: true)]
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum AlphaCombinerMul {
+    LODFraction = 0,
+    Texel0 = 1,
+    Texel1 = 2,
+    Primitive = 3,
+    Shade = 4,
+    Environment = 5,
+    PrimitiveLODFraction = 6,
+    Zero = 7,
+}
+
+/// The color combiner as set by SET_COMBINE_MODE. Each cycle calculates (a - b) * c + d, separately for RGB and alpha.
+/// In 1 cycle mode, the settings of the second cycle (1) are used
+#[bitfield(u64, default: 0)]
+pub struct CombineMode {
+    #[bits(52..=55, rw)]
+    rgb_0a: Option<CombinerSubA>,
+
+    #[bits(47..=51, rw)]
+    rgb_0c: Option<CombinerMul>,
+
+    #[bits(44..=46, rw)]
+    alpha_0a: AlphaCombinerAddSub,
+
+    #[bits(41..=43, rw)]
+    alpha_0c: AlphaCombinerMul,
+
+    #[bits(37..=40, rw)]
+    rgb_1a: Option<CombinerSubA>,
+
+    #[bits(32..=36, rw)]
+    rgb_1c: Option<CombinerMul>,
+
+    #[bits(28..=31, rw)]
+    rgb_0b: Option<CombinerSubB>,
+
+    #[bits(24..=27, rw)]
+    rgb_1b: Option<CombinerSubB>,
+
+    #[bits(21..=23, rw)]
+    alpha_1a: AlphaCombinerAddSub,
+
+    #[bits(18..=20, rw)]
+    alpha_1c: AlphaCombinerMul,
+
+    #[bits(15..=17, rw)]
+    rgb_0d: CombinerAdd,
+
+    #[bits(12..=14, rw)]
+    alpha_0b: AlphaCombinerAddSub,
+
+    #[bits(9..=11, rw)]
+    alpha_0d: AlphaCombinerAddSub,
+
+    #[bits(6..=8, rw)]
+    rgb_1d: CombinerAdd,
+
+    #[bits(3..=5, rw)]
+    alpha_1b: AlphaCombinerAddSub,
+
+    #[bits(0..=2, rw)]
+    alpha_1d: AlphaCombinerAddSub,
+}
+
+impl CombineMode {
+    pub const fn with_rgb_0(&self, value: ColorCombiner) -> Self {
+        self
+            .with_rgb_0a(value.a)
+            .with_rgb_0b(value.b)
+            .with_rgb_0c(value.c)
+            .with_rgb_0d(value.d)
+    }
+
+    pub const fn with_alpha_0(&self, value: AlphaCombiner) -> Self {
+        self
+            .with_alpha_0a(value.a)
+            .with_alpha_0b(value.b)
+            .with_alpha_0c(value.c)
+            .with_alpha_0d(value.d)
+    }
+
+    pub const fn with_rgb_1(&self, value: ColorCombiner) -> Self {
+        self
+            .with_rgb_1a(value.a)
+            .with_rgb_1b(value.b)
+            .with_rgb_1c(value.c)
+            .with_rgb_1d(value.d)
+    }
+
+    pub const fn with_alpha_1(&self, value: AlphaCombiner) -> Self {
+        self
+            .with_alpha_1a(value.a)
+            .with_alpha_1b(value.b)
+            .with_alpha_1c(value.c)
+            .with_alpha_1d(value.d)
+    }
+
+    /// Uses the same settings for both cycles, which is what 1 cycle mode usually wants
+    pub const fn with_both_cycles(&self, rgb: ColorCombiner, alpha: AlphaCombiner) -> Self {
+        self
+            .with_rgb_0(rgb)
+            .with_alpha_0(alpha)
+            .with_rgb_1(rgb)
+            .with_alpha_1(alpha)
+    }
+}
+
+/// (a - b) * c + d for the color channels
+#[derive(Copy, Clone)]
+pub struct ColorCombiner {
+    a: CombinerSubA,
+    b: CombinerSubB,
+    c: CombinerMul,
+    d: CombinerAdd,
+}
+
+impl ColorCombiner {
+    pub const fn new(a: CombinerSubA, b: CombinerSubB, c: CombinerMul, d: CombinerAdd) -> Self { Self { a, b, c, d } }
+
+    pub const fn a(&self) -> CombinerSubA { self.a }
+    pub const fn b(&self) -> CombinerSubB { self.b }
+    pub const fn c(&self) -> CombinerMul { self.c }
+    pub const fn d(&self) -> CombinerAdd { self.d }
+}
+
+/// (a - b) * c + d for the alpha channel
+#[derive(Copy, Clone)]
+pub struct AlphaCombiner {
+    a: AlphaCombinerAddSub,
+    b: AlphaCombinerAddSub,
+    c: AlphaCombinerMul,
+    d: AlphaCombinerAddSub,
+}
+
+impl AlphaCombiner {
+    pub const fn new(a: AlphaCombinerAddSub, b: AlphaCombinerAddSub, c: AlphaCombinerMul, d: AlphaCombinerAddSub) -> Self { Self { a, b, c, d } }
+
+    pub const fn a(&self) -> AlphaCombinerAddSub { self.a }
+    pub const fn b(&self) -> AlphaCombinerAddSub { self.b }
+    pub const fn c(&self) -> AlphaCombinerMul { self.c }
+    pub const fn d(&self) -> AlphaCombinerAddSub { self.d }
+}
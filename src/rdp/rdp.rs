@@ -14,7 +14,9 @@ pub const DP_STATUS_XBUS: u32 = 0x1;
 pub const DP_STATUS_FREEZE: u32 = 0x2;
 pub const DP_STATUS_START_GCLK: u32 = 0x8;
 pub const DP_STATUS_PIPE_BUSY: u32 = 0x20;
+pub const DP_STATUS_COMMAND_BUSY: u32 = 0x40;
 pub const DP_STATUS_COMMAND_BUFFER_READY: u32 = 0x80;
+pub const DP_STATUS_DMA_BUSY: u32 = 0x100;
 pub const DP_STATUS_END_VALID: u32 = 0x200;
 pub const DP_STATUS_START_VALID: u32 = 0x400;
 
@@ -1,17 +1,20 @@
+use alloc::string::String;
+use alloc::vec::Vec;
 use core::fmt::{Debug, Formatter};
-use arbitrary_int::u12;
+use arbitrary_int::{u10, u12, u15, u3, u5};
 
 use crate::graphics::color::{RGBA5551, ARGB8888};
 use crate::math::bits::{Bitmasks32, Bitmasks64};
-use crate::rdp::fixedpoint::{I12_2, I16_16, U10_2};
-use crate::rdp::modes::{Format, Othermode, PixelSize};
+use crate::rdp::disassembler;
+use crate::rdp::fixedpoint::{I11_5, I12_2, I16_16, I6_10, U10_2};
+use crate::rdp::modes::{CombineMode, Format, Othermode, PixelSize, TileDescriptor};
+use crate::MemoryMap;
 use crate::uncached_memory::UncachedHeapMemory;
 
 // @formatter:off
-#[allow(dead_code)]
 #[repr(u8)]
 #[derive(Debug)]
-enum RDPCommand {
+pub enum RDPCommand {
     FilledTriangle = 8, DepthFilledTriangle = 9, TexturedTriangle = 10, TexturedDepthTriangle = 11, ShadedTriangle = 12, ShadedDepthTriangle = 13, ShadedTexturedTriangle = 14, ShadedTexturedDepthTriangle = 15,
     TexturedRectangle = 36, FlippedTexturedRectangle = 37, SyncLoad = 38, SyncPipe = 39,
     TileSync = 40, SyncFull = 41, SetKeyColorGreenBlue = 42, SetKeyColorRed = 43, SetConvert = 44, SetScissor = 45, SetPrimitiveDepth = 46, SetOtherMode = 47,
@@ -20,6 +23,50 @@ enum RDPCommand {
 }
 // @formatter:on
 
+impl RDPCommand {
+    /// The command for an id (bits 56..=61 of the first word of a command)
+    pub const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            8 => Some(Self::FilledTriangle),
+            9 => Some(Self::DepthFilledTriangle),
+            10 => Some(Self::TexturedTriangle),
+            11 => Some(Self::TexturedDepthTriangle),
+            12 => Some(Self::ShadedTriangle),
+            13 => Some(Self::ShadedDepthTriangle),
+            14 => Some(Self::ShadedTexturedTriangle),
+            15 => Some(Self::ShadedTexturedDepthTriangle),
+            36 => Some(Self::TexturedRectangle),
+            37 => Some(Self::FlippedTexturedRectangle),
+            38 => Some(Self::SyncLoad),
+            39 => Some(Self::SyncPipe),
+            40 => Some(Self::TileSync),
+            41 => Some(Self::SyncFull),
+            42 => Some(Self::SetKeyColorGreenBlue),
+            43 => Some(Self::SetKeyColorRed),
+            44 => Some(Self::SetConvert),
+            45 => Some(Self::SetScissor),
+            46 => Some(Self::SetPrimitiveDepth),
+            47 => Some(Self::SetOtherMode),
+            48 => Some(Self::LoadPalette),
+            50 => Some(Self::SetTileSize),
+            51 => Some(Self::LoadBlock),
+            52 => Some(Self::LoadTile),
+            53 => Some(Self::SetTile),
+            54 => Some(Self::FilledRectangle),
+            55 => Some(Self::SetFillColor),
+            56 => Some(Self::SetFogColor),
+            57 => Some(Self::SetBlendColor),
+            58 => Some(Self::SetPrimitiveColor),
+            59 => Some(Self::SetEnvColor),
+            60 => Some(Self::SetCombine),
+            61 => Some(Self::SetTextureImage),
+            62 => Some(Self::SetDepthImage),
+            63 => Some(Self::SetFramebufferImage),
+            _ => None,
+        }
+    }
+}
+
 const INSTRUCTION_STREAM_SIZE: usize = 128;
 
 #[derive(Debug)]
@@ -41,6 +88,45 @@ impl RDPRectangle {
     pub const fn bottom(&self) -> U10_2 { self.bottom }
 }
 
+/// Texture coordinates of a texture rectangle. s and t are the coordinates of the top left pixel. In copy mode,
+/// dsdx is usually 4.0 as four texels are copied per clock
+#[derive(Debug)]
+pub struct TextureCoordinates {
+    s: I11_5,
+    t: I11_5,
+    dsdx: I6_10,
+    dtdy: I6_10,
+}
+
+impl TextureCoordinates {
+    pub const fn new(s: I11_5, t: I11_5, dsdx: I6_10, dtdy: I6_10) -> Self {
+        Self { s, t, dsdx, dtdy }
+    }
+
+    pub const fn s(&self) -> I11_5 { self.s }
+    pub const fn t(&self) -> I11_5 { self.t }
+    pub const fn dsdx(&self) -> I6_10 { self.dsdx }
+    pub const fn dtdy(&self) -> I6_10 { self.dtdy }
+}
+
+/// Chroma key settings for a single color channel
+pub struct ChromaKey {
+    width: u12,
+    center: u8,
+    scale: u8,
+}
+
+impl ChromaKey {
+    /// width is an unsigned 4.8 value
+    pub const fn new(width: u12, center: u8, scale: u8) -> Self {
+        Self { width, center, scale }
+    }
+
+    pub const fn width(&self) -> u12 { self.width }
+    pub const fn center(&self) -> u8 { self.center }
+    pub const fn scale(&self) -> u8 { self.scale }
+}
+
 pub struct TriangleBase {
     data: [u64; 4],
 }
@@ -142,6 +228,71 @@ impl Debug for TriangleBase {
     }
 }
 
+/// Shade coefficients that follow the edge coefficients of a shaded triangle. Each is in the order red, green, blue,
+/// alpha. Colors are 8 bit integers in the upper half of the 16.16 values
+pub struct ShadeCoefficients {
+    data: [u64; 8],
+}
+
+impl ShadeCoefficients {
+    /// Packs the integer parts (upper 16 bits) or the fractional parts (lower 16 bits) of four values into one word
+    const fn pack(values: &[I16_16; 4], integer: bool) -> u64 {
+        let shift = if integer { 16 } else { 0 };
+        let mut result = 0u64;
+        let mut i = 0;
+        while i < 4 {
+            result |= (((values[i].masked_value() >> shift) & 0xFFFF) as u64) << (48 - 16 * i);
+            i += 1;
+        }
+        result
+    }
+
+    pub const fn new(color: [I16_16; 4], dx: [I16_16; 4], de: [I16_16; 4], dy: [I16_16; 4]) -> Self {
+        Self {
+            data: [
+                Self::pack(&color, true),
+                Self::pack(&dx, true),
+                Self::pack(&color, false),
+                Self::pack(&dx, false),
+                Self::pack(&de, true),
+                Self::pack(&dy, true),
+                Self::pack(&de, false),
+                Self::pack(&dy, false),
+            ]
+        }
+    }
+
+    /// The same color for every pixel
+    pub fn flat(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
+        let zero = [I16_16::from_i32(0); 4];
+        Self::new(
+            [I16_16::from_i32(red as i32), I16_16::from_i32(green as i32), I16_16::from_i32(blue as i32), I16_16::from_i32(alpha as i32)],
+            zero, zero, zero)
+    }
+}
+
+/// Depth coefficients that come last in a Z-buffered triangle. Depth is 16.16, with a 15 bit integer part
+pub struct DepthCoefficients {
+    data: [u64; 2],
+}
+
+impl DepthCoefficients {
+    pub const fn new(z: I16_16, dzdx: I16_16, dzde: I16_16, dzdy: I16_16) -> Self {
+        Self {
+            data: [
+                ((z.masked_value() as u64) << 32) | (dzdx.masked_value() as u64),
+                ((dzde.masked_value() as u64) << 32) | (dzdy.masked_value() as u64),
+            ]
+        }
+    }
+
+    /// The same depth for every pixel
+    pub fn flat(z: u15) -> Self {
+        let zero = I16_16::from_i32(0);
+        Self::new(I16_16::new_with_masked_value((z.value() as u32) << 16), zero, zero, zero)
+    }
+}
+
 pub struct RDPAssembler {
     data: UncachedHeapMemory<u64>,
     index: usize,
@@ -159,6 +310,16 @@ impl<'a> RDPAssembler {
 
     pub fn end(&mut self) -> usize { self.data.start_phyiscal() + (self.index << 3) }
 
+    /// The commands that have been written so far, e.g. to place them into DMEM
+    pub fn commands(&mut self) -> Vec<u64> {
+        (0..self.index).map(|i| self.data.read(i)).collect()
+    }
+
+    /// The commands that have been written so far, in the format of [disassembler::disassemble]
+    pub fn disassemble(&self) -> String {
+        disassembler::disassemble_rdram(MemoryMap::uncached_to_physical_mut(self.data.as_ptr()), self.index)
+    }
+
     fn write(&mut self, value: u64) {
         self.data.write(self.index, value);
         self.index += 1;
@@ -187,12 +348,26 @@ impl<'a> RDPAssembler {
         self.write_command(RDPCommand::SyncPipe, 0);
     }
 
+    pub fn sync_load(&mut self) {
+        self.write_command(RDPCommand::SyncLoad, 0);
+    }
+
+    pub fn sync_tile(&mut self) {
+        self.write_command(RDPCommand::TileSync, 0);
+    }
+
     pub fn set_blendcolor(&mut self, color: ARGB8888) {
         self.write_command(
             RDPCommand::SetBlendColor,
             color.raw_value() as u64);
     }
 
+    pub fn set_fog_color(&mut self, color: ARGB8888) {
+        self.write_command(
+            RDPCommand::SetFogColor,
+            color.raw_value() as u64);
+    }
+
     pub fn set_fillcolor32(&mut self, color: ARGB8888) {
         self.write_command(
             RDPCommand::SetFillColor,
@@ -211,6 +386,52 @@ impl<'a> RDPAssembler {
             othermode.raw_value());
     }
 
+    pub fn set_combine_mode(&mut self, combine_mode: CombineMode) {
+        self.write_command(
+            RDPCommand::SetCombine,
+            combine_mode.raw_value());
+    }
+
+    /// Packs a color in the RGBA byte order that SET_PRIM_COLOR, SET_ENV_COLOR etc expect
+    fn rgba(color: ARGB8888) -> u64 {
+        ((color.red() as u64) << 24) |
+            ((color.green() as u64) << 16) |
+            ((color.blue() as u64) << 8) |
+            (color.alpha() as u64)
+    }
+
+    /// Sets the primitive color. lod_fraction is the combiner's "primitive LOD fraction" input
+    pub fn set_primitive_color(&mut self, min_level: u5, lod_fraction: u8, color: ARGB8888) {
+        self.write_command(
+            RDPCommand::SetPrimitiveColor,
+            ((min_level.value() as u64) << 40) |
+                ((lod_fraction as u64) << 32) |
+                Self::rgba(color));
+    }
+
+    pub fn set_env_color(&mut self, color: ARGB8888) {
+        self.write_command(
+            RDPCommand::SetEnvColor,
+            Self::rgba(color));
+    }
+
+    /// Sets the chroma key. Center and scale are also available as combiner inputs (KeyCenter/KeyScale)
+    pub fn set_key(&mut self, red: &ChromaKey, green: &ChromaKey, blue: &ChromaKey) {
+        self.write_command(
+            RDPCommand::SetKeyColorGreenBlue,
+            ((green.width.value() as u64) << 44) |
+                ((blue.width.value() as u64) << 32) |
+                ((green.center as u64) << 24) |
+                ((green.scale as u64) << 16) |
+                ((blue.center as u64) << 8) |
+                (blue.scale as u64));
+        self.write_command(
+            RDPCommand::SetKeyColorRed,
+            ((red.width.value() as u64) << 16) |
+                ((red.center as u64) << 8) |
+                (red.scale as u64));
+    }
+
     pub fn set_framebuffer_image<T: Copy + Clone>(&mut self, format: Format, pixel_size: PixelSize, width: u12, memory: &'a mut UncachedHeapMemory<T>) {
         let value = ((memory.start_phyiscal() as u64) & Bitmasks64::M26) |
             ((width.value() as u64) << 32) |
@@ -222,14 +443,54 @@ impl<'a> RDPAssembler {
             value);
     }
 
-    pub fn filled_triangle(&mut self, base: &TriangleBase) {
+    pub fn set_depth_image<T: Copy + Clone>(&mut self, memory: &'a mut UncachedHeapMemory<T>) {
         self.write_command(
-            RDPCommand::FilledTriangle,
+            RDPCommand::SetDepthImage,
+            (memory.start_phyiscal() as u64) & Bitmasks64::M26);
+    }
+
+    /// Sets the depth that is used when Othermode.z_source_primitive is set. delta_z is used for the depth compare
+    pub fn set_primitive_depth(&mut self, z: u15, delta_z: u16) {
+        self.write_command(
+            RDPCommand::SetPrimitiveDepth,
+            ((z.value() as u64) << 16) | (delta_z as u64));
+    }
+
+    /// Emits a triangle command: The edge coefficients, followed by the optional shade and depth coefficients
+    fn triangle(&mut self, command: RDPCommand, base: &TriangleBase, shade: Option<&ShadeCoefficients>, depth: Option<&DepthCoefficients>) {
+        self.write_command(
+            command,
             base.data[0]);
 
         for i in 1..base.data.len() {
             self.write(base.data[i]);
         }
+        if let Some(shade) = shade {
+            for value in shade.data {
+                self.write(value);
+            }
+        }
+        if let Some(depth) = depth {
+            for value in depth.data {
+                self.write(value);
+            }
+        }
+    }
+
+    pub fn filled_triangle(&mut self, base: &TriangleBase) {
+        self.triangle(RDPCommand::FilledTriangle, base, None, None);
+    }
+
+    pub fn depth_triangle(&mut self, base: &TriangleBase, depth: &DepthCoefficients) {
+        self.triangle(RDPCommand::DepthFilledTriangle, base, None, Some(depth));
+    }
+
+    pub fn shaded_triangle(&mut self, base: &TriangleBase, shade: &ShadeCoefficients) {
+        self.triangle(RDPCommand::ShadedTriangle, base, Some(shade), None);
+    }
+
+    pub fn shaded_depth_triangle(&mut self, base: &TriangleBase, shade: &ShadeCoefficients, depth: &DepthCoefficients) {
+        self.triangle(RDPCommand::ShadedDepthTriangle, base, Some(shade), Some(depth));
     }
 
     pub fn filled_rectangle(&mut self, value: &RDPRectangle) {
@@ -240,4 +501,81 @@ impl<'a> RDPAssembler {
                 ((value.left.masked_value() as u64) << 12) |
                 (((value.top.masked_value() as u64) << 0)));
     }
+
+    /// Sets the image that the load commands (LOAD_TILE, LOAD_BLOCK, LOAD_TLUT) read from. width is in texels, minus one
+    pub fn set_texture_image<T: Copy + Clone>(&mut self, format: Format, pixel_size: PixelSize, width: u10, memory: &'a mut UncachedHeapMemory<T>) {
+        let value = ((memory.start_phyiscal() as u64) & Bitmasks64::M26) |
+            ((width.value() as u64) << 32) |
+            ((pixel_size as u64) << 51) |
+            ((format as u64) << 53);
+
+        self.write_command(
+            RDPCommand::SetTextureImage,
+            value);
+    }
+
+    pub fn set_tile(&mut self, tile: TileDescriptor) {
+        self.write_command(
+            RDPCommand::SetTile,
+            tile.raw_value());
+    }
+
+    /// Tile commands that take a rectangle of texels. For LOAD_TLUT, the x coordinates are palette indices
+    fn write_tile_command(&mut self, command: RDPCommand, tile: u3, value: &RDPRectangle) {
+        self.write_command(
+            command,
+            ((value.left.masked_value() as u64) << 44) |
+                ((value.top.masked_value() as u64) << 32) |
+                ((tile.value() as u64) << 24) |
+                ((value.right.masked_value() as u64) << 12) |
+                ((value.bottom.masked_value() as u64) << 0));
+    }
+
+    pub fn set_tile_size(&mut self, tile: u3, value: &RDPRectangle) {
+        self.write_tile_command(RDPCommand::SetTileSize, tile, value);
+    }
+
+    pub fn load_tile(&mut self, tile: u3, value: &RDPRectangle) {
+        self.write_tile_command(RDPCommand::LoadTile, tile, value);
+    }
+
+    pub fn load_tlut(&mut self, tile: u3, value: &RDPRectangle) {
+        self.write_tile_command(RDPCommand::LoadPalette, tile, value);
+    }
+
+    /// Loads `texel_count_minus_one + 1` texels starting at texel (s, t). dxt (in 1.11) is added to t for every 64 bit
+    /// word loaded and decides whether a line is odd (and therefore word swapped in TMEM)
+    pub fn load_block(&mut self, tile: u3, s: u12, t: u12, texel_count_minus_one: u12, dxt: u12) {
+        self.write_command(
+            RDPCommand::LoadBlock,
+            ((s.value() as u64) << 44) |
+                ((t.value() as u64) << 32) |
+                ((tile.value() as u64) << 24) |
+                ((texel_count_minus_one.value() as u64) << 12) |
+                ((dxt.value() as u64) << 0));
+    }
+
+    fn write_texture_rectangle(&mut self, command: RDPCommand, value: &RDPRectangle, tile: u3, coordinates: &TextureCoordinates) {
+        self.write_command(
+            command,
+            ((value.right.masked_value() as u64) << 44) |
+                ((value.bottom.masked_value() as u64) << 32) |
+                ((tile.value() as u64) << 24) |
+                ((value.left.masked_value() as u64) << 12) |
+                ((value.top.masked_value() as u64) << 0));
+        self.write(
+            ((coordinates.s.masked_value() as u64) << 48) |
+                ((coordinates.t.masked_value() as u64) << 32) |
+                ((coordinates.dsdx.masked_value() as u64) << 16) |
+                ((coordinates.dtdy.masked_value() as u64) << 0));
+    }
+
+    pub fn texture_rectangle(&mut self, value: &RDPRectangle, tile: u3, coordinates: &TextureCoordinates) {
+        self.write_texture_rectangle(RDPCommand::TexturedRectangle, value, tile, coordinates);
+    }
+
+    /// Like texture_rectangle, but s advances along the y axis and t along the x axis
+    pub fn texture_rectangle_flipped(&mut self, value: &RDPRectangle, tile: u3, coordinates: &TextureCoordinates) {
+        self.write_texture_rectangle(RDPCommand::FlippedTexturedRectangle, value, tile, coordinates);
+    }
 }
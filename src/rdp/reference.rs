@@ -0,0 +1,165 @@
+//! CPU reference implementation of the RDP rasterizer. Tests render the same primitive on the RDP and here and
+//! compare the results.
+//!
+//! The model walks the edges of a triangle the same way the RDP does:
+//! - Every pixel is split into 4 subscanlines. For every subscanline, the major and minor edge are stepped by
+//!   their slope. The minor edge switches from (xm, dm) to (xl, dl) at ym
+//! - Along x, each subscanline is split into 4 subpixels as well. Only two of them are sampled per subscanline, in a
+//!   checkerboard pattern: Subpixels 0 and 2 on even subscanlines, 1 and 3 on odd ones. A fully covered pixel has a
+//!   coverage of 8
+//! - The top and left scissor are only pixel accurate (they are rounded up), while the right and bottom scissor
+//!   work on subpixels
+//! - The resulting coverage is combined with the coverage in memory according to the CoverageMode. The result
+//!   ends up in the alpha bits of the framebuffer
+
+use alloc::format;
+use alloc::string::String;
+use core::fmt::Debug;
+
+use crate::graphics::color::{ARGB8888, Color};
+use crate::rdp::modes::CoverageMode;
+use crate::rdp::rdp_assembler::{RDPRectangle, TriangleBase};
+
+/// Coverage of a pixel that is fully inside of a primitive
+pub const FULL_COVERAGE: u8 = 8;
+
+/// Memory coverage that the blender sees if image_read is off
+pub const MEMORY_COVERAGE_WITHOUT_IMAGE_READ: u8 = 7;
+
+/// Walks the edges of a triangle and returns the number of covered samples (0 to [FULL_COVERAGE]) for every pixel
+pub fn coverage<const WIDTH: usize, const HEIGHT: usize>(base: &TriangleBase, scissor: &RDPRectangle) -> [[u8; WIDTH]; HEIGHT] {
+    let mut subpixel_coverage: [[u8; WIDTH]; HEIGHT] = [[0; WIDTH]; HEIGHT];
+
+    let is_right_major = base.is_right_major();
+
+    let yl: i32 = base.yl().raw_value();
+    let ym: i32 = base.ym().raw_value();
+    let yh: i32 = base.yh().raw_value();
+
+    // TODO: Is 64 bit precision correct here or should it be i32 only?
+    let xl: i64 = (base.xl().raw_value() as i64) << 2;
+    let xm: i64 = (base.xm().raw_value() as i64) << 2;
+    let xh: i64 = (base.xh().raw_value() as i64) << 2;
+
+    let dl: i64 = base.dl().raw_value() as i64;
+    let dm: i64 = base.dm().raw_value() as i64;
+    let dh: i64 = base.dh().raw_value() as i64;
+
+    let mut major_x = xh;
+    let major_inc = dh;
+    let mut y = yh;
+
+    let sections = [(ym, xm, dm), (yl, xl, dl)];
+    for (y_target, mut minor_x, minor_inc) in sections {
+        while y < y_target {
+            // Top scissor is "just" pixel accurate
+            if (y >> 2) >= (((scissor.top().raw_value() as i32) + 3) >> 2) {
+                if y >= scissor.bottom().raw_value() as i32 {
+                    break;
+                }
+
+                let (left, right) = if is_right_major { (major_x, minor_x) } else { (minor_x, major_x) };
+
+                if right >= left {
+                    let subpixel_left = left >> 16;
+                    let subpixel_right = (right - (2 << 2)) >> 16;
+                    for x in subpixel_left..=subpixel_right {
+                        // Left scissor is "just" pixel accurate
+                        if (x >> 2) < (((scissor.left().raw_value() as i64) + 3) >> 2) {
+                            continue;
+                        }
+
+                        // Right scissor is subpixel accurate
+                        if x >= scissor.right().raw_value() as i64 {
+                            break;
+                        }
+                        // Checkerboard: Only every other subpixel is a sample
+                        if (x & 1) != (y as i64 & 1) {
+                            continue;
+                        }
+                        let y_pixel = y as usize >> 2;
+                        let x_pixel = x as usize >> 2;
+                        if y_pixel < HEIGHT && x_pixel < WIDTH {
+                            subpixel_coverage[y_pixel][x_pixel] += 1;
+                        }
+                    }
+                }
+            }
+
+            let next_y = y + 1;
+            let next_major_x = major_x + major_inc;
+            let next_minor_x = minor_x + minor_inc;
+
+            minor_x = next_minor_x;
+            major_x = next_major_x;
+            y = next_y;
+        }
+    }
+
+    subpixel_coverage
+}
+
+/// The alpha bits that end up in a 32 bit framebuffer for a pixel with the given (non-zero) coverage.
+/// `memory_coverage` are the three coverage bits that were read from the framebuffer (or
+/// [MEMORY_COVERAGE_WITHOUT_IMAGE_READ]). `blend` is set if the blender ran for the pixel
+pub fn coverage_to_alpha(coverage: u8, memory_coverage: u8, blend: bool, coverage_mode: CoverageMode) -> u8 {
+    let framebuffer_coverage = match coverage_mode {
+        // Without blending, the new coverage replaces what's in memory. With blending, they're added up and
+        // saturate at full coverage
+        CoverageMode::Clamp => {
+            let sum = if blend { coverage + memory_coverage } else { coverage - 1 };
+            if (sum & 8) != 0 { 7 } else { sum & 7 }
+        }
+        CoverageMode::Wrap => (coverage + memory_coverage) & 7,
+        CoverageMode::Zap => 7,
+        CoverageMode::Save => memory_coverage,
+    };
+    framebuffer_coverage << 5
+}
+
+/// The expected result of rendering a primitive, together with the coverage that led to it
+pub struct Reference<T, const WIDTH: usize, const HEIGHT: usize> {
+    pixels: [[T; WIDTH]; HEIGHT],
+    coverage: [[u8; WIDTH]; HEIGHT],
+}
+
+impl<T: Copy + Debug + Eq, const WIDTH: usize, const HEIGHT: usize> Reference<T, WIDTH, HEIGHT> {
+    pub const fn new(pixels: [[T; WIDTH]; HEIGHT], coverage: [[u8; WIDTH]; HEIGHT]) -> Self {
+        Self { pixels, coverage }
+    }
+
+    pub const fn pixels(&self) -> &[[T; WIDTH]; HEIGHT] { &self.pixels }
+
+    pub const fn coverage(&self) -> &[[u8; WIDTH]; HEIGHT] { &self.coverage }
+
+    /// Compares the pixels that the RDP rendered against this reference. Reports the first mismatching pixel
+    /// (row by row) with its expected coverage
+    pub fn compare<H: FnOnce() -> String>(&self, actual: &[[T; WIDTH]; HEIGHT], help: H) -> Result<(), String> {
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                if actual[y][x] != self.pixels[y][x] {
+                    return Err(format!("Mismatch at ({}, {}) for '{}': actual={:#X?} expected={:#X?} (expected coverage: {}/{})",
+                                       x, y, help(), actual[y][x], self.pixels[y][x], self.coverage[y][x], FULL_COVERAGE));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Renders a single-colored triangle over a black background. The alpha bits of covered pixels are set
+/// according to the coverage mode. image_read and force_blend are expected to be off
+pub fn render_triangle<T: Color + Copy + Debug + Eq + From<ARGB8888>, const WIDTH: usize, const HEIGHT: usize>(base: &TriangleBase, scissor: &RDPRectangle, color32: ARGB8888, coverage_mode: CoverageMode) -> Reference<T, WIDTH, HEIGHT> {
+    let color: T = color32.into();
+    let coverage = coverage::<WIDTH, HEIGHT>(base, scissor);
+    let mut pixels: [[T; WIDTH]; HEIGHT] = [[T::BLACK; WIDTH]; HEIGHT];
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            if coverage[y][x] != 0 {
+                pixels[y][x] = color.with_alpha(coverage_to_alpha(coverage[y][x], MEMORY_COVERAGE_WITHOUT_IMAGE_READ, false, coverage_mode));
+            }
+        }
+    }
+
+    Reference::new(pixels, coverage)
+}
@@ -14,6 +14,7 @@ enum RegisterOffset {
 }
 
 pub const SP_STATUS_HALT: u32 = 0b1;
+pub const SP_STATUS_BROKE: u32 = 0b10;
 pub const SP_STATUS_DMA_BUSY: u32 = 0b100;
 pub const SP_STATUS_INTERRUPT_ON_BREAK: u32 = 0b1000000;
 
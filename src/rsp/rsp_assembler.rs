@@ -246,7 +246,7 @@ enum CP0OP {
 #[repr(u8)]
 pub enum CP0Register {
     SPAddress = 0, DRAMAddress = 1, ReadLength = 2, WriteLength = 3, SPStatus = 4, DmaFull = 5, DmaBusy = 6, Semaphore = 7,
-    DPStart = 8, DPEnd = 9, DPStatus = 11, DPClock = 12
+    DPStart = 8, DPEnd = 9, DPCurrent = 10, DPStatus = 11, DPClock = 12, DPBufferBusy = 13, DPPipeBusy = 14, DPTMEMBusy = 15
 }
 // @formatter:on
 
@@ -518,6 +518,11 @@ impl RSPAssembler {
         self.write_bgtz(rs, offset as i16);
     }
 
+    pub fn write_bne_backwards(&mut self, rt: GPR, rs: GPR, target: &RSMAssemblerJumpTarget) {
+        let offset = (((target.offset - self.writer.offset()) & 0xFFF) >> 2) - 1;
+        self.write_bne(rt, rs, offset as i16);
+    }
+
     // COP0
     pub fn write_mfc0(&mut self, cp0register: CP0Register, rt: GPR) {
         self.write_cop0(CP0OP::MFC0, cp0register, rt);
@@ -527,6 +532,69 @@ impl RSPAssembler {
         self.write_cop0(CP0OP::MTC0, cp0register, rt);
     }
 
+    // SP DMA (COP0 0 to 6)
+    /// Starts a DMA from RDRAM into SPMEM. length is in the format of SP_RD_LEN (length, count and skip). If a DMA is
+    /// already running, this one is queued up (and DMA_FULL is set)
+    pub fn write_dma_to_spmem(&mut self, spmem: GPR, dram: GPR, length: GPR) {
+        self.write_mtc0(CP0Register::SPAddress, spmem);
+        self.write_mtc0(CP0Register::DRAMAddress, dram);
+        self.write_mtc0(CP0Register::ReadLength, length);
+    }
+
+    /// Starts a DMA from SPMEM into RDRAM. length is in the format of SP_WR_LEN (length, count and skip)
+    pub fn write_dma_from_spmem(&mut self, spmem: GPR, dram: GPR, length: GPR) {
+        self.write_mtc0(CP0Register::SPAddress, spmem);
+        self.write_mtc0(CP0Register::DRAMAddress, dram);
+        self.write_mtc0(CP0Register::WriteLength, length);
+    }
+
+    /// Spins until there is no DMA pending anymore (so that another one can be queued up)
+    pub fn write_wait_for_dma_not_full(&mut self, temp: GPR) {
+        let loop_beginning = self.get_jump_target();
+        self.write_mfc0(CP0Register::DmaFull, temp);
+        self.write_bgtz_backwards(temp, &loop_beginning);
+        self.write_nop();
+    }
+
+    /// Spins until all DMAs are done
+    pub fn write_wait_for_dma_idle(&mut self, temp: GPR) {
+        let loop_beginning = self.get_jump_target();
+        self.write_mfc0(CP0Register::DmaBusy, temp);
+        self.write_bgtz_backwards(temp, &loop_beginning);
+        self.write_nop();
+    }
+
+    // RDP registers (COP0 8 to 15)
+    /// Starts the RDP on the command list from start to end. If XBUS is set, these are DMEM offsets. If the RDP is
+    /// still busy, the list is queued up
+    pub fn write_run_rdp(&mut self, start: GPR, end: GPR) {
+        self.write_mtc0(CP0Register::DPStart, start);
+        self.write_mtc0(CP0Register::DPEnd, end);
+    }
+
+    /// Writes DP_STATUS. This takes the same set/clear bits as a write from the CPU
+    pub fn write_set_dp_status(&mut self, value: u32, temp: GPR) {
+        self.write_li(temp, value);
+        self.write_mtc0(CP0Register::DPStatus, temp);
+    }
+
+    /// Spins until DP_CURRENT reaches end
+    pub fn write_wait_for_dp_current(&mut self, end: GPR, temp: GPR) {
+        let loop_beginning = self.get_jump_target();
+        self.write_mfc0(CP0Register::DPCurrent, temp);
+        self.write_bne_backwards(temp, end, &loop_beginning);
+        self.write_nop();
+    }
+
+    /// Spins until all bits of mask are cleared in DP_STATUS. Afterwards, status holds the last value that was read
+    pub fn write_wait_for_dp_status_clear(&mut self, mask: u16, status: GPR, temp: GPR) {
+        let loop_beginning = self.get_jump_target();
+        self.write_mfc0(CP0Register::DPStatus, status);
+        self.write_andi(temp, status, mask);
+        self.write_bgtz_backwards(temp, &loop_beginning);
+        self.write_nop();
+    }
+
     // COP2
     pub fn write_ctc2(&mut self, flags_register: CP2FlagsRegister, rt: GPR) {
         self.write_ctc2_any_index(flags_register.raw_value(), rt);
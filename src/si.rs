@@ -0,0 +1,71 @@
+#![allow(dead_code)]
+
+use bitbybit::bitfield;
+
+use crate::MemoryMap;
+
+const SI_BASE_REG: usize = 0xA480_0000u32 as i32 as usize;
+const SI_DRAM_ADDR: *mut u32 = (SI_BASE_REG + 0x0) as *mut u32;
+const SI_PIF_AD_RD64B: *mut u32 = (SI_BASE_REG + 0x4) as *mut u32;
+const SI_PIF_AD_WR64B: *mut u32 = (SI_BASE_REG + 0x10) as *mut u32;
+const SI_STATUS: *mut u32 = (SI_BASE_REG + 0x18) as *mut u32;
+
+/// Size of PIF RAM, which is also the size of every SI DMA
+pub const PIF_RAM_SIZE: usize = 64;
+
+#[bitfield(u32, default: 0)]
+#[derive(Eq, PartialEq, Debug)]
+pub struct SiStatusRead {
+    #[bit(12, rw)]
+    pub interrupt: bool,
+
+    #[bit(3, rw)]
+    pub dma_error: bool,
+
+    #[bit(2, rw)]
+    pub read_pending: bool,
+
+    #[bit(1, rw)]
+    pub io_busy: bool,
+
+    #[bit(0, rw)]
+    pub dma_busy: bool,
+}
+
+pub struct Si {}
+
+impl Si {
+    pub fn set_dram_address(value: u32) {
+        unsafe { SI_DRAM_ADDR.write_volatile(value) }
+    }
+
+    pub fn dram_address() -> u32 {
+        unsafe { SI_DRAM_ADDR.read_volatile() }
+    }
+
+    /// Starts a 64 byte DMA from PIF RAM into RDRAM
+    pub fn start_pif_to_dram() {
+        unsafe { SI_PIF_AD_RD64B.write_volatile(MemoryMap::PHYSICAL_PIFRAM_BASE as u32) }
+    }
+
+    /// Starts a 64 byte DMA from RDRAM into PIF RAM. If the control byte (offset 63) has bit 0
+    /// set, the PIF will process the joybus commands afterwards
+    pub fn start_dram_to_pif() {
+        unsafe { SI_PIF_AD_WR64B.write_volatile(MemoryMap::PHYSICAL_PIFRAM_BASE as u32) }
+    }
+
+    pub fn status() -> SiStatusRead {
+        SiStatusRead::new_with_raw_value(unsafe { SI_STATUS.read_volatile() })
+    }
+
+    /// Any write to SI_STATUS acknowledges the SI interrupt
+    pub fn clear_interrupt() {
+        unsafe { SI_STATUS.write_volatile(0) }
+    }
+
+    /// Neither a DMA nor a PIF access are in progress
+    pub fn is_idle() -> bool {
+        let status = Self::status();
+        !status.dma_busy() && !status.io_busy()
+    }
+}
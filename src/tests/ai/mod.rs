@@ -0,0 +1,180 @@
+use alloc::boxed::Box;
+use alloc::{format, vec};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+
+use crate::ai::{Ai, AiControl};
+use crate::mi;
+use crate::tests::{Level, Test, wait_for};
+use crate::tests::soft_asserts::{soft_assert_eq, soft_assert_eq2};
+use crate::uncached_memory::UncachedHeapMemory;
+
+// Notes:
+// - Writing AI_LEN queues a buffer into a two entry FIFO. If nothing is playing, the buffer starts right away
+// - AI_LEN is 18 bits and 8 byte aligned. Reading it returns the remaining length of the current buffer
+// - The AI interrupt is raised whenever a buffer starts playing. It is acknowledged by writing AI_STATUS
+
+const FREQUENCY: u32 = 44100;
+
+/// Size of a buffer in bytes. At 44.1khz and 4 bytes per sample, this plays for 5.8ms
+const BUFFER_SIZE: usize = 1024;
+
+/// Waits until all queued buffers are played, then turns off DMA and acknowledges the interrupt
+fn wait_until_idle() -> Result<(), String> {
+    let result = wait_for("AI to become idle", 200, || !Ai::status().busy());
+    Ai::set_control(AiControl::new().with_dma_enable(false));
+    Ai::clear_interrupt();
+    result
+}
+
+fn prepare() -> Result<UncachedHeapMemory<u64>, String> {
+    wait_until_idle()?;
+    Ai::set_frequency(FREQUENCY);
+    Ai::set_control(AiControl::new().with_dma_enable(true));
+    // Silence
+    Ok(UncachedHeapMemory::<u64>::new_with_init_value(BUFFER_SIZE / 8, 0))
+}
+
+fn queue(buffer: &mut UncachedHeapMemory<u64>, length: u32) {
+    Ai::set_dram_address(buffer.start_phyiscal() as u32);
+    Ai::set_length(length);
+}
+
+pub struct DMAFifoFullAndBusy {}
+
+impl Test for DMAFifoFullAndBusy {
+    fn name(&self) -> &str { "AI: DMA FIFO (full and busy)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let mut buffer = prepare()?;
+        soft_assert_eq(Ai::status().busy(), false, "AI_STATUS.busy before queueing anything")?;
+        soft_assert_eq(Ai::status().full(), false, "AI_STATUS.full before queueing anything")?;
+
+        queue(&mut buffer, BUFFER_SIZE as u32);
+        let status = Ai::status();
+        soft_assert_eq(status.busy(), true, "AI_STATUS.busy after queueing one buffer")?;
+        soft_assert_eq(status.full(), false, "AI_STATUS.full after queueing one buffer")?;
+
+        queue(&mut buffer, BUFFER_SIZE as u32);
+        let status = Ai::status();
+        soft_assert_eq(status.busy(), true, "AI_STATUS.busy after queueing two buffers")?;
+        soft_assert_eq(status.full(), true, "AI_STATUS.full after queueing two buffers")?;
+        soft_assert_eq(status.full2(), true, "AI_STATUS bit 0 (mirror of full) after queueing two buffers")?;
+
+        // Once the first buffer is played, the second one moves up and the FIFO has a free entry again
+        wait_for("AI_STATUS.full to clear", 100, || !Ai::status().full())?;
+        soft_assert_eq(Ai::status().busy(), true, "AI_STATUS.busy while playing the second buffer")?;
+
+        wait_for("AI_STATUS.busy to clear", 100, || !Ai::status().busy())?;
+        soft_assert_eq(Ai::length(), 0, "AI_LEN after all buffers were played")?;
+
+        wait_until_idle()
+    }
+}
+
+pub struct LengthDecrements {}
+
+impl Test for LengthDecrements {
+    fn name(&self) -> &str { "AI: AI_LEN readback decrements" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let mut buffer = prepare()?;
+        queue(&mut buffer, BUFFER_SIZE as u32);
+
+        let mut previous = Ai::length();
+        soft_assert_eq2(previous <= BUFFER_SIZE as u32 && previous > 0, true, || format!("AI_LEN right after queueing a buffer of {:#x} bytes was {:#x}", BUFFER_SIZE, previous))?;
+        let mut decrements = 0;
+        let mut increase = None;
+        wait_for("AI_STATUS.busy to clear", 100, || {
+            if !Ai::status().busy() {
+                return true;
+            }
+            let length = Ai::length();
+            if (length > previous) && increase.is_none() {
+                increase = Some((previous, length));
+            }
+            if length < previous {
+                decrements += 1;
+            }
+            previous = length;
+            false
+        })?;
+        if let Some((from, to)) = increase {
+            return Err(format!("AI_LEN is expected to only go down, but it went from {:#x} to {:#x}", from, to));
+        }
+        soft_assert_eq2(decrements > 1, true, || format!("AI_LEN was expected to decrement while playing, but only saw {} decrements", decrements))?;
+        soft_assert_eq(Ai::length(), 0, "AI_LEN after playing")?;
+
+        wait_until_idle()
+    }
+}
+
+pub struct LengthMasking {}
+
+impl Test for LengthMasking {
+    fn name(&self) -> &str { "AI: AI_LEN (masking)" }
+
+    fn level(&self) -> Level { Level::RarelyUsed }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        // Written value and the length that is expected to be used
+        vec! {
+            Box::new((0x107u32, 0x100u32)),
+            Box::new((0x4_0100u32, 0x100u32)),
+            Box::new((0xFFFC_0207u32, 0x200u32)),
+            Box::new((0x7u32, 0u32)),
+        }
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (written, expected) = *value.downcast_ref::<(u32, u32)>().unwrap();
+        let mut buffer = prepare()?;
+        queue(&mut buffer, written);
+
+        // The buffer starts playing right away, so a few bytes might already be gone
+        let length = Ai::length();
+        soft_assert_eq2(length <= expected && length + 0x40 >= expected, true, || format!("AI_LEN after writing {:#x} was {:#x}. Expected a value just below {:#x}", written, length, expected))?;
+        soft_assert_eq2(Ai::status().busy(), expected != 0, || format!("AI_STATUS.busy after writing AI_LEN={:#x}", written))?;
+
+        wait_until_idle()
+    }
+}
+
+pub struct InterruptOnBuffer {}
+
+impl Test for InterruptOnBuffer {
+    fn name(&self) -> &str { "AI: Interrupt (MI_INTR)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let mut buffer = prepare()?;
+        soft_assert_eq(mi::is_ai_interrupt(), false, "MI_INTR.AI before queueing a buffer (after acknowledging)")?;
+
+        queue(&mut buffer, BUFFER_SIZE as u32);
+        queue(&mut buffer, BUFFER_SIZE as u32);
+        wait_for("AI_STATUS.full to clear", 100, || !Ai::status().full())?;
+        soft_assert_eq(mi::is_ai_interrupt(), true, "MI_INTR.AI after the first buffer was consumed")?;
+
+        Ai::clear_interrupt();
+        soft_assert_eq(mi::is_ai_interrupt(), false, "MI_INTR.AI after writing AI_STATUS")?;
+
+        wait_for("AI_STATUS.busy to clear", 100, || !Ai::status().busy())?;
+        let status = Ai::status();
+        soft_assert_eq(status.full() || status.full2(), false, "AI_STATUS.full after playing")?;
+
+        wait_until_idle()?;
+        soft_assert_eq(mi::is_ai_interrupt(), false, "MI_INTR.AI after acknowledging")
+    }
+}
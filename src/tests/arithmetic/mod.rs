@@ -1,4 +1,5 @@
 pub mod nemu_port;
 pub mod shifts;
 pub mod div;
+pub mod mult;
 pub mod ll_sc;
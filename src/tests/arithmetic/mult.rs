@@ -0,0 +1,522 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::arch::asm;
+use oorandom::Rand64;
+
+use crate::graphics::color::{Color, RGBA5551};
+use crate::graphics::cursor::Cursor;
+use crate::graphics::font::Font;
+use crate::graphics::system_font::FONT_GENEVA_9;
+use crate::tests::{Level, Test};
+use crate::tests::soft_asserts::{soft_assert_eq, soft_assert_eq2};
+use crate::VIDEO;
+
+// Notes:
+// - MULT and MULTU only look at the lower 32 bits of their operands. LO and HI each receive 32 bits of the result,
+//   sign extended to 64 bit (even for MULTU)
+// - DMULT and DMULTU produce a 128 bit result: LO receives the lower 64 bit, HI the upper 64 bit
+// - The VR4300 interlocks on HI/LO: Reading them right after a multiply stalls until the result is ready.
+//   The R4000 hazard (MFHI/MFLO followed by a multiply/divide within two instructions) shouldn't exist
+
+#[derive(Copy, Clone, Debug)]
+enum Op {
+    MULT,
+    MULTU,
+    DMULT,
+    DMULTU,
+}
+
+impl Op {
+    fn name(&self) -> &'static str {
+        match self {
+            Op::MULT => "MULT",
+            Op::MULTU => "MULTU",
+            Op::DMULT => "DMULT",
+            Op::DMULTU => "DMULTU",
+        }
+    }
+}
+
+/// Unsigned 64x64 bit multiply using only shifts and adds, returns (lower 64 bit, upper 64 bit). This
+/// intentionally avoids the multiply instructions that are under test
+fn shift_and_add(a: u64, b: u64) -> (u64, u64) {
+    let mut lo = 0u64;
+    let mut hi = 0u64;
+    for bit in 0..64 {
+        if (b & (1 << bit)) != 0 {
+            let addend_lo = a << bit;
+            let addend_hi = if bit == 0 { 0 } else { a >> (64 - bit) };
+            let (sum, carry) = lo.overflowing_add(addend_lo);
+            lo = sum;
+            hi = hi.wrapping_add(addend_hi).wrapping_add(carry as u64);
+        }
+    }
+    (lo, hi)
+}
+
+/// Signed 64x64 bit multiply. The unsigned product is corrected by subtracting the other operand from the
+/// upper half for each negative operand
+fn shift_and_add_signed(a: u64, b: u64) -> (u64, u64) {
+    let (lo, mut hi) = shift_and_add(a, b);
+    if (a as i64) < 0 {
+        hi = hi.wrapping_sub(b);
+    }
+    if (b as i64) < 0 {
+        hi = hi.wrapping_sub(a);
+    }
+    (lo, hi)
+}
+
+/// Calculates (LO, HI) in software
+fn reference(op: Op, a: u64, b: u64) -> (u64, u64) {
+    match op {
+        Op::MULT => {
+            let (result, _) = shift_and_add_signed(a as i32 as i64 as u64, b as i32 as i64 as u64);
+            (result as i32 as i64 as u64, (result >> 32) as i32 as i64 as u64)
+        }
+        Op::MULTU => {
+            let (result, _) = shift_and_add(a as u32 as u64, b as u32 as u64);
+            (result as u32 as i32 as i64 as u64, (result >> 32) as u32 as i32 as i64 as u64)
+        }
+        Op::DMULT => shift_and_add_signed(a, b),
+        Op::DMULTU => shift_and_add(a, b),
+    }
+}
+
+/// Runs the instruction on hardware and returns (LO, HI)
+fn multiply(op: Op, a: u64, b: u64) -> (u64, u64) {
+    let mut lo: u64 = 0;
+    let mut hi: u64 = 0;
+    unsafe {
+        match op {
+            Op::MULT => asm!("
+                .set noat
+                .set noreorder
+                LD $2, 0($4)
+                LD $3, 0($5)
+                MULT $2, $3
+                MFLO $2
+                MFHI $3
+                SD $2, 0($6)
+                SD $3, 0($7)
+            ", out("$2") _, out("$3") _, in("$4") &a, in("$5") &b, in("$6") &mut lo, in("$7") &mut hi),
+            Op::MULTU => asm!("
+                .set noat
+                .set noreorder
+                LD $2, 0($4)
+                LD $3, 0($5)
+                MULTU $2, $3
+                MFLO $2
+                MFHI $3
+                SD $2, 0($6)
+                SD $3, 0($7)
+            ", out("$2") _, out("$3") _, in("$4") &a, in("$5") &b, in("$6") &mut lo, in("$7") &mut hi),
+            Op::DMULT => asm!("
+                .set noat
+                .set noreorder
+                LD $2, 0($4)
+                LD $3, 0($5)
+                DMULT $2, $3
+                MFLO $2
+                MFHI $3
+                SD $2, 0($6)
+                SD $3, 0($7)
+            ", out("$2") _, out("$3") _, in("$4") &a, in("$5") &b, in("$6") &mut lo, in("$7") &mut hi),
+            Op::DMULTU => asm!("
+                .set noat
+                .set noreorder
+                LD $2, 0($4)
+                LD $3, 0($5)
+                DMULTU $2, $3
+                MFLO $2
+                MFHI $3
+                SD $2, 0($6)
+                SD $3, 0($7)
+            ", out("$2") _, out("$3") _, in("$4") &a, in("$5") &b, in("$6") &mut lo, in("$7") &mut hi),
+        }
+    }
+    (lo, hi)
+}
+
+fn test_table(op: Op, value: &Box<dyn Any>) -> Result<(), String> {
+    match (*value).downcast_ref::<(u64, u64, u64, u64)>() {
+        Some((a, b, expected_lo, expected_hi)) => {
+            let (lo, hi) = multiply(op, *a, *b);
+            soft_assert_eq(lo, *expected_lo, "LO")?;
+            soft_assert_eq(hi, *expected_hi, "HI")?;
+            Ok(())
+        }
+        _ => Err("Value is not valid".to_string())
+    }
+}
+
+pub struct MULT {}
+
+impl Test for MULT {
+    fn name(&self) -> &str { "MULT" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        vec! {
+            Box::new((0x00000000_00001234u64, 0x00000000_00005678u64, 0x00000000_06260060u64, 0x00000000_00000000u64)),
+            Box::new((0xFFFFFFFF_FFFFFFFFu64, 0x00000000_00000002u64, 0xFFFFFFFF_FFFFFFFEu64, 0xFFFFFFFF_FFFFFFFFu64)),
+            Box::new((0xFFFFFFFF_80000000u64, 0xFFFFFFFF_80000000u64, 0x00000000_00000000u64, 0x00000000_40000000u64)),
+            Box::new((0x00000000_7FFFFFFFu64, 0x00000000_7FFFFFFFu64, 0x00000000_00000001u64, 0x00000000_3FFFFFFFu64)),
+            Box::new((0x00000000_12345678u64, 0x00000000_00000010u64, 0x00000000_23456780u64, 0x00000000_00000001u64)),
+            Box::new((0xFFFFFFFF_80000000u64, 0xFFFFFFFF_FFFFFFFFu64, 0xFFFFFFFF_80000000u64, 0x00000000_00000000u64)),
+            Box::new((0x00000000_00000000u64, 0xFFFFFFFF_FFFFFFFFu64, 0x00000000_00000000u64, 0x00000000_00000000u64)),
+        }
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> { test_table(Op::MULT, value) }
+}
+
+pub struct MULTU {}
+
+impl Test for MULTU {
+    fn name(&self) -> &str { "MULTU" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        vec! {
+            Box::new((0x00000000_00001234u64, 0x00000000_00005678u64, 0x00000000_06260060u64, 0x00000000_00000000u64)),
+            Box::new((0xFFFFFFFF_FFFFFFFFu64, 0xFFFFFFFF_FFFFFFFFu64, 0x00000000_00000001u64, 0xFFFFFFFF_FFFFFFFEu64)),
+            Box::new((0xFFFFFFFF_FFFFFFFFu64, 0x00000000_00000002u64, 0xFFFFFFFF_FFFFFFFEu64, 0x00000000_00000001u64)),
+            Box::new((0xFFFFFFFF_80000000u64, 0x00000000_00000002u64, 0x00000000_00000000u64, 0x00000000_00000001u64)),
+            Box::new((0x00000000_12345678u64, 0x00000000_00000010u64, 0x00000000_23456780u64, 0x00000000_00000001u64)),
+        }
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> { test_table(Op::MULTU, value) }
+}
+
+pub struct MULTNotSignExtended {}
+
+impl Test for MULTNotSignExtended {
+    fn name(&self) -> &str { "MULT/MULTU (operands not sign extended)" }
+
+    fn level(&self) -> Level { Level::Weird }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        // The upper 32 bit of the operands are ignored
+        let (lo, hi) = multiply(Op::MULT, 0x12345678_00000003, 0xFFFF0000_00000005);
+        soft_assert_eq(lo, 0x00000000_0000000F, "MULT LO")?;
+        soft_assert_eq(hi, 0x00000000_00000000, "MULT HI")?;
+        let (lo, hi) = multiply(Op::MULTU, 0x12345678_FFFFFFFF, 0x00000001_00000002);
+        soft_assert_eq(lo, 0xFFFFFFFF_FFFFFFFE, "MULTU LO")?;
+        soft_assert_eq(hi, 0x00000000_00000001, "MULTU HI")?;
+        Ok(())
+    }
+}
+
+pub struct DMULT {}
+
+impl Test for DMULT {
+    fn name(&self) -> &str { "DMULT" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        vec! {
+            Box::new((0x00000000_00001234u64, 0x00000000_00005678u64, 0x00000000_06260060u64, 0x00000000_00000000u64)),
+            Box::new((0xFFFFFFFF_FFFFFFFFu64, 0xFFFFFFFF_FFFFFFFFu64, 0x00000000_00000001u64, 0x00000000_00000000u64)),
+            Box::new((0x80000000_00000000u64, 0x80000000_00000000u64, 0x00000000_00000000u64, 0x40000000_00000000u64)),
+            Box::new((0x80000000_00000000u64, 0xFFFFFFFF_FFFFFFFFu64, 0x80000000_00000000u64, 0x00000000_00000000u64)),
+            Box::new((0x01234567_89ABCDEFu64, 0xFEDCBA98_76543210u64, 0x2236D88F_E5618CF0u64, 0xFFFEB499_23CC0953u64)),
+            Box::new((0x7FFFFFFF_FFFFFFFFu64, 0x00000000_00000002u64, 0xFFFFFFFF_FFFFFFFEu64, 0x00000000_00000000u64)),
+        }
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> { test_table(Op::DMULT, value) }
+}
+
+pub struct DMULTU {}
+
+impl Test for DMULTU {
+    fn name(&self) -> &str { "DMULTU" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        vec! {
+            Box::new((0x00000000_00001234u64, 0x00000000_00005678u64, 0x00000000_06260060u64, 0x00000000_00000000u64)),
+            Box::new((0xFFFFFFFF_FFFFFFFFu64, 0xFFFFFFFF_FFFFFFFFu64, 0x00000000_00000001u64, 0xFFFFFFFF_FFFFFFFEu64)),
+            Box::new((0x80000000_00000000u64, 0x00000000_00000002u64, 0x00000000_00000000u64, 0x00000000_00000001u64)),
+            Box::new((0x01234567_89ABCDEFu64, 0xFEDCBA98_76543210u64, 0x2236D88F_E5618CF0u64, 0x0121FA00_AD77D742u64)),
+            Box::new((0xFFFFFFFF_FFFFFFFFu64, 0x00000000_00000002u64, 0xFFFFFFFF_FFFFFFFEu64, 0x00000000_00000001u64)),
+        }
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> { test_table(Op::DMULTU, value) }
+}
+
+pub struct MTHIMTLO {}
+
+impl Test for MTHIMTLO {
+    fn name(&self) -> &str { "MTHI/MTLO/MFHI/MFLO" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        for value in [0x01234567_89ABCDEFu64, 0xFEDCBA98_76543210, 0x00000000_80000000, 0] {
+            let written_hi = value;
+            let written_lo = !value;
+            let mut hi = 0u64;
+            let mut lo = 0u64;
+            unsafe {
+                asm!("
+                    .set noat
+                    .set noreorder
+                    LD $2, 0($4)
+                    LD $3, 0($5)
+                    MTHI $2
+                    MTLO $3
+                    MFHI $2
+                    MFLO $3
+                    SD $2, 0($6)
+                    SD $3, 0($7)
+                ", out("$2") _, out("$3") _, in("$4") &written_hi, in("$5") &written_lo, in("$6") &mut hi, in("$7") &mut lo)
+            }
+            // All 64 bits are kept, no sign extension
+            soft_assert_eq2(hi, written_hi, || format!("HI after MTHI {:#x}", written_hi))?;
+            soft_assert_eq2(lo, written_lo, || format!("LO after MTLO {:#x}", written_lo))?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads LO and then starts a multiply DISTANCE instructions later. Returns (value read by MFLO, LO after the multiply)
+fn mflo_then_mult<const DISTANCE: usize>(previous_lo: u64, a: u64, b: u64) -> (u64, u64) {
+    let mut read = 0u64;
+    let mut after = 0u64;
+    unsafe {
+        asm!("
+            .set noat
+            .set noreorder
+            LD $2, 0($4)
+            LD $3, 0($5)
+            LD $8, 0($9)
+            MTLO $8
+            NOP
+            NOP
+            MFLO $8
+            .rept {DISTANCE}
+            NOP
+            .endr
+            MULT $2, $3
+            MFLO $2
+            SD $8, 0($6)
+            SD $2, 0($7)
+        ", DISTANCE = const DISTANCE, out("$2") _, out("$3") _, out("$8") _, in("$4") &a, in("$5") &b, in("$6") &mut read, in("$7") &mut after, in("$9") &previous_lo)
+    }
+    (read, after)
+}
+
+pub struct MFLOFollowedByMULT {}
+
+impl Test for MFLOFollowedByMULT {
+    fn name(&self) -> &str { "MFLO followed by MULT (within two instructions)" }
+
+    fn level(&self) -> Level { Level::Weird }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { vec! { Box::new(0u32), Box::new(1u32), Box::new(2u32) } }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let distance = *value.downcast_ref::<u32>().unwrap();
+        let previous_lo = 0x01234567_89ABCDEFu64;
+        let (a, b) = (0x00000000_00001234u64, 0x00000000_00005678u64);
+        let (read, after) = match distance {
+            0 => mflo_then_mult::<0>(previous_lo, a, b),
+            1 => mflo_then_mult::<1>(previous_lo, a, b),
+            _ => mflo_then_mult::<2>(previous_lo, a, b),
+        };
+        soft_assert_eq(read, previous_lo, "MFLO is expected to return the value before the following MULT")?;
+        soft_assert_eq(after, reference(Op::MULT, a, b).0, "LO after MULT")?;
+        Ok(())
+    }
+}
+
+pub struct MTLOAfterMULT {}
+
+impl Test for MTLOAfterMULT {
+    fn name(&self) -> &str { "MTLO right after MULT" }
+
+    fn level(&self) -> Level { Level::PoorlyUnderstoodQuirk }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        // The multiply is still running when MTLO executes. In program order, MTLO is the last write to LO
+        let written = 0x01234567_89ABCDEFu64;
+        let (a, b) = (0x00000000_00001234u64, 0x00000000_00005678u64);
+        let mut lo = 0u64;
+        let mut hi = 0u64;
+        unsafe {
+            asm!("
+                .set noat
+                .set noreorder
+                LD $2, 0($4)
+                LD $3, 0($5)
+                LD $8, 0($9)
+                MULT $2, $3
+                MTLO $8
+                MFLO $2
+                MFHI $3
+                SD $2, 0($6)
+                SD $3, 0($7)
+            ", out("$2") _, out("$3") _, out("$8") _, in("$4") &a, in("$5") &b, in("$6") &mut lo, in("$7") &mut hi, in("$9") &written)
+        }
+        soft_assert_eq(lo, written, "LO after MULT followed by MTLO")?;
+        soft_assert_eq(hi, reference(Op::MULT, a, b).1, "HI after MULT followed by MTLO")?;
+        Ok(())
+    }
+}
+
+/// Multiplies random operands and hashes the results. The expected hash is calculated in software using the
+/// same random sequence and a shift-and-add multiply, so it doesn't depend on the CPU's multiplier
+fn randomized_test(op: Op, progress_indicator: bool, iterations: u32) -> Result<(), String> {
+    let mut random = Rand64::new(0);
+    let mut hash = 0u64;
+    let mut expected_hash = 0u64;
+
+    let font = Font::from_data(&FONT_GENEVA_9).unwrap();
+    let mut cursor = Cursor::new_with_font(&font, RGBA5551::BLACK);
+
+    for i in 0..iterations {
+        let (a, b) = match op {
+            // Sign extended 32 bit operands
+            Op::MULT | Op::MULTU => (random.rand_u64() as i32 as i64 as u64, random.rand_u64() as i32 as i64 as u64),
+            Op::DMULT | Op::DMULTU => (random.rand_u64(), random.rand_u64()),
+        };
+        let (lo, hi) = multiply(op, a, b);
+        let (expected_lo, expected_hi) = reference(op, a, b);
+        hash = hash.wrapping_mul(397) ^ lo;
+        hash = hash.wrapping_mul(397) ^ hi;
+        expected_hash = expected_hash.wrapping_mul(397) ^ expected_lo;
+        expected_hash = expected_hash.wrapping_mul(397) ^ expected_hi;
+
+        if progress_indicator {
+            if (i & 65535) == 0 {
+                let v = VIDEO.lock();
+                {
+                    let mut lock = v.framebuffers().backbuffer().lock();
+                    let buffer = lock.as_mut().unwrap();
+                    buffer.clear_with_color(RGBA5551::WHITE);
+
+                    cursor.x = 16;
+                    cursor.y = 16;
+                    cursor.draw_text(buffer, format!("Stress testing {}. {}% complete", op.name(), i * 100 / iterations).as_str());
+                }
+                v.swap_buffers();
+            }
+        }
+    }
+
+    soft_assert_eq(hash, expected_hash, "Hash")
+}
+
+pub struct MULTRandomized;
+
+impl Test for MULTRandomized {
+    fn name(&self) -> &str { "MULT (randomized - quick)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> { randomized_test(Op::MULT, false, 3000) }
+}
+
+pub struct MULTURandomized;
+
+impl Test for MULTURandomized {
+    fn name(&self) -> &str { "MULTU (randomized - quick)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> { randomized_test(Op::MULTU, false, 3000) }
+}
+
+pub struct DMULTRandomized;
+
+impl Test for DMULTRandomized {
+    fn name(&self) -> &str { "DMULT (randomized - quick)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> { randomized_test(Op::DMULT, false, 3000) }
+}
+
+pub struct DMULTURandomized;
+
+impl Test for DMULTURandomized {
+    fn name(&self) -> &str { "DMULTU (randomized - quick)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> { randomized_test(Op::DMULTU, false, 3000) }
+}
+
+pub struct StresstestMULT;
+
+impl Test for StresstestMULT {
+    fn name(&self) -> &str { "MULT (randomized - stresstest)" }
+
+    fn level(&self) -> Level { Level::StressTest }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> { randomized_test(Op::MULT, true, 10000000) }
+}
+
+pub struct StresstestMULTU;
+
+impl Test for StresstestMULTU {
+    fn name(&self) -> &str { "MULTU (randomized - stresstest)" }
+
+    fn level(&self) -> Level { Level::StressTest }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> { randomized_test(Op::MULTU, true, 10000000) }
+}
+
+pub struct StresstestDMULT;
+
+impl Test for StresstestDMULT {
+    fn name(&self) -> &str { "DMULT (randomized - stresstest)" }
+
+    fn level(&self) -> Level { Level::StressTest }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> { randomized_test(Op::DMULT, true, 10000000) }
+}
+
+pub struct StresstestDMULTU;
+
+impl Test for StresstestDMULTU {
+    fn name(&self) -> &str { "DMULTU (randomized - stresstest)" }
+
+    fn level(&self) -> Level { Level::StressTest }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> { randomized_test(Op::DMULTU, true, 10000000) }
+}
@@ -0,0 +1,165 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::arch::asm;
+
+use crate::cop0;
+use crate::cop0::{CauseException, Status};
+use crate::exception_handler::{clear_exception_return_override, drain_seen_exception, expect_exception, set_exception_return_override};
+use crate::MemoryMap;
+use crate::tests::{Level, NamedValue, Test};
+use crate::tests::privilege::return_via_s0_stub;
+use crate::tests::soft_asserts::{soft_assert_eq, soft_assert_eq2};
+
+// Accessing holes in the physical memory map:
+// - The RCP never signals a bus error, so neither IBE nor DBE are ever raised. Loads return whatever is on the bus
+// - Unused RDRAM address space (between the end of RDRAM and the RDRAM registers) reads as 0. Writes are dropped
+// - Unused RCP address space (after the RDRAM interface registers) reads as 0 as well. Writes are dropped
+// - Unmapped PI addresses (e.g. cart domain 2 at 0x0800_0000 without SRAM or 0x0500_0000 without 64DD) read back the
+//   lower 16 bits of the address twice
+// - Executing from unused RDRAM or RCP address space executes zeros, i.e. NOPs. The only way out is an interrupt, and
+//   EPC points into the hole
+// - The cart domain 2 cases only hold if the cartridge (or flashcart) doesn't provide SRAM/FlashRAM
+// - Stores only go to the RDRAM and RCP holes: A store to cart domain 2 would corrupt SRAM if the cartridge has some
+//   and a store to 0x0500_0000 would send a command to an attached 64DD
+
+/// (name, physical address, expected value on read)
+type Case = (&'static str, usize, u32);
+
+const LOAD_CASES: [Case; 6] = [
+    ("RDRAM: unused (0x03E0_0000)", 0x03E0_0000, 0),
+    ("RDRAM: unused (0x03E0_1234)", 0x03E0_1234, 0),
+    ("RCP: unused (0x0490_0000)", 0x0490_0000, 0),
+    ("PI: 64DD registers without 64DD (0x0500_1234)", 0x0500_1234, 0x1234_1234),
+    ("PI: cart domain 2 without SRAM (0x0800_0010)", 0x0800_0010, 0x0010_0010),
+    ("PI: cart domain 2 without SRAM (0x0800_5678)", 0x0800_5678, 0x5678_5678),
+];
+
+const STORE_CASES: [Case; 4] = [
+    ("RDRAM: unused (0x03E0_0000)", 0x03E0_0000, 0),
+    ("RDRAM: unused (0x03E0_1234)", 0x03E0_1234, 0),
+    ("RCP: unused (0x0490_0000)", 0x0490_0000, 0),
+    ("RCP: unused (0x04A0_5678)", 0x04A0_5678, 0),
+];
+
+/// (name, physical address to jump to, size of the hole). Both only contain zeros, in 4MB and 8MB configurations
+const FETCH_CASES: [(&str, usize, u64); 2] = [
+    ("RDRAM: unused (0x03E0_0000)", 0x03E0_0000, 0x10_0000),
+    ("RCP: unused (0x0490_0000)", 0x0490_0000, 0x70_0000),
+];
+
+/// Number of Count increments until the timer interrupt gets us out of the hole
+const COMPARE_DISTANCE: u32 = 1000;
+
+fn values(cases: &[Case]) -> Vec<Box<dyn Any>> {
+    cases.iter().map(|case| NamedValue::boxed(case.0, *case)).collect()
+}
+
+/// Fails if the previous access caused an exception
+fn assert_no_exception(what: &str, name: &str) -> Result<(), String> {
+    match drain_seen_exception() {
+        Some((exception_context, _)) => Err(format!(
+            "{} of '{}' caused exception {:?}, but no bus error was expected. ExceptPC={:#x} BadVAddr={:#x}",
+            what,
+            name,
+            exception_context.cause.exception(),
+            exception_context.exceptpc,
+            exception_context.badvaddr)),
+        None => Ok(()),
+    }
+}
+
+pub struct UnmappedLoad {}
+
+impl Test for UnmappedLoad {
+    fn name(&self) -> &str { "Bus error: Load from unmapped physical address" }
+
+    fn level(&self) -> Level { Level::Weird }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { values(&LOAD_CASES) }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (name, address, expected) = *NamedValue::get::<Case>(value);
+        let result = unsafe { MemoryMap::physical_to_uncached_mut::<u32>(address).read_volatile() };
+        assert_no_exception("Load", name)?;
+        soft_assert_eq2(result, expected, || format!("Value read from '{}'", name))?;
+
+        Ok(())
+    }
+}
+
+pub struct UnmappedStore {}
+
+impl Test for UnmappedStore {
+    fn name(&self) -> &str { "Bus error: Store to unmapped physical address" }
+
+    fn level(&self) -> Level { Level::Weird }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { values(&STORE_CASES) }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (name, address, expected) = *NamedValue::get::<Case>(value);
+        let p = MemoryMap::physical_to_uncached_mut::<u32>(address);
+        unsafe { p.write_volatile(0xDEADBEEF); }
+        assert_no_exception("Store", name)?;
+
+        let result = unsafe { p.read_volatile() };
+        assert_no_exception("Load after store", name)?;
+        soft_assert_eq2(result, expected, || format!("Value read from '{}' after writing to it", name))?;
+
+        Ok(())
+    }
+}
+
+pub struct UnmappedInstructionFetch {}
+
+impl Test for UnmappedInstructionFetch {
+    fn name(&self) -> &str { "Bus error: Instruction fetch from unused address space" }
+
+    fn level(&self) -> Level { Level::Weird }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        FETCH_CASES.iter().map(|case| NamedValue::boxed(case.0, *case)).collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (name, address, size) = *NamedValue::get::<(&'static str, usize, u64)>(value);
+        let target = MemoryMap::physical_to_uncached_mut::<u32>(address) as u32;
+        let status = Status::DEFAULT.with_interrupt_mask_compare(true).with_ie(true);
+
+        let exception_context = expect_exception(CauseException::Int, 0, || {
+            // Come back through $s0 with interrupts disabled
+            set_exception_return_override(return_via_s0_stub as u32 as i32 as i64 as u64, Status::DEFAULT.raw_value());
+            unsafe {
+                cop0::set_compare(cop0::count().wrapping_add(COMPARE_DISTANCE));
+                asm!("
+                    .set noat
+                    .set noreorder
+                    LA $16, 1f
+                    MTC0 {status}, $12
+                    NOP
+                    JR {target}
+                    NOP
+                    1:
+                ", status = in(reg) status.raw_value(), target = in(reg) target, out("$16") _)
+            }
+            Ok(())
+        });
+        clear_exception_return_override();
+        // Acknowledge the timer interrupt
+        unsafe { cop0::set_compare(cop0::count().wrapping_sub(1)); }
+        let exception_context = exception_context?;
+
+        let start = target as i32 as u64;
+        let end = start + size;
+        if (exception_context.exceptpc < start) || (exception_context.exceptpc >= end) {
+            return Err(format!("ExceptPC {:#x} is expected to point into '{}' at {:#x}", exception_context.exceptpc, name, start));
+        }
+        soft_assert_eq(exception_context.cause.branch_delay(), false, "Cause.BD (the hole only contains NOPs)")?;
+        soft_assert_eq(exception_context.cause.interrupt_compare(), true, "Cause.IP7")?;
+
+        Ok(())
+    }
+}
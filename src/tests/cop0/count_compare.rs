@@ -0,0 +1,242 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::arch::asm;
+
+use crate::cop0;
+use crate::cop0::{CauseException, RegisterIndex, Status};
+use crate::exception_handler::{clear_exception_return_override, drain_seen_exception, expect_exception, set_exception_status_override};
+use crate::tests::{Level, NamedValue, Test};
+use crate::tests::soft_asserts::{soft_assert_eq, soft_assert_eq2};
+
+// Notes:
+// - Count increments every other PClock cycle. Once it equals Compare, Cause.IP7 is set
+// - IP7 stays set until Compare is written (even if the same value is written again)
+// - The timer interrupt is only taken if Status.IM7 and IE are set and EXL and ERL are clear
+
+/// Number of iterations of the busy loop in [spin]. Each iteration is two instructions, so this is a bit
+/// more than 4000 cycles or 2000 Count increments
+const SPIN_ITERATIONS: u32 = 2000;
+
+/// Number of Count increments until Compare is reached
+const COMPARE_DISTANCE: u32 = 200;
+
+/// Enables the given Status, spins for a while and disables it again
+fn spin(status_on: Status, status_off: Status) {
+    unsafe {
+        asm!("
+            .set noreorder
+            MTC0 {on}, $12
+            NOP
+            1:
+            BNE {counter}, $0, 1b
+            ADDIU {counter}, {counter}, -1
+            MTC0 {off}, $12
+            NOP
+            NOP
+        ", counter = inout(reg) SPIN_ITERATIONS => _, on = in(reg) status_on.raw_value(), off = in(reg) status_off.raw_value())
+    }
+}
+
+const fn status_timer_enabled() -> Status {
+    Status::DEFAULT.with_interrupt_mask_compare(true).with_ie(true)
+}
+
+/// (description, Status while the timer interrupt is pending)
+const MASKED_CASES: [(&str, Status); 4] = [
+    ("Status.IE clear", status_timer_enabled().with_ie(false)),
+    ("Status.IM7 clear", status_timer_enabled().with_interrupt_mask_compare(false)),
+    ("Status.EXL set", status_timer_enabled().with_exl(true)),
+    ("Status.ERL set", status_timer_enabled().with_erl(true)),
+];
+
+/// Writes Compare so that IP7 is acknowledged and won't be raised again any time soon
+fn acknowledge() {
+    unsafe { cop0::set_compare(cop0::count().wrapping_sub(1)); }
+}
+
+pub struct CompareReadWrite {}
+
+impl Test for CompareReadWrite {
+    fn name(&self) -> &str { "Compare (read/write)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        for value in [0xFFFF_FFFFu32, 0x1234_5678, 0] {
+            unsafe { cop0::set_compare(value); }
+            soft_assert_eq2(cop0::compare(), value, || format!("Compare after writing {:#x}", value))?;
+        }
+        acknowledge();
+        Ok(())
+    }
+}
+
+pub struct CompareMatchSetsIP7 {}
+
+impl Test for CompareMatchSetsIP7 {
+    fn name(&self) -> &str { "Count/Compare match sets Cause.IP7" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        acknowledge();
+        soft_assert_eq(cop0::cause().interrupt_compare(), false, "Cause.IP7 after writing Compare")?;
+
+        let compare = cop0::count().wrapping_add(COMPARE_DISTANCE);
+        unsafe { cop0::set_compare(compare); }
+        soft_assert_eq(cop0::cause().interrupt_compare(), false, "Cause.IP7 before Count reached Compare")?;
+
+        while cop0::count().wrapping_sub(compare) > 0x8000_0000 {}
+        soft_assert_eq(cop0::cause().interrupt_compare(), true, "Cause.IP7 after Count reached Compare")?;
+
+        // Count is past Compare now. IP7 stays set
+        spin(Status::DEFAULT, Status::DEFAULT);
+        soft_assert_eq(cop0::cause().interrupt_compare(), true, "Cause.IP7 a while after Count reached Compare")?;
+
+        acknowledge();
+        Ok(())
+    }
+}
+
+pub struct CompareWriteClearsIP7 {}
+
+impl Test for CompareWriteClearsIP7 {
+    fn name(&self) -> &str { "Writing Compare clears Cause.IP7" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let compare = cop0::count().wrapping_add(COMPARE_DISTANCE);
+        unsafe { cop0::set_compare(compare); }
+        while cop0::count().wrapping_sub(compare) > 0x8000_0000 {}
+        soft_assert_eq(cop0::cause().interrupt_compare(), true, "Cause.IP7 after Count reached Compare")?;
+
+        // Writing the same value again also acknowledges
+        unsafe { cop0::set_compare(compare); }
+        soft_assert_eq(cop0::cause().interrupt_compare(), false, "Cause.IP7 after writing the same value to Compare")?;
+        soft_assert_eq(cop0::compare(), compare, "Compare after writing it again")?;
+        Ok(())
+    }
+}
+
+pub struct CountIncrementRate {}
+
+impl Test for CountIncrementRate {
+    fn name(&self) -> &str { "Count (increments every other cycle)" }
+
+    fn level(&self) -> Level { Level::Timing }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        // 200 NOPs between the two reads: 201 cycles in total. Depending on the phase, that's 100 or 101 increments.
+        // Run twice so that the second run comes from the instruction cache
+        let mut delta = 0;
+        for _ in 0..2 {
+            let before: u32;
+            let after: u32;
+            unsafe {
+                asm!("
+                    .set noreorder
+                    .balign 32
+                    MFC0 {before}, ${COUNT}
+                    nop; nop; nop; nop; nop; nop; nop; nop; nop; nop;
+                    nop; nop; nop; nop; nop; nop; nop; nop; nop; nop;
+                    nop; nop; nop; nop; nop; nop; nop; nop; nop; nop;
+                    nop; nop; nop; nop; nop; nop; nop; nop; nop; nop;
+                    nop; nop; nop; nop; nop; nop; nop; nop; nop; nop;
+                    nop; nop; nop; nop; nop; nop; nop; nop; nop; nop;
+                    nop; nop; nop; nop; nop; nop; nop; nop; nop; nop;
+                    nop; nop; nop; nop; nop; nop; nop; nop; nop; nop;
+                    nop; nop; nop; nop; nop; nop; nop; nop; nop; nop;
+                    nop; nop; nop; nop; nop; nop; nop; nop; nop; nop;
+                    nop; nop; nop; nop; nop; nop; nop; nop; nop; nop;
+                    nop; nop; nop; nop; nop; nop; nop; nop; nop; nop;
+                    nop; nop; nop; nop; nop; nop; nop; nop; nop; nop;
+                    nop; nop; nop; nop; nop; nop; nop; nop; nop; nop;
+                    nop; nop; nop; nop; nop; nop; nop; nop; nop; nop;
+                    nop; nop; nop; nop; nop; nop; nop; nop; nop; nop;
+                    nop; nop; nop; nop; nop; nop; nop; nop; nop; nop;
+                    nop; nop; nop; nop; nop; nop; nop; nop; nop; nop;
+                    nop; nop; nop; nop; nop; nop; nop; nop; nop; nop;
+                    nop; nop; nop; nop; nop; nop; nop; nop; nop; nop;
+                    MFC0 {after}, ${COUNT}
+                ", before = out(reg) before, after = out(reg) after, COUNT = const RegisterIndex::Count as usize)
+            }
+            delta = after.wrapping_sub(before);
+        }
+        soft_assert_eq2(delta == 100 || delta == 101, true, || format!("Count was expected to increment by 100 or 101 over 201 cycles, but it incremented by {}", delta))
+    }
+}
+
+pub struct TimerInterrupt {}
+
+impl Test for TimerInterrupt {
+    fn name(&self) -> &str { "Count/Compare timer interrupt" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        // Return with interrupts disabled, otherwise the still pending interrupt would be taken again
+        set_exception_status_override(Status::DEFAULT.raw_value());
+        let result = expect_exception(CauseException::Int, 0, || {
+            unsafe { cop0::set_compare(cop0::count().wrapping_add(COMPARE_DISTANCE)); }
+            spin(status_timer_enabled(), Status::DEFAULT);
+            Ok(())
+        });
+        clear_exception_return_override();
+        unsafe { cop0::set_status(Status::DEFAULT); }
+        let exception_context = result?;
+
+        soft_assert_eq(exception_context.k0_exception_vector, 0xFFFFFFFF_80000180, "Exception Vector")?;
+        soft_assert_eq(exception_context.cause.exception(), Ok(CauseException::Int), "Cause.exception")?;
+        soft_assert_eq(exception_context.cause.interrupt_compare(), true, "Cause.IP7")?;
+        soft_assert_eq(exception_context.status, status_timer_enabled().with_exl(true).raw_value(), "Status")?;
+
+        // The exception itself doesn't acknowledge
+        soft_assert_eq(cop0::cause().interrupt_compare(), true, "Cause.IP7 after the exception")?;
+        acknowledge();
+        soft_assert_eq(cop0::cause().interrupt_compare(), false, "Cause.IP7 after writing Compare")?;
+        Ok(())
+    }
+}
+
+pub struct TimerInterruptMasked {}
+
+impl Test for TimerInterruptMasked {
+    fn name(&self) -> &str { "Count/Compare timer interrupt (masked)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        MASKED_CASES.iter().map(|case| NamedValue::boxed(case.0, *case)).collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (case, status) = *NamedValue::get::<(&'static str, Status)>(value);
+
+        // Prevent an exception storm in case the interrupt is (wrongly) taken
+        set_exception_status_override(Status::DEFAULT.raw_value());
+        unsafe { cop0::set_compare(cop0::count().wrapping_add(COMPARE_DISTANCE)); }
+        spin(status, Status::DEFAULT);
+        clear_exception_return_override();
+
+        let ip7 = cop0::cause().interrupt_compare();
+        acknowledge();
+        if let Some((exception_context, _)) = drain_seen_exception() {
+            return Err(format!("Timer interrupt was taken even though {}. ExceptPC={:#x} Cause={:#x}", case, exception_context.exceptpc, exception_context.cause.raw_value()));
+        }
+        soft_assert_eq(ip7, true, "Cause.IP7 is expected to be set even though the interrupt wasn't taken")
+    }
+}
@@ -821,9 +821,13 @@ impl Test for CacheErrorMasking {
 }
 
 mod cache_common;
+mod count_compare;
 mod icache_cache;
 mod icache_functional;
 mod dcache_cache;
+mod watch;
+pub use count_compare::*;
 pub use icache_cache::*;
 pub use icache_functional::*;
 pub use dcache_cache::*;
+pub use watch::*;
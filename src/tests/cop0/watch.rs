@@ -0,0 +1,211 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::arch::asm;
+
+use crate::cop0;
+use crate::cop0::{CauseException, Status, WatchLo};
+use crate::exception_handler::{drain_seen_exception, expect_exception};
+use crate::tests::{Level, NamedValue, Test};
+use crate::tests::soft_asserts::{soft_assert_eq, soft_assert_eq2};
+use crate::uncached_memory::UncachedHeapMemory;
+
+// WatchLo/WatchHi:
+// - WatchLo holds bits 3..=31 of a physical address plus a read (bit 1) and a write (bit 0) enable. Bit 2 reads back as 0
+// - WatchHi holds bits 32..=35 of the physical address. All other bits read back as 0
+// - Any load (if R is set) or store (if W is set) that touches the watched doubleword raises a Watch exception (23).
+//   ExceptPC points to the load/store
+// - No Watch exception is raised while Status.EXL is set (and it isn't raised later either)
+
+#[derive(Clone, Copy, Debug)]
+enum Access {
+    LB,
+    LW,
+    LD,
+    SB,
+    SW,
+    SD,
+}
+
+/// (name, WatchLo.R, WatchLo.W, access, offset relative to the watched doubleword, Watch exception expected)
+type Case = (&'static str, bool, bool, Access, i32, bool);
+
+const CASES: [Case; 15] = [
+    ("R: LW of lower word", true, false, Access::LW, 0, true),
+    ("R: LW of upper word", true, false, Access::LW, 4, true),
+    ("R: LB of last byte", true, false, Access::LB, 7, true),
+    ("R: LD", true, false, Access::LD, 0, true),
+    ("R: LW of previous doubleword", true, false, Access::LW, -4, false),
+    ("R: LW of next doubleword", true, false, Access::LW, 8, false),
+    ("R: SW", true, false, Access::SW, 0, false),
+    ("W: SW of upper word", false, true, Access::SW, 4, true),
+    ("W: SB of fourth byte", false, true, Access::SB, 3, true),
+    ("W: SD", false, true, Access::SD, 0, true),
+    ("W: LW", false, true, Access::LW, 0, false),
+    ("RW: LW", true, true, Access::LW, 0, true),
+    ("RW: SW", true, true, Access::SW, 0, true),
+    ("none: LW", false, false, Access::LW, 0, false),
+    ("none: SW", false, false, Access::SW, 0, false),
+];
+
+/// Performs the access and returns the address of the load/store instruction
+fn access(access: Access, address: u32) -> u32 {
+    macro_rules! access {
+        ($instruction:literal) => {{
+            let pc: u32;
+            unsafe {
+                asm!(concat!("
+                    .set noat
+                    LA {pc}, 1f
+                    1:
+                    ", $instruction, " {value}, 0({address})"),
+                    pc = out(reg) pc, value = inout(reg) 0u32 => _, address = in(reg) address)
+            }
+            pc
+        }};
+    }
+
+    match access {
+        Access::LB => access!("LB"),
+        Access::LW => access!("LW"),
+        Access::LD => access!("LD"),
+        Access::SB => access!("SB"),
+        Access::SW => access!("SW"),
+        Access::SD => access!("SD"),
+    }
+}
+
+/// Three doublewords of memory. The one in the middle is the one that is being watched
+fn watched_memory() -> (UncachedHeapMemory<u64>, u32, u32) {
+    let mut memory = UncachedHeapMemory::<u64>::new(3);
+    let address = memory.as_ptr() as u32 + 8;
+    let physical = memory.start_phyiscal() as u32 + 8;
+    (memory, address, physical)
+}
+
+pub struct WatchLoHiMasking {}
+
+impl Test for WatchLoHiMasking {
+    fn name(&self) -> &str { "WatchLo/WatchHi (masking)" }
+
+    fn level(&self) -> Level { Level::Weird }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        // Watch a physical address that nothing here touches
+        unsafe { cop0::set_watch_lo_32(0xFFFFFFFF); }
+        let watch_lo_all = cop0::watch_lo_32();
+        unsafe { cop0::set_watch_lo_32(0x12345677); }
+        let watch_lo_pattern = cop0::watch_lo_32();
+        unsafe { cop0::set_watch_lo_32(0); }
+        let watch_lo_zero = cop0::watch_lo_32();
+
+        unsafe { cop0::set_watch_hi(0xFFFFFFFF); }
+        let watch_hi_all = cop0::watch_hi();
+        unsafe { cop0::set_watch_hi(0); }
+        let watch_hi_zero = cop0::watch_hi();
+
+        soft_assert_eq(watch_lo_all, 0xFFFFFFFB, "WatchLo written with 0xFFFFFFFF")?;
+        soft_assert_eq(watch_lo_pattern, 0x12345673, "WatchLo written with 0x12345677")?;
+        soft_assert_eq(watch_lo_zero, 0, "WatchLo written with 0")?;
+        soft_assert_eq(watch_hi_all, 0xF, "WatchHi written with 0xFFFFFFFF")?;
+        soft_assert_eq(watch_hi_zero, 0, "WatchHi written with 0")?;
+
+        Ok(())
+    }
+}
+
+pub struct WatchException {}
+
+impl Test for WatchException {
+    fn name(&self) -> &str { "Watch exception" }
+
+    fn level(&self) -> Level { Level::RarelyUsed }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        CASES.iter().map(|case| NamedValue::boxed(case.0, *case)).collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (name, read, write, kind, offset, expected) = *NamedValue::get::<Case>(value);
+        let (_memory, address, physical) = watched_memory();
+        let address = address.wrapping_add(offset as u32);
+
+        unsafe {
+            cop0::set_watch_hi(0);
+            cop0::set_watch_lo(WatchLo::from_physical_address(physical, read, write));
+        }
+        if expected {
+            let mut pc = 0u32;
+            let exception_context = expect_exception(CauseException::Watch, 1, || {
+                pc = access(kind, address);
+                Ok(())
+            });
+            unsafe { cop0::set_watch_lo_32(0); }
+            let exception_context = exception_context?;
+
+            soft_assert_eq2(exception_context.k0_exception_vector, 0xFFFFFFFF_80000180, || format!("Exception vector for '{}'", name))?;
+            soft_assert_eq2(exception_context.exceptpc, pc as i32 as u64, || format!("ExceptPC for '{}'", name))?;
+            soft_assert_eq2(exception_context.cause.branch_delay(), false, || format!("Cause.BD for '{}'", name))?;
+        } else {
+            access(kind, address);
+            unsafe { cop0::set_watch_lo_32(0); }
+            if let Some((exception_context, _)) = drain_seen_exception() {
+                return Err(format!("'{}' wasn't expected to raise an exception, but got {:?}. ExceptPC={:#x}", name, exception_context.cause.exception(), exception_context.exceptpc));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct WatchExceptionSuppressedByEXL {}
+
+impl Test for WatchExceptionSuppressedByEXL {
+    fn name(&self) -> &str { "Watch exception (suppressed while EXL=1)" }
+
+    fn level(&self) -> Level { Level::RarelyUsed }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let (_memory, address, physical) = watched_memory();
+
+        unsafe {
+            cop0::set_watch_hi(0);
+            cop0::set_watch_lo(WatchLo::from_physical_address(physical, true, true));
+
+            // ExceptPC is preset to the NOP after the store. A (wrong) exception skips that NOP and continues from there
+            asm!("
+                .set noat
+                .set noreorder
+                LA $4, 2f
+                DMTC0 $4, $14
+                MTC0 {status_exl}, $12
+                NOP
+                NOP
+                LW $2, 0($3)
+                SW $0, 0($3)
+                2:
+                NOP
+                MTC0 {status}, $12
+                NOP
+                NOP
+            ", in("$3") address,
+                status_exl = in(reg) Status::DEFAULT.with_exl(true).raw_value(),
+                status = in(reg) Status::DEFAULT.raw_value(),
+                out("$2") _, out("$4") _);
+
+            cop0::set_watch_lo_32(0);
+        }
+
+        if let Some((exception_context, _)) = drain_seen_exception() {
+            return Err(format!("No exception expected while EXL=1, but got {:?}. ExceptPC={:#x}", exception_context.cause.exception(), exception_context.exceptpc));
+        }
+
+        Ok(())
+    }
+}
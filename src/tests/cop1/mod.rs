@@ -1,6 +1,7 @@
 pub mod compares;
 pub mod full_vs_half_mode;
 pub mod randomized;
+pub mod unimplemented;
 
 use alloc::boxed::Box;
 use alloc::{format, vec};
@@ -0,0 +1,297 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+
+use crate::assembler::{Assembler, FR, GPR};
+use crate::cop0::{Cause, CauseException, preset_cause_to_copindex2, Status};
+use crate::cop1::{fcsr, FCSR, FCSRFlags, FConst, set_fcsr};
+use crate::exception_handler::{drain_seen_exception, expect_exception};
+use crate::tests::{Level, NamedValue, Test};
+use crate::tests::cop1::compares::FPUSpecialNumber;
+use crate::tests::exception_instructions::slot::Slot;
+use crate::tests::soft_asserts::soft_assert_eq2;
+
+// Which inputs make an instruction fire the unimplemented operation exception (E), as opposed to producing a result.
+// Every arithmetic/convert instruction is run with every class of input, with FS=0 and FS=1 and with various enables:
+// - Signalling NANs and subnormal inputs are always E, no matter what FS is. A subnormal input wins over a quiet NAN
+// - Quiet NANs produce a NAN and set invalid operation. This is a regular exception if it is enabled
+// - MOV.S/MOV.D never look at the value, so they neither trap nor set any flags
+// - CVT.S.S and CVT.D.D are always E, even with regular inputs
+// - Conversions to W/L are E for any NAN, subnormal or out of range input. CVT.S.L/CVT.D.L are E for inputs that
+//   don't fit into 55 bits
+// - A result that underflows is E with FS=0. With FS=1 it is flushed and sets underflow and inexact, unless any of
+//   those two is enabled, which makes it E again
+// - E can't be masked. When it fires, it is the only cause bit and the flags and the target register are unchanged
+
+/// Value of the target register before an instruction is executed
+const TARGET_REG_DEFAULT: u64 = 0x01234567_89ABCDEF;
+
+/// The enables that every input is run with
+const ENABLES: [FCSRFlags; 4] = [
+    FCSRFlags::NONE,
+    FCSRFlags::new().with_invalid_operation(true),
+    FCSRFlags::new().with_underflow(true).with_inexact_operation(true),
+    FCSRFlags::ALL,
+];
+
+const FLAGS_UNDERFLOW: FCSRFlags = FCSRFlags::new().with_underflow(true).with_inexact_operation(true);
+
+#[derive(Clone, Copy, Debug)]
+enum Format {
+    S,
+    D,
+    W,
+    L,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Kind {
+    /// ADD, SUB, MUL, DIV
+    Binary,
+    /// SQRT, ABS, NEG
+    Unary,
+    Move,
+    /// CVT.D.S, CVT.S.D
+    Convert,
+    /// CVT.S.S, CVT.D.D
+    ConvertToSameFormat,
+    /// CVT/ROUND/TRUNC/CEIL/FLOOR to W/L
+    ConvertToInteger,
+    /// CVT.S/CVT.D from W/L
+    ConvertFromInteger,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Input {
+    Special(FPUSpecialNumber),
+    /// Inputs whose result is too small for a normalized number
+    Underflow,
+    /// Inputs that don't fit into the target (or source) integer
+    OutOfIntegerRange,
+}
+
+const INPUTS: [Input; 8] = [
+    Input::Special(FPUSpecialNumber::Nope),
+    Input::Special(FPUSpecialNumber::QuietNAN),
+    Input::Special(FPUSpecialNumber::SignallingNAN),
+    Input::Special(FPUSpecialNumber::BothNAN),
+    Input::Special(FPUSpecialNumber::Subnormal),
+    Input::Special(FPUSpecialNumber::QuietNANAndSubnormal),
+    Input::Underflow,
+    Input::OutOfIntegerRange,
+];
+
+#[derive(Clone, Copy, Debug)]
+enum Outcome {
+    Flags(FCSRFlags),
+    Unimplemented,
+}
+
+/// Index of the instruction under test within the slot. It is preceded by loading F0, F2 and F4 from scratch space
+/// and followed by storing F4 back
+const INSTRUCTION_INDEX: usize = 3;
+
+/// (name, instruction (fd=F4, fs=F0, ft=F2), source format, kind, operands that underflow (if any))
+type Op = (&'static str, u32, Format, Kind, Option<(f64, f64)>);
+
+const OPS: [Op; 44] = [
+    ("ADD.S", Assembler::make_add(FR::F4, FR::F0, FR::F2).s(), Format::S, Kind::Binary, Some((1.5 * f32::MIN_POSITIVE as f64, -(f32::MIN_POSITIVE as f64)))),
+    ("ADD.D", Assembler::make_add(FR::F4, FR::F0, FR::F2).d(), Format::D, Kind::Binary, Some((1.5 * f64::MIN_POSITIVE, -f64::MIN_POSITIVE))),
+    ("SUB.S", Assembler::make_sub(FR::F4, FR::F0, FR::F2).s(), Format::S, Kind::Binary, Some((1.5 * f32::MIN_POSITIVE as f64, f32::MIN_POSITIVE as f64))),
+    ("SUB.D", Assembler::make_sub(FR::F4, FR::F0, FR::F2).d(), Format::D, Kind::Binary, Some((1.5 * f64::MIN_POSITIVE, f64::MIN_POSITIVE))),
+    ("MUL.S", Assembler::make_mul(FR::F4, FR::F0, FR::F2).s(), Format::S, Kind::Binary, Some((f32::MIN_POSITIVE as f64, 0.5))),
+    ("MUL.D", Assembler::make_mul(FR::F4, FR::F0, FR::F2).d(), Format::D, Kind::Binary, Some((f64::MIN_POSITIVE, 0.5))),
+    ("DIV.S", Assembler::make_div(FR::F4, FR::F0, FR::F2).s(), Format::S, Kind::Binary, Some((f32::MIN_POSITIVE as f64, 2.0))),
+    ("DIV.D", Assembler::make_div(FR::F4, FR::F0, FR::F2).d(), Format::D, Kind::Binary, Some((f64::MIN_POSITIVE, 2.0))),
+    ("SQRT.S", Assembler::make_sqrt(FR::F4, FR::F0).s(), Format::S, Kind::Unary, None),
+    ("SQRT.D", Assembler::make_sqrt(FR::F4, FR::F0).d(), Format::D, Kind::Unary, None),
+    ("ABS.S", Assembler::make_abs(FR::F4, FR::F0).s(), Format::S, Kind::Unary, None),
+    ("ABS.D", Assembler::make_abs(FR::F4, FR::F0).d(), Format::D, Kind::Unary, None),
+    ("NEG.S", Assembler::make_neg(FR::F4, FR::F0).s(), Format::S, Kind::Unary, None),
+    ("NEG.D", Assembler::make_neg(FR::F4, FR::F0).d(), Format::D, Kind::Unary, None),
+    ("MOV.S", Assembler::make_mov(FR::F4, FR::F0).s(), Format::S, Kind::Move, None),
+    ("MOV.D", Assembler::make_mov(FR::F4, FR::F0).d(), Format::D, Kind::Move, None),
+    ("CVT.D.S", Assembler::make_cvt_d(FR::F4, FR::F0).s(), Format::S, Kind::Convert, None),
+    ("CVT.S.D", Assembler::make_cvt_s(FR::F4, FR::F0).d(), Format::D, Kind::Convert, Some((f64::MIN_POSITIVE, 0.0))),
+    ("CVT.S.S", Assembler::make_cvt_s(FR::F4, FR::F0).s(), Format::S, Kind::ConvertToSameFormat, None),
+    ("CVT.D.D", Assembler::make_cvt_d(FR::F4, FR::F0).d(), Format::D, Kind::ConvertToSameFormat, None),
+    ("CVT.W.S", Assembler::make_cvt_w(FR::F4, FR::F0).s(), Format::S, Kind::ConvertToInteger, None),
+    ("CVT.W.D", Assembler::make_cvt_w(FR::F4, FR::F0).d(), Format::D, Kind::ConvertToInteger, None),
+    ("ROUND.W.S", Assembler::make_round_w(FR::F4, FR::F0).s(), Format::S, Kind::ConvertToInteger, None),
+    ("ROUND.W.D", Assembler::make_round_w(FR::F4, FR::F0).d(), Format::D, Kind::ConvertToInteger, None),
+    ("TRUNC.W.S", Assembler::make_trunc_w(FR::F4, FR::F0).s(), Format::S, Kind::ConvertToInteger, None),
+    ("TRUNC.W.D", Assembler::make_trunc_w(FR::F4, FR::F0).d(), Format::D, Kind::ConvertToInteger, None),
+    ("CEIL.W.S", Assembler::make_ceil_w(FR::F4, FR::F0).s(), Format::S, Kind::ConvertToInteger, None),
+    ("CEIL.W.D", Assembler::make_ceil_w(FR::F4, FR::F0).d(), Format::D, Kind::ConvertToInteger, None),
+    ("FLOOR.W.S", Assembler::make_floor_w(FR::F4, FR::F0).s(), Format::S, Kind::ConvertToInteger, None),
+    ("FLOOR.W.D", Assembler::make_floor_w(FR::F4, FR::F0).d(), Format::D, Kind::ConvertToInteger, None),
+    ("CVT.L.S", Assembler::make_cvt_l(FR::F4, FR::F0).s(), Format::S, Kind::ConvertToInteger, None),
+    ("CVT.L.D", Assembler::make_cvt_l(FR::F4, FR::F0).d(), Format::D, Kind::ConvertToInteger, None),
+    ("ROUND.L.S", Assembler::make_round_l(FR::F4, FR::F0).s(), Format::S, Kind::ConvertToInteger, None),
+    ("ROUND.L.D", Assembler::make_round_l(FR::F4, FR::F0).d(), Format::D, Kind::ConvertToInteger, None),
+    ("TRUNC.L.S", Assembler::make_trunc_l(FR::F4, FR::F0).s(), Format::S, Kind::ConvertToInteger, None),
+    ("TRUNC.L.D", Assembler::make_trunc_l(FR::F4, FR::F0).d(), Format::D, Kind::ConvertToInteger, None),
+    ("CEIL.L.S", Assembler::make_ceil_l(FR::F4, FR::F0).s(), Format::S, Kind::ConvertToInteger, None),
+    ("CEIL.L.D", Assembler::make_ceil_l(FR::F4, FR::F0).d(), Format::D, Kind::ConvertToInteger, None),
+    ("FLOOR.L.S", Assembler::make_floor_l(FR::F4, FR::F0).s(), Format::S, Kind::ConvertToInteger, None),
+    ("FLOOR.L.D", Assembler::make_floor_l(FR::F4, FR::F0).d(), Format::D, Kind::ConvertToInteger, None),
+    ("CVT.S.W", Assembler::make_cvt_s(FR::F4, FR::F0).w(), Format::W, Kind::ConvertFromInteger, None),
+    ("CVT.D.W", Assembler::make_cvt_d(FR::F4, FR::F0).w(), Format::W, Kind::ConvertFromInteger, None),
+    ("CVT.S.L", Assembler::make_cvt_s(FR::F4, FR::F0).l(), Format::L, Kind::ConvertFromInteger, None),
+    ("CVT.D.L", Assembler::make_cvt_d(FR::F4, FR::F0).l(), Format::L, Kind::ConvertFromInteger, None),
+];
+
+/// Bit patterns of the two operands (F0 and F2) for the given input. None if the input doesn't apply
+fn operands(format: Format, kind: Kind, underflow: Option<(f64, f64)>, input: Input) -> Option<(u64, u64)> {
+    let bits32 = |f: f32| f.to_bits() as u64;
+    let bits64 = |f: f64| f.to_bits();
+    match input {
+        Input::Special(number) => {
+            let (one, two, quiet, signalling, subnormal) = match format {
+                Format::S => (bits32(1f32), bits32(2f32), bits32(FConst::QUIET_NAN_START_32), bits32(FConst::SIGNALLING_NAN_START_32), bits32(FConst::SUBNORMAL_MIN_POSITIVE_32)),
+                Format::D => (bits64(1f64), bits64(2f64), bits64(FConst::QUIET_NAN_START_64), bits64(FConst::SIGNALLING_NAN_START_64), bits64(FConst::SUBNORMAL_MIN_POSITIVE_64)),
+                Format::W | Format::L => return if number == FPUSpecialNumber::Nope { Some((1, 2)) } else { None },
+            };
+            match number {
+                FPUSpecialNumber::Nope => Some((one, two)),
+                FPUSpecialNumber::QuietNAN => Some((quiet, one)),
+                FPUSpecialNumber::SignallingNAN => Some((signalling, one)),
+                FPUSpecialNumber::Subnormal => Some((subnormal, one)),
+                // These need two operands
+                FPUSpecialNumber::BothNAN if kind == Kind::Binary => Some((signalling, quiet)),
+                FPUSpecialNumber::QuietNANAndSubnormal if kind == Kind::Binary => Some((quiet, subnormal)),
+                FPUSpecialNumber::BothNAN | FPUSpecialNumber::QuietNANAndSubnormal => None,
+            }
+        }
+        Input::Underflow => {
+            let (value1, value2) = underflow?;
+            match format {
+                Format::S => Some((bits32(value1 as f32), bits32(value2 as f32))),
+                _ => Some((bits64(value1), bits64(value2))),
+            }
+        }
+        Input::OutOfIntegerRange => match (kind, format) {
+            (Kind::ConvertToInteger, Format::S) => Some((bits32(1e30f32), 0)),
+            (Kind::ConvertToInteger, Format::D) => Some((bits64(1e30f64), 0)),
+            (Kind::ConvertFromInteger, Format::L) => Some((1u64 << 55, 0)),
+            _ => None,
+        },
+    }
+}
+
+/// What the instruction does with the given input, independent of FS and the enables
+fn outcome(kind: Kind, input: Input) -> Outcome {
+    match input {
+        Input::Special(number) => match (kind, number) {
+            (Kind::ConvertToSameFormat, _) => Outcome::Unimplemented,
+            (Kind::Move, _) | (_, FPUSpecialNumber::Nope) => Outcome::Flags(FCSRFlags::NONE),
+            (Kind::Binary | Kind::Unary | Kind::Convert, FPUSpecialNumber::QuietNAN) => Outcome::Flags(FCSRFlags::new().with_invalid_operation(true)),
+            _ => Outcome::Unimplemented,
+        },
+        Input::Underflow => Outcome::Flags(FLAGS_UNDERFLOW),
+        Input::OutOfIntegerRange => Outcome::Unimplemented,
+    }
+}
+
+/// A slot that loads the operands into F0 and F2 and the default into F4, runs the instruction and stores F4
+fn make_slot(instruction: u32) -> Slot {
+    Slot::new(&[
+        Assembler::make_ldc1(GPR::R0, 0, GPR::V1),
+        Assembler::make_ldc1(GPR::V0, 8, GPR::V1),
+        Assembler::make_ldc1(GPR::A0, 16, GPR::V1),
+        instruction,
+        Assembler::make_sdc1(GPR::A0, 16, GPR::V1),
+    ])
+}
+
+/// Runs the slot with the given operands and returns the bits of the target register
+fn execute(slot: &mut Slot, operands: (u64, u64)) -> u64 {
+    slot.write_scratch(0, operands.0);
+    slot.write_scratch(1, operands.1);
+    slot.write_scratch(2, TARGET_REG_DEFAULT);
+    slot.execute(Status::DEFAULT);
+    slot.read_scratch(2)
+}
+
+/// Runs the instruction and expects an FPE with the given FCSR
+fn expect_fpe(context: &str, slot: &mut Slot, operands: (u64, u64), fcsr_before: FCSR, expected_fcsr: FCSR) -> Result<(), String> {
+    preset_cause_to_copindex2()?;
+    let mut result = 0u64;
+    let exception_context = expect_exception(CauseException::FPE, 1, || {
+        set_fcsr(fcsr_before);
+        result = execute(slot, operands);
+        set_fcsr(FCSR::new());
+        Ok(())
+    });
+    set_fcsr(FCSR::DEFAULT);
+    let exception_context = exception_context?;
+
+    soft_assert_eq2(exception_context.k0_exception_vector, 0xFFFFFFFF_80000180, || format!("Exception vector for {}", context))?;
+    soft_assert_eq2(exception_context.exceptpc, slot.instruction_address(INSTRUCTION_INDEX), || format!("ExceptPC for {}", context))?;
+    soft_assert_eq2(exception_context.cause, Cause::new().with_exception(CauseException::FPE), || format!("Cause for {}", context))?;
+    soft_assert_eq2(exception_context.fcsr, expected_fcsr, || format!("FCSR for {}", context))?;
+    soft_assert_eq2(result, TARGET_REG_DEFAULT, || format!("Target register after {} should be unchanged", context))?;
+
+    Ok(())
+}
+
+/// Runs the instruction and expects it to finish with the given FCSR
+fn expect_no_exception(context: &str, slot: &mut Slot, operands: (u64, u64), fcsr_before: FCSR, expected_fcsr: FCSR) -> Result<(), String> {
+    set_fcsr(fcsr_before);
+    execute(slot, operands);
+    let fcsr_after = fcsr();
+    set_fcsr(FCSR::DEFAULT);
+    if let Some((exception_context, _)) = drain_seen_exception() {
+        return Err(format!("{} wasn't expected to trap, but got {:?}. FCSR={:?}", context, exception_context.cause.exception(), exception_context.fcsr));
+    }
+
+    soft_assert_eq2(fcsr_after, expected_fcsr, || format!("FCSR after {}", context))
+}
+
+fn run_case(name: &str, slot: &mut Slot, kind: Kind, input: Input, operands: (u64, u64), flush_denorm_to_zero: bool, enables: FCSRFlags) -> Result<(), String> {
+    let context = format!("{} with {:?} input (FS={}, enables: {:?})", name, input, flush_denorm_to_zero, enables);
+    let fcsr_before = FCSR::new().with_flush_denorm_to_zero(flush_denorm_to_zero).with_enables(enables);
+    let is_enabled = |flags: FCSRFlags| (enables.raw_value() & flags.raw_value()) != 0;
+
+    match outcome(kind, input) {
+        Outcome::Unimplemented => expect_fpe(&context, slot, operands, fcsr_before, fcsr_before.with_cause_unimplemented_operation(true)),
+        // Underflow is only supported with FS=1 and neither underflow nor inexact enabled
+        Outcome::Flags(flags) if (flags == FLAGS_UNDERFLOW) && (!flush_denorm_to_zero || is_enabled(flags)) =>
+            expect_fpe(&context, slot, operands, fcsr_before, fcsr_before.with_cause_unimplemented_operation(true)),
+        Outcome::Flags(flags) if is_enabled(flags) => expect_fpe(&context, slot, operands, fcsr_before, fcsr_before.with_maskable_causes(flags)),
+        Outcome::Flags(flags) => expect_no_exception(&context, slot, operands, fcsr_before, fcsr_before.with_maskable_causes(flags).with_flags(flags)),
+    }
+}
+
+pub struct UnimplementedOperationMatrix {}
+
+impl Test for UnimplementedOperationMatrix {
+    fn name(&self) -> &str { "COP1 unimplemented operation (matrix over inputs, FS and enables)" }
+
+    fn level(&self) -> Level { Level::Weird }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        OPS.iter().map(|op| NamedValue::boxed(op.0, *op)).collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (name, instruction, format, kind, underflow) = *NamedValue::get::<Op>(value);
+        let mut slot = make_slot(instruction);
+
+        for input in INPUTS {
+            let operands = match operands(format, kind, underflow, input) {
+                Some(operands) => operands,
+                None => continue,
+            };
+            for flush_denorm_to_zero in [false, true] {
+                for enables in ENABLES {
+                    run_case(name, &mut slot, kind, input, operands, flush_denorm_to_zero, enables)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -9,6 +9,9 @@ use crate::exception_handler::expect_exception;
 use crate::tests::{Level, Test};
 use crate::tests::soft_asserts::soft_assert_eq;
 
+pub mod reserved;
+pub mod slot;
+
 pub struct Break {}
 
 impl Test for Break {
@@ -0,0 +1,221 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+use arbitrary_int::u2;
+
+use crate::cop0::{Cause, CauseException, preset_cause_to_copindex2, Status};
+use crate::cop1::{FCSR, set_fcsr};
+use crate::exception_handler::{drain_seen_exception, expect_exception};
+use crate::tests::{Level, NamedValue, Test};
+use crate::tests::exception_instructions::slot::Slot;
+use crate::tests::soft_asserts::soft_assert_eq2;
+
+// Sweep over every encoding that the VR4300 doesn't implement. Each instruction is patched into an uncached slot
+// and executed once:
+// - Unused primary opcodes, SPECIAL functions, REGIMM rt values and COP0 rs/functions fire RI. Cause.CE is cleared
+// - COP3 (and LWC3/SWC3) don't exist, so they fire RI as well, no matter whether COP3 is usable. Cause.CE stays 0
+// - COP2 exists as far as moves and LWC2/LDC2/SWC2/SDC2 are concerned. While unusable, all of them fire CpU with
+//   Cause.CE=2. While usable, DCFC2/DCTC2 fire RI (with Cause.CE=2) and everything else executes
+// - Unused COP1 encodings (including W/L arithmetic and DCFC1/DCTC1) fire FPE with only the unimplemented cause bit set
+//   while COP1 is usable. While unusable, they fire CpU with Cause.CE=1 and leave FCSR alone
+// - Fields that an instruction doesn't use (e.g. shamt of ADD or rs of SLL) are ignored and never trap
+
+const PRIMARY_RESERVED: [u32; 7] = [0x13, 0x1C, 0x1D, 0x1E, 0x1F, 0x33, 0x3B];
+/// COP3, LWC3, SWC3
+const COP3_OPCODES: [u32; 3] = [0x13, 0x33, 0x3B];
+const SPECIAL_RESERVED: [u32; 12] = [1, 5, 10, 11, 14, 21, 40, 41, 53, 55, 57, 61];
+const REGIMM_IMPLEMENTED: [u32; 14] = [0, 1, 2, 3, 8, 9, 10, 11, 12, 14, 16, 17, 18, 19];
+const COP0_RS_RESERVED: [u32; 12] = [2, 3, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+/// TLBR, TLBWI, TLBWR, TLBP, ERET
+const COP0_CO_IMPLEMENTED: [u32; 5] = [1, 2, 6, 8, 24];
+/// DCFC1, DCTC1 and everything that isn't MF/DMF/CF/MT/DMT/CT/BC or a format
+const COP1_RS_RESERVED: [u32; 21] = [3, 7, 9, 10, 11, 12, 13, 14, 15, 18, 19, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31];
+
+const OPCODE_SPECIAL: u32 = 0x00;
+const OPCODE_REGIMM: u32 = 0x01;
+const OPCODE_COP0: u32 = 0x10;
+const OPCODE_COP1: u32 = 0x11;
+const COP_RS_CO: u32 = 0x10;
+const FMT_S: u32 = 16;
+const FMT_D: u32 = 17;
+const FMT_W: u32 = 20;
+const FMT_L: u32 = 21;
+
+/// (name, instruction, exception while COP2 is usable (None if it executes)). Loads and stores go to the scratch
+/// space of the slot
+const COP2_ENCODINGS: [(&str, u32, Option<CauseException>); 7] = [
+    ("COP2 (MFC2 $0, $0)", 0x48000000, None),
+    ("DCFC2", 0x48600000, Some(CauseException::RI)),
+    ("DCTC2", 0x48E00000, Some(CauseException::RI)),
+    ("LWC2", 0xC8600000, None),
+    ("LDC2", 0xD8600000, None),
+    ("SWC2", 0xE8600000, None),
+    ("SDC2", 0xF8600000, None),
+];
+
+/// Encodings that use fields which are unused for the instruction. (name, instruction). They target $0 or leave
+/// registers unchanged
+const ODD_ENCODINGS: [(&str, u32); 14] = [
+    ("ADD with shamt=5", 0x00000160),
+    ("ADDU with shamt=31", 0x000007E1),
+    ("AND with shamt=5", 0x00000164),
+    ("SLT with shamt=5", 0x0000016A),
+    ("SLL with rs=1", 0x00200000),
+    ("SRL with rs=1", 0x00200002),
+    ("DSLL32 with rs=1", 0x0020003C),
+    ("MFHI with rs=31, rt=31", 0x03FF0010),
+    ("MFLO with shamt=5", 0x00000152),
+    ("SYNC with stype=31", 0x000007CF),
+    ("LUI with rs=1", 0x3C200000),
+    ("MFC0 with lower bits set", 0x40006005),
+    ("CFC1 with lower bits set", 0x4440F803),
+    ("MOV.S with ft=1", 0x46010006),
+];
+
+fn reserved_encodings() -> Vec<u32> {
+    let mut result = Vec::new();
+    result.extend(PRIMARY_RESERVED.iter().map(|op| op << 26));
+    result.extend(SPECIAL_RESERVED.iter().map(|funct| (OPCODE_SPECIAL << 26) | funct));
+    result.extend((0..32).filter(|rt| !REGIMM_IMPLEMENTED.contains(rt)).map(|rt| (OPCODE_REGIMM << 26) | (rt << 16)));
+    result.extend(COP0_RS_RESERVED.iter().map(|rs| (OPCODE_COP0 << 26) | (rs << 21)));
+    result.extend((0..64).filter(|funct| !COP0_CO_IMPLEMENTED.contains(funct)).map(|funct| (OPCODE_COP0 << 26) | (COP_RS_CO << 21) | funct));
+    result
+}
+
+fn cop1_unimplemented_encodings() -> Vec<u32> {
+    let cop1 = |rs: u32, funct: u32| (OPCODE_COP1 << 26) | (rs << 21) | funct;
+    let mut result = Vec::new();
+    result.extend(COP1_RS_RESERVED.iter().map(|rs| cop1(*rs, 0)));
+    for fmt in [FMT_S, FMT_D] {
+        result.extend((16..32).chain([34, 35]).chain(38..48).map(|funct| cop1(fmt, funct)));
+    }
+    // CVT.S.S and CVT.D.D
+    result.push(cop1(FMT_S, 32));
+    result.push(cop1(FMT_D, 33));
+    for fmt in [FMT_W, FMT_L] {
+        result.extend((0..64).filter(|funct| (*funct != 32) && (*funct != 33)).map(|funct| cop1(fmt, funct)));
+    }
+    result
+}
+
+/// Executes the instruction and checks the exception, its Cause (including CE) and FCSR
+fn expect_reserved(instruction: u32, status: Status, exception: CauseException, cop_index: u2, fcsr: FCSR) -> Result<(), String> {
+    preset_cause_to_copindex2()?;
+    let slot = Slot::new(&[instruction]);
+    set_fcsr(FCSR::DEFAULT);
+    let exception_context = expect_exception(exception, 1, || {
+        slot.execute(status);
+        Ok(())
+    })?;
+
+    soft_assert_eq2(exception_context.k0_exception_vector, 0xFFFFFFFF_80000180, || format!("Exception Vector for {:#010x}", instruction))?;
+    soft_assert_eq2(exception_context.exceptpc, slot.instruction_address(0), || format!("ExceptPC for {:#010x}", instruction))?;
+    soft_assert_eq2(exception_context.cause, Cause::new().with_exception(exception).with_coprocessor_error(cop_index), || format!("Cause for {:#010x}", instruction))?;
+    soft_assert_eq2(exception_context.fcsr, fcsr, || format!("FCSR for {:#010x}", instruction))?;
+
+    Ok(())
+}
+
+pub struct ReservedInstructionSweep {}
+
+impl Test for ReservedInstructionSweep {
+    fn name(&self) -> &str { "Reserved instruction (sweep over unused encodings)" }
+
+    fn level(&self) -> Level { Level::Weird }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        let cop3_usable = COP3_OPCODES.iter()
+            .map(|op| NamedValue::boxed(format!("{:#010x} (COP3 usable)", op << 26), (Status::DEFAULT.with_cop3usable(true), op << 26)));
+        reserved_encodings().into_iter()
+            .map(|instruction| NamedValue::boxed(format!("{:#010x}", instruction), (Status::DEFAULT, instruction)))
+            .chain(cop3_usable)
+            .collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (status, instruction) = *NamedValue::get::<(Status, u32)>(value);
+        expect_reserved(instruction, status, CauseException::RI, u2::new(0), FCSR::DEFAULT)
+    }
+}
+
+pub struct COP1UnimplementedSweep {}
+
+impl Test for COP1UnimplementedSweep {
+    fn name(&self) -> &str { "COP1 unimplemented (sweep over unused encodings)" }
+
+    fn level(&self) -> Level { Level::Weird }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        cop1_unimplemented_encodings().into_iter()
+            .flat_map(|instruction| [(true, instruction), (false, instruction)])
+            .map(|value| Box::new(value) as Box<dyn Any>)
+            .collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (cop1_usable, instruction) = *value.downcast_ref::<(bool, u32)>().unwrap();
+        if cop1_usable {
+            expect_reserved(instruction, Status::DEFAULT.with_cop1usable(true), CauseException::FPE, u2::new(0), FCSR::DEFAULT.with_cause_unimplemented_operation(true))
+        } else {
+            expect_reserved(instruction, Status::DEFAULT.with_cop1usable(false), CauseException::CopUnusable, u2::new(1), FCSR::DEFAULT)
+        }
+    }
+}
+
+pub struct COP2Sweep {}
+
+impl Test for COP2Sweep {
+    fn name(&self) -> &str { "COP2 encodings (sweep over usable and unusable)" }
+
+    fn level(&self) -> Level { Level::Weird }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        COP2_ENCODINGS.iter()
+            .flat_map(|case| [(true, *case), (false, *case)])
+            .map(|(usable, case)| NamedValue::boxed(format!("{} (COP2 {})", case.0, if usable { "usable" } else { "unusable" }), (usable, case)))
+            .collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (cop2_usable, (name, instruction, exception_when_usable)) = *NamedValue::get::<(bool, (&'static str, u32, Option<CauseException>))>(value);
+        if !cop2_usable {
+            return expect_reserved(instruction, Status::DEFAULT.with_cop2usable(false), CauseException::CopUnusable, u2::new(2), FCSR::DEFAULT);
+        }
+        match exception_when_usable {
+            Some(exception) => expect_reserved(instruction, Status::DEFAULT.with_cop2usable(true), exception, u2::new(2), FCSR::DEFAULT),
+            None => {
+                Slot::new(&[instruction]).execute(Status::DEFAULT.with_cop2usable(true));
+                if let Some((exception_context, _)) = drain_seen_exception() {
+                    return Err(format!("'{}' ({:#010x}) wasn't expected to trap while COP2 is usable, but got {:?}", name, instruction, exception_context.cause.exception()));
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+pub struct OddEncodingsDontTrap {}
+
+impl Test for OddEncodingsDontTrap {
+    fn name(&self) -> &str { "Unused instruction fields don't trap" }
+
+    fn level(&self) -> Level { Level::Weird }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        ODD_ENCODINGS.iter().map(|case| NamedValue::boxed(case.0, *case)).collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (name, instruction) = *NamedValue::get::<(&'static str, u32)>(value);
+
+        Slot::new(&[instruction]).execute(Status::DEFAULT);
+        if let Some((exception_context, _)) = drain_seen_exception() {
+            return Err(format!("'{}' ({:#010x}) wasn't expected to trap, but got {:?}", name, instruction, exception_context.cause.exception()));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use core::arch::asm;
+
+use crate::assembler::{Assembler, GPR};
+use crate::cop0;
+use crate::cop0::Status;
+use crate::uncached_memory::UncachedHeapMemory;
+
+/// Number of doublewords of scratch space at the start of a slot
+const SCRATCH_DOUBLEWORDS: usize = 4;
+
+/// Index (in words) of the first instruction
+const CODE_START: usize = SCRATCH_DOUBLEWORDS * 2;
+
+/// An uncached slot that holds a few instructions followed by a return. It is preceded by scratch space that the
+/// instructions can access through $3
+pub struct Slot {
+    memory: UncachedHeapMemory<u32>,
+}
+
+impl Slot {
+    pub fn new(instructions: &[u32]) -> Self {
+        let mut memory = UncachedHeapMemory::<u32>::new(CODE_START + instructions.len() + 2);
+        for i in 0..CODE_START {
+            memory.write(i, 0);
+        }
+        for (i, instruction) in instructions.iter().enumerate() {
+            memory.write(CODE_START + i, *instruction);
+        }
+        memory.write(CODE_START + instructions.len(), Assembler::make_jr(GPR::A0));
+        memory.write(CODE_START + instructions.len() + 1, Assembler::make_nop());
+
+        Self { memory }
+    }
+
+    /// The (sign extended) address of the instruction with the given index, e.g. to compare against ExceptPC
+    pub fn instruction_address(&self, index: usize) -> u64 {
+        unsafe { self.memory.as_ptr().add(CODE_START + index) as u32 as i32 as u64 }
+    }
+
+    pub fn write_scratch(&mut self, index: usize, value: u64) {
+        assert!(index < SCRATCH_DOUBLEWORDS);
+        self.memory.write(index * 2, (value >> 32) as u32);
+        self.memory.write(index * 2 + 1, value as u32);
+    }
+
+    pub fn read_scratch(&mut self, index: usize) -> u64 {
+        assert!(index < SCRATCH_DOUBLEWORDS);
+        ((self.memory.read(index * 2) as u64) << 32) | (self.memory.read(index * 2 + 1) as u64)
+    }
+
+    /// Runs the instructions with the given Status. An exception handler that skips the faulting instruction continues
+    /// with the next one. The instructions are free to use $f0, $f2 and $f4
+    #[inline(never)]
+    pub fn execute(&self, status: Status) {
+        unsafe {
+            cop0::set_status(status);
+            asm!("
+                .set noat
+                .set noreorder
+                JALR $4, $5
+                NOP
+            ", in("$3") self.memory.as_ptr() as u32, in("$5") self.instruction_address(0) as u32,
+                out("$4") _, out("$f0") _, out("$f2") _, out("$f4") _);
+            cop0::set_status(Status::DEFAULT);
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Runtime selection of tests, without having to recompile the rom.
+//!
+//! The filter is a 256 byte block that starts with the magic `N64STFLT`. It is read from (in order
+//! of priority):
+//! 1. The ISViewer mailbox at cart address 0x13FF_1000, which allows an emulator to provide it
+//!    on startup,
+//! 2. The [`TEST_FILTER_CONFIG`] block embedded in the rom. Search the rom for the magic and patch
+//!    the bytes that follow after building.
+//!
+//! Layout (all integers big endian):
+//! - 0x00: Magic `N64STFLT`
+//! - 0x08: Index of the first test to run (index into [`testlist::tests()`](super::testlist::tests))
+//! - 0x0C: Bitmask of [`Level`]s to run (bit n = level n). If 0, the compile time configuration is used
+//! - 0x10: Include patterns, comma separated, zero terminated. If empty, all tests are included
+//! - 0x88: Exclude patterns, comma separated, zero terminated
+//!
+//! Patterns are matched against [`Test::name()`](super::Test::name) and may contain `*` (any
+//! sequence of characters) and `?` (any single character).
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ptr::read_volatile;
+
+use crate::tests::Level;
+
+const MAGIC: [u8; 8] = *b"N64STFLT";
+const CONFIG_SIZE: usize = 256;
+const START_INDEX_OFFSET: usize = 0x08;
+const LEVEL_MASK_OFFSET: usize = 0x0C;
+const INCLUDE_OFFSET: usize = 0x10;
+const EXCLUDE_OFFSET: usize = 0x88;
+const PATTERN_SIZE: usize = 0x78;
+
+const fn empty_config() -> [u8; CONFIG_SIZE] {
+    let mut result = [0u8; CONFIG_SIZE];
+    let mut i = 0;
+    while i < MAGIC.len() {
+        result[i] = MAGIC[i];
+        i += 1;
+    }
+    result
+}
+
+/// Filter block that is meant to be patched in the rom file after building. It is copied to RAM
+/// by the bootcode like all other data.
+#[used]
+#[no_mangle]
+static TEST_FILTER_CONFIG: [u8; CONFIG_SIZE] = empty_config();
+
+pub struct Filter {
+    start_index: usize,
+    level_mask: u32,
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl Filter {
+    /// Returns the filter provided by the emulator or patched into the rom. If neither exists,
+    /// a filter that includes every test is returned.
+    pub fn load() -> Self {
+        if let Some(config) = crate::isviewer::read_test_filter::<CONFIG_SIZE>() {
+            if config[0..8] == MAGIC {
+                return Self::parse(&config);
+            }
+        }
+        // Read volatile, as the compiler would otherwise see the unpatched initial value
+        let config = unsafe { read_volatile(&TEST_FILTER_CONFIG) };
+        Self::parse(&config)
+    }
+
+    fn parse(config: &[u8; CONFIG_SIZE]) -> Self {
+        fn read_u32(config: &[u8; CONFIG_SIZE], offset: usize) -> u32 {
+            u32::from_be_bytes([config[offset], config[offset + 1], config[offset + 2], config[offset + 3]])
+        }
+        fn read_patterns(config: &[u8; CONFIG_SIZE], offset: usize) -> Vec<String> {
+            let bytes = &config[offset..offset + PATTERN_SIZE];
+            let length = bytes.iter().position(|b| *b == 0).unwrap_or(PATTERN_SIZE);
+            String::from_utf8_lossy(&bytes[..length])
+                .split(',')
+                .map(|s| s.trim())
+                .filter(|s| !s.is_empty())
+                .map(String::from)
+                .collect()
+        }
+
+        Self {
+            start_index: read_u32(config, START_INDEX_OFFSET) as usize,
+            level_mask: read_u32(config, LEVEL_MASK_OFFSET),
+            include: read_patterns(config, INCLUDE_OFFSET),
+            exclude: read_patterns(config, EXCLUDE_OFFSET),
+        }
+    }
+
+    /// Returns true if any kind of filtering is active
+    pub fn is_active(&self) -> bool {
+        self.start_index != 0 || self.level_mask != 0 || !self.include.is_empty() || !self.exclude.is_empty()
+    }
+
+    /// If the filter specifies a set of levels, returns whether the given level is part of it.
+    /// Returns None if the compile time configuration should decide.
+    pub fn level_enabled(&self, level: Level) -> Option<bool> {
+        if self.level_mask == 0 {
+            None
+        } else {
+            Some((self.level_mask & (1 << (level as u32))) != 0)
+        }
+    }
+
+    /// Returns whether the test at the given index in the testlist passes the name and index filters.
+    pub fn includes(&self, index: usize, name: &str) -> bool {
+        if index < self.start_index {
+            return false;
+        }
+        if !self.include.is_empty() && !self.include.iter().any(|p| glob_match(p.as_bytes(), name.as_bytes())) {
+            return false;
+        }
+        !self.exclude.iter().any(|p| glob_match(p.as_bytes(), name.as_bytes()))
+    }
+
+    /// Human-readable description of the active filter
+    pub fn describe(&self) -> String {
+        alloc::format!("Test filter: start={} levels={:#x} include={:?} exclude={:?}\n", self.start_index, self.level_mask, self.include, self.exclude)
+    }
+}
+
+/// Matches text against a pattern that can contain `*` and `?`.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let mut p = 0;
+    let mut t = 0;
+    // Position after the last seen '*' and the text position it was matched against
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == b'*' {
+            p += 1;
+            backtrack = Some((p, t));
+        } else if let Some((star_p, star_t)) = backtrack {
+            // Let the last '*' swallow one more character
+            p = star_p;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    while p < pattern.len() && pattern[p] == b'*' {
+        p += 1;
+    }
+    p == pattern.len()
+}
@@ -0,0 +1,370 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::arch::asm;
+
+use crate::cop0;
+use crate::cop0::{CauseException, Status};
+use crate::exception_handler::{clear_exception_return_override, drain_seen_exception, expect_exception, set_exception_status_override};
+use crate::graphics::vi::{RegisterOffset, Video};
+use crate::memory_map::MemoryMap;
+use crate::mi;
+use crate::mi::{Interrupt, InterruptMaskWrite};
+use crate::pi::{Pi, PiStatusWrite};
+use crate::rdp::rdp::RDP;
+use crate::rdp::rdp_assembler::RDPAssembler;
+use crate::rsp::rsp::RSP;
+use crate::tests::{Level, NamedValue, Test, wait_for};
+use crate::tests::soft_asserts::{soft_assert_eq, soft_assert_eq2};
+use crate::uncached_memory::UncachedHeapMemory;
+
+// Notes:
+// - All RCP interrupts are combined by the MI: MI_INTR & MI_INTR_MASK is wired to the CPU's Int0 line,
+//   which shows up as Cause.IP2 (bit 10)
+// - Cause.IP2 follows the MI line directly: it is set even if Status.IE or Status.IM2 are clear
+// - The interrupt is only taken if IE and IM2 are set and EXL and ERL are both clear
+// - ExceptPC points to the instruction that was interrupted (not executed yet). If that instruction is in a
+//   delay slot, ExceptPC points to the branch and Cause.BD is set
+
+#[derive(Clone, Copy, Debug)]
+enum Source {
+    SP,
+    DP,
+    PI,
+    VI,
+}
+
+const SOURCES: [Source; 4] = [Source::SP, Source::DP, Source::PI, Source::VI];
+
+/// Some data to DMA from cart
+static DMA_SOURCE: [u64; 16] = [0x0123_4567_89AB_CDEF; 16];
+
+/// A PI DMA of [DMA_SOURCE] takes a couple of microseconds
+const DMA_TIMEOUT_MILLISECONDS: u32 = 10;
+
+fn mask_for(source: Source) -> Interrupt {
+    match source {
+        Source::SP => Interrupt::new().with_sp(true),
+        Source::DP => Interrupt::new().with_dp(true),
+        Source::PI => Interrupt::new().with_pi(true),
+        Source::VI => Interrupt::new().with_vi(true),
+    }
+}
+
+fn is_pending(source: Source) -> bool {
+    match source {
+        Source::SP => mi::is_sp_interrupt(),
+        Source::DP => mi::is_dp_interrupt(),
+        Source::PI => mi::is_pi_interrupt(),
+        Source::VI => mi::is_vi_interrupt(),
+    }
+}
+
+/// Starts a PI DMA from cart into the given buffer without waiting for it to finish
+fn start_pi_dma(target: &mut UncachedHeapMemory<u64>) {
+    Pi::set_status(PiStatusWrite::new().with_reset(true).with_clear_interrupt(true));
+    Pi::set_cart_address(MemoryMap::physical_cart_address(&DMA_SOURCE[0] as *const u64) as u32);
+    Pi::set_dram_address(target.start_phyiscal() as u32);
+    Pi::set_write_length((DMA_SOURCE.len() * 8) as u32 - 1);
+}
+
+/// Makes the given source raise its interrupt and waits until it shows up in MI_INTR
+fn raise(source: Source) -> Result<(), String> {
+    match source {
+        Source::SP => RSP::set_interrupt(),
+        Source::DP => {
+            let mut assembler = RDPAssembler::new();
+            assembler.sync_full();
+            RDP::run_and_wait(&mut assembler);
+        }
+        Source::PI => {
+            let mut target = UncachedHeapMemory::<u64>::new(DMA_SOURCE.len());
+            start_pi_dma(&mut target);
+            wait_for("PI DMA", DMA_TIMEOUT_MILLISECONDS, || !Pi::status().dma_busy())?;
+        }
+        // VI_V_INTR is setup during boot, so there is an interrupt once per frame
+        Source::VI => {}
+    }
+    wait_for(&format!("MI_INTR.{:?}", source), 50, || is_pending(source))
+}
+
+fn acknowledge(source: Source) {
+    match source {
+        Source::SP => RSP::clear_interrupt(),
+        Source::DP => mi::clear_dp_interrupt(),
+        Source::PI => Pi::set_status(PiStatusWrite::new().with_clear_interrupt(true)),
+        Source::VI => Video::set_register(RegisterOffset::Current, 0),
+    }
+}
+
+/// Runs f with only the given source enabled in MI_INTR_MASK. Afterwards, the interrupt is acknowledged and
+/// MI_INTR_MASK and Status are restored, even if f fails
+fn with_source<F: FnOnce() -> Result<(), String>>(source: Source, f: F) -> Result<(), String> {
+    let previous_mask = mi::interrupt_mask();
+    acknowledge(source);
+    mi::restore_interrupt_mask(mask_for(source));
+
+    let result = f();
+
+    acknowledge(source);
+    mi::restore_interrupt_mask(previous_mask);
+    clear_exception_return_override();
+    unsafe { cop0::set_status(Status::DEFAULT); }
+    result
+}
+
+/// Enables the given Status for a couple of instructions and disables it again. Returns the
+/// range of addresses at which an interrupt is expected to be taken.
+fn interrupt_window(status_on: Status, status_off: Status) -> (u32, u32) {
+    let begin: u32;
+    let end: u32;
+    unsafe {
+        asm!("
+            .set noreorder
+            LA {begin}, 1f
+            LA {end}, 2f
+            MTC0 {on}, $12
+            1:
+            NOP
+            NOP
+            NOP
+            NOP
+            NOP
+            NOP
+            NOP
+            NOP
+            2:
+            MTC0 {off}, $12
+            NOP
+            NOP
+        ", begin = out(reg) begin, end = out(reg) end, on = in(reg) status_on.raw_value(), off = in(reg) status_off.raw_value())
+    }
+    (begin, end)
+}
+
+const fn status_interrupts_enabled() -> Status {
+    Status::DEFAULT.with_interrupt_mask_int0(true).with_ie(true)
+}
+
+/// (description, Status while the interrupt is pending, MI_INTR_MASK cleared)
+const MASKED_CASES: [(&str, Status, bool); 5] = [
+    ("Status.IE clear", status_interrupts_enabled().with_ie(false), false),
+    ("Status.IM2 clear", status_interrupts_enabled().with_interrupt_mask_int0(false), false),
+    ("Status.EXL set", status_interrupts_enabled().with_exl(true), false),
+    ("Status.ERL set", status_interrupts_enabled().with_erl(true), false),
+    ("MI_INTR_MASK clear", status_interrupts_enabled(), true),
+];
+
+pub struct MaskRegister {}
+
+impl Test for MaskRegister {
+    fn name(&self) -> &str { "MI: MI_INTR_MASK set/clear" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let previous_mask = mi::interrupt_mask();
+
+        let result = (|| {
+            mi::clear_interrupt_mask();
+            soft_assert_eq(mi::interrupt_mask(), Interrupt::new(), "MI_INTR_MASK after clearing everything")?;
+
+            // Set one bit at a time. Bits that were set before stay set
+            let steps = [
+                (InterruptMaskWrite::new().with_setSP(true), Interrupt::new().with_sp(true)),
+                (InterruptMaskWrite::new().with_setSI(true), Interrupt::new().with_sp(true).with_si(true)),
+                (InterruptMaskWrite::new().with_setAI(true), Interrupt::new().with_sp(true).with_si(true).with_ai(true)),
+                (InterruptMaskWrite::new().with_setVI(true), Interrupt::new().with_sp(true).with_si(true).with_ai(true).with_vi(true)),
+                (InterruptMaskWrite::new().with_setPI(true), Interrupt::new().with_sp(true).with_si(true).with_ai(true).with_vi(true).with_pi(true)),
+                (InterruptMaskWrite::new().with_setDP(true), Interrupt::new().with_sp(true).with_si(true).with_ai(true).with_vi(true).with_pi(true).with_dp(true)),
+                (InterruptMaskWrite::new().with_clearSI(true).with_clearVI(true), Interrupt::new().with_sp(true).with_ai(true).with_pi(true).with_dp(true)),
+                (InterruptMaskWrite::new().with_clearSP(true).with_clearDP(true), Interrupt::new().with_ai(true).with_pi(true)),
+            ];
+            for (write, expected) in steps {
+                mi::set_interrupt_mask(write);
+                soft_assert_eq2(mi::interrupt_mask(), expected, || format!("MI_INTR_MASK after writing {:#x}", write.raw_value()))?;
+            }
+            Ok(())
+        })();
+
+        mi::restore_interrupt_mask(previous_mask);
+        result
+    }
+}
+
+pub struct PendingWithoutDelivery {}
+
+impl Test for PendingWithoutDelivery {
+    fn name(&self) -> &str { "MI: MI_INTR and Cause.IP2 (interrupts disabled)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        SOURCES.iter().map(|s| NamedValue::boxed(format!("{:?}", s), *s)).collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let source = *NamedValue::get::<Source>(value);
+        let previous_mask = mi::interrupt_mask();
+        acknowledge(source);
+        mi::clear_interrupt_mask();
+
+        let result = (|| {
+            soft_assert_eq(is_pending(source), false, "MI_INTR after acknowledging")?;
+            raise(source)?;
+            soft_assert_eq(cop0::cause().interrupt_int0(), false, "Cause.IP2 while the source is masked in MI_INTR_MASK")?;
+
+            mi::restore_interrupt_mask(mask_for(source));
+            soft_assert_eq(cop0::cause().interrupt_int0(), true, "Cause.IP2 after unmasking the source in MI_INTR_MASK")?;
+
+            acknowledge(source);
+            soft_assert_eq(is_pending(source), false, "MI_INTR after acknowledging")?;
+            soft_assert_eq(cop0::cause().interrupt_int0(), false, "Cause.IP2 after acknowledging")?;
+            Ok(())
+        })();
+
+        acknowledge(source);
+        mi::restore_interrupt_mask(previous_mask);
+        result
+    }
+}
+
+pub struct Delivery {}
+
+impl Test for Delivery {
+    fn name(&self) -> &str { "MI: Interrupt delivery to CPU" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        SOURCES.iter().map(|s| NamedValue::boxed(format!("{:?}", s), *s)).collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let source = *NamedValue::get::<Source>(value);
+        with_source(source, || {
+            raise(source)?;
+
+            let mut window = (0, 0);
+            set_exception_status_override(Status::DEFAULT.raw_value());
+            let exception_context = expect_exception(CauseException::Int, 0, || {
+                window = interrupt_window(status_interrupts_enabled(), Status::DEFAULT);
+                Ok(())
+            })?;
+
+            soft_assert_eq(exception_context.k0_exception_vector, 0xFFFFFFFF_80000180, "Exception Vector")?;
+            soft_assert_eq(exception_context.cause.exception(), Ok(CauseException::Int), "Cause.exception")?;
+            soft_assert_eq(exception_context.cause.interrupt_int0(), true, "Cause.IP2")?;
+            soft_assert_eq(exception_context.cause.interrupt_compare(), false, "Cause.IP7")?;
+            soft_assert_eq(exception_context.cause.branch_delay(), false, "Cause.BD")?;
+            soft_assert_eq(exception_context.status, status_interrupts_enabled().with_exl(true).raw_value(), "Status")?;
+            let (begin, end) = window;
+            let exceptpc = exception_context.exceptpc as u32;
+            soft_assert_eq2(exceptpc >= begin && exceptpc <= end, true, || format!("ExceptPC ({:#x}) is expected to be within the range in which interrupts were enabled ({:#x}..={:#x})", exceptpc, begin, end))?;
+
+            // Returning from the exception did not acknowledge the interrupt
+            soft_assert_eq(is_pending(source), true, "MI_INTR after the exception")?;
+            Ok(())
+        })
+    }
+}
+
+pub struct NotDeliveredWhenMasked {}
+
+impl Test for NotDeliveredWhenMasked {
+    fn name(&self) -> &str { "MI: No interrupt delivery when masked" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        MASKED_CASES.iter().map(|case| NamedValue::boxed(case.0, *case)).collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (case, status, clear_mi_mask) = *NamedValue::get::<(&'static str, Status, bool)>(value);
+        with_source(Source::SP, || {
+            raise(Source::SP)?;
+            if clear_mi_mask {
+                mi::clear_interrupt_mask();
+            }
+
+            // Prevent an exception storm in case the interrupt is (wrongly) taken
+            set_exception_status_override(Status::DEFAULT.raw_value());
+            interrupt_window(status, Status::DEFAULT);
+            clear_exception_return_override();
+            if let Some((exception_context, _)) = drain_seen_exception() {
+                return Err(format!("Interrupt was taken even though {}. ExceptPC={:#x} Cause={:#x}", case, exception_context.exceptpc, exception_context.cause.raw_value()));
+            }
+
+            // The interrupt is still pending and will be taken once everything is enabled
+            soft_assert_eq(cop0::cause().interrupt_int0(), !clear_mi_mask, "Cause.IP2")?;
+            Ok(())
+        })
+    }
+}
+
+pub struct DeliveryInDelaySlot {}
+
+impl Test for DeliveryInDelaySlot {
+    fn name(&self) -> &str { "MI: Interrupt delivery in delay slot (Cause.BD)" }
+
+    fn level(&self) -> Level { Level::RarelyUsed }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        // The PI interrupt arrives asynchronously while the CPU spins in a loop that consists of a branch and its delay
+        // slot. Repeat until the interrupt hit both the branch and the delay slot
+        with_source(Source::PI, || {
+            let mut target = UncachedHeapMemory::<u64>::new(DMA_SOURCE.len());
+            let mut seen_branch = false;
+            let mut seen_delay_slot = false;
+            for _ in 0..64 {
+                acknowledge(Source::PI);
+                let branch: u32;
+                set_exception_status_override(Status::DEFAULT.raw_value());
+                let exception_context = expect_exception(CauseException::Int, 0, || {
+                    start_pi_dma(&mut target);
+                    unsafe {
+                        asm!("
+                            .set noreorder
+                            LI {counter}, 20000
+                            LA {branch}, 1f
+                            MTC0 {on}, $12
+                            NOP
+                            1:
+                            BNE {counter}, $0, 1b
+                            ADDIU {counter}, {counter}, -1
+                            MTC0 {off}, $12
+                            NOP
+                            NOP
+                        ", counter = out(reg) _, branch = out(reg) branch, on = in(reg) status_interrupts_enabled().raw_value(), off = in(reg) Status::DEFAULT.raw_value())
+                    }
+                    Ok(())
+                })?;
+                wait_for("PI DMA", DMA_TIMEOUT_MILLISECONDS, || !Pi::status().dma_busy())?;
+
+                let exceptpc = exception_context.exceptpc as u32;
+                if exceptpc == branch || exceptpc == branch + 4 {
+                    soft_assert_eq(exception_context.cause.interrupt_int0(), true, "Cause.IP2")?;
+                    if exception_context.cause.branch_delay() {
+                        // Interrupted in the delay slot: ExceptPC points to the branch
+                        soft_assert_eq2(exceptpc, branch, || format!("ExceptPC when interrupted in a delay slot (Cause={:#x})", exception_context.cause.raw_value()))?;
+                        seen_delay_slot = true;
+                    } else {
+                        soft_assert_eq2(exceptpc, branch, || format!("ExceptPC when interrupted on the branch (Cause={:#x}). The delay slot should have set Cause.BD instead", exception_context.cause.raw_value()))?;
+                        seen_branch = true;
+                    }
+                }
+                if seen_branch && seen_delay_slot {
+                    return Ok(());
+                }
+            }
+            Err(format!("Expected to see interrupts on both the branch and in its delay slot. Branch: {}, delay slot: {}", seen_branch, seen_delay_slot))
+        })
+    }
+}
@@ -1 +1,2 @@
+pub mod interrupts;
 pub mod repeat;
@@ -8,28 +8,34 @@ use arbitrary_int::{u2, u27, u5};
 
 use crate::cop0::{set_status, Status};
 use crate::exception_handler::drain_seen_exception;
-use crate::{FramebufferConsole, print, println};
+use crate::{FramebufferConsole, print};
 use crate::cop1::{FCSR, FCSRFlags, FCSRRoundingMode, set_fcsr};
 use crate::text_out::text_out;
 use crate::math::soft_float::{SoftF32, SoftF64};
 use crate::tests::cop1::compares::FPUSpecialNumber;
 use crate::tests::traps::Immediate;
 
+mod ai;
 mod arithmetic;
 mod address_error_exception;
+mod bus_error;
 mod cart_memory;
 mod cop_unusable;
 mod cop0;
 mod cop1;
 mod endian_re;
 mod exception_instructions;
+mod filter;
 mod jumps;
 mod mi;
 mod overflow_exception;
+mod partial_load_store;
 mod pif_memory;
 mod privilege;
 mod rdp;
+mod results;
 mod rsp;
+mod si;
 mod startup;
 mod soft_asserts;
 mod sp_memory;
@@ -37,6 +43,7 @@ mod testlist;
 mod tlb;
 mod tlb64;
 mod traps;
+mod vi;
 
 mod configuration {
     pub const BASE: bool = cfg!(feature = "base");
@@ -47,7 +54,7 @@ mod configuration {
 }
 
 /// The importance level of a [test](Test).
-#[derive(Eq, PartialEq)]
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
 #[repr(u8)]
 pub enum Level {
     /// Very basic functionality. If this is broken, expect things to go bad.
@@ -110,76 +117,137 @@ pub trait Test {
     fn run(&self, value: &Box<dyn Any>) -> Result<(), String>;
 }
 
+/// A test value that is reported by name. Use this for values that value_desc() can't describe
+/// by itself, e.g. tuples of test-specific types
+pub struct NamedValue {
+    name: String,
+    value: Box<dyn Any>,
+}
+
+impl NamedValue {
+    /// Boxes the value, to be returned from [`Test::values()`]
+    pub fn boxed<T: Any>(name: impl Into<String>, value: T) -> Box<dyn Any> {
+        Box::new(Self { name: name.into(), value: Box::new(value) })
+    }
+
+    /// Returns the value that was passed to [`Self::boxed()`]
+    pub fn get<T: Any>(value: &Box<dyn Any>) -> &T {
+        value.downcast_ref::<Self>().unwrap().value.downcast_ref::<T>().unwrap()
+    }
+}
+
 fn cycles_to_seconds(value: u32) -> f32
 {
     value as f32 / (93_750_000f32 / 2f32)
 }
 
+/// COP0 Count increments at half the CPU clock
+const COUNT_PER_MILLISECOND: u32 = 93_750_000 / 2 / 1000;
+
+/// Busy-waits for the given number of milliseconds
+fn delay_ms(milliseconds: u32) {
+    let start = crate::cop0::count();
+    while crate::cop0::count().wrapping_sub(start) < milliseconds * COUNT_PER_MILLISECOND {}
+}
+
+/// Polls until the condition is met. Fails if that takes longer than the timeout
+fn wait_for<F: FnMut() -> bool>(what: &str, timeout_ms: u32, mut condition: F) -> Result<(), String> {
+    let start = crate::cop0::count();
+    while !condition() {
+        if crate::cop0::count().wrapping_sub(start) > timeout_ms * COUNT_PER_MILLISECOND {
+            return Err(format!("Time out waiting for {} (after {}ms)", what, timeout_ms));
+        }
+    }
+    Ok(())
+}
+
 pub fn run() {
     const LEVEL_COUNT: usize = Level::_COUNT as usize;
     let mut succeeded = [0u32; LEVEL_COUNT];
     let mut failed = [0u32; LEVEL_COUNT];
 
-    fn test_value(test: &Box<dyn Test>, value: &Box::<dyn Any>, failed: &mut u32, succeeded: &mut u32, time: &mut u32) {
+    /// Prints to screen and console. If a machine-readable result stream was requested, the
+    /// console is reserved for that, so prose only goes to the screen
+    fn print_human_readable(s: &str) {
+        if results::FORMAT.is_some() {
+            FramebufferConsole::instance().lock().append(s);
+        } else {
+            print!("{}", s);
+        }
+    }
+
+    fn test_value(test: &Box<dyn Test>, level: &Level, value: &Box::<dyn Any>, failed: &mut u32, succeeded: &mut u32, time: &mut u32) {
         fn value_desc(value: &Box<dyn Any>) -> String {
             match (*value).downcast_ref::<()>() {
                 Some(_) => return String::new(),
                 None => {},
             }
             match (*value).downcast_ref::<u32>() {
-                Some(v) => return format!(" with '{:x?}'", v),
+                Some(v) => return format!("{:x?}", v),
                 None => {},
             }
             match (*value).downcast_ref::<bool>() {
-                Some(v) => return format!(" with '{:?}'", v),
+                Some(v) => return format!("{:?}", v),
+                None => {},
+            }
+            match (*value).downcast_ref::<&'static str>() {
+                Some(v) => return String::from(*v),
+                None => {},
+            }
+            match (*value).downcast_ref::<NamedValue>() {
+                Some(v) => return v.name.clone(),
                 None => {},
             }
             match (*value).downcast_ref::<(bool, u32)>() {
-                Some(v) => return format!(" with '{:x?}'", v),
+                Some(v) => return format!("{:x?}", v),
                 None => {},
             }
             match (*value).downcast_ref::<(bool, u64)>() {
-                Some(v) => return format!(" with '{:x?}'", v),
+                Some(v) => return format!("{:x?}", v),
                 None => {},
             }
             match (*value).downcast_ref::<(bool, u32, u32)>() {
-                Some(v) => return format!(" with '{:x?}'", v),
+                Some(v) => return format!("{:x?}", v),
                 None => {},
             }
             match (*value).downcast_ref::<(u32, u32)>() {
-                Some(v) => return format!(" with '{:#x?}'", v),
+                Some(v) => return format!("{:#x?}", v),
                 None => {},
             }
             match (*value).downcast_ref::<(u32, u32, u32)>() {
-                Some(v) => return format!(" with '{:x?}'", v),
+                Some(v) => return format!("{:x?}", v),
                 None => {},
             }
             match (*value).downcast_ref::<(u32, u5, u32)>() {
-                Some(v) => return format!(" with '{:x?}'", v),
+                Some(v) => return format!("{:x?}", v),
+                None => {},
+            }
+            match (*value).downcast_ref::<(u64, u64, u64, u64)>() {
+                Some(v) => return format!("{:x?}", v),
                 None => {},
             }
             match (*value).downcast_ref::<(u64, u32, u64)>() {
-                Some(v) => return format!(" with '{:x?}'", v),
+                Some(v) => return format!("{:x?}", v),
                 None => {},
             }
             match (*value).downcast_ref::<(u64, u32, u8)>() {
-                Some(v) => return format!(" with '{:x?}'", v),
+                Some(v) => return format!("{:x?}", v),
                 None => {},
             }
             match (*value).downcast_ref::<(u64, u27, u2)>() {
-                Some(v) => return format!(" with '{:x?}'", v),
+                Some(v) => return format!("{:x?}", v),
                 None => {},
             }
             match (*value).downcast_ref::<(bool, i64, i64)>() {
-                Some(v) => return format!(" with '{:x?}'", v),
+                Some(v) => return format!("{:x?}", v),
                 None => {},
             }
             match (*value).downcast_ref::<(bool, u64, u64)>() {
-                Some(v) => return format!(" with '{:x?}'", v),
+                Some(v) => return format!("{:x?}", v),
                 None => {}
             }
             match (*value).downcast_ref::<(bool, u64, Immediate)>() {
-                Some(v) => return format!(" with '{:x?}'", v),
+                Some(v) => return format!("{:x?}", v),
                 None => {}
             }
             match (*value).downcast_ref::<(bool, FCSRRoundingMode, f32, Result<(FCSRFlags, f32), ()>)>() {
@@ -187,7 +255,7 @@ pub fn run() {
                     // Convert f32 to SoftF32 - it prints more nicely
                     let new_expected = expected.map(|(flags, f)| (flags, SoftF32::new(f)));
                     let temp = (*flush_denorm_to_zero, *rounding_mode, SoftF32::new(*value), new_expected);
-                    return format!(" with '{:x?}'", temp);
+                    return format!("{:x?}", temp);
                 }
                 None => {}
             }
@@ -196,7 +264,7 @@ pub fn run() {
                     // Convert f32 to SoftF32 - it prints more nicely
                     let new_expected = expected.map(|(flags, f)| (flags, SoftF64::new(f)));
                     let temp = (*flush_denorm_to_zero, *rounding_mode, SoftF32::new(*value), new_expected);
-                    return format!(" with '{:x?}'", temp);
+                    return format!("{:x?}", temp);
                 }
                 None => {}
             }
@@ -204,7 +272,7 @@ pub fn run() {
                 Some((flush_denorm_to_zero, rounding_mode, value, expected)) => {
                     // Convert f32 to SoftF32 - it prints more nicely
                     let temp = (*flush_denorm_to_zero, *rounding_mode, SoftF32::new(*value), expected);
-                    return format!(" with '{:x?}'", temp);
+                    return format!("{:x?}", temp);
                 }
                 None => {}
             }
@@ -212,7 +280,7 @@ pub fn run() {
                 Some((flush_denorm_to_zero, rounding_mode, value, expected)) => {
                     // Convert f32 to SoftF32 - it prints more nicely
                     let temp = (*flush_denorm_to_zero, *rounding_mode, SoftF32::new(*value), expected);
-                    return format!(" with '{:x?}'", temp);
+                    return format!("{:x?}", temp);
                 }
                 None => {}
             }
@@ -220,7 +288,7 @@ pub fn run() {
                 Some((value, expected)) => {
                     // Convert f32 to SoftF32 - it prints more nicely
                     let temp = (SoftF32::new(*value), expected);
-                    return format!(" with '{:x?}'", temp);
+                    return format!("{:x?}", temp);
                 }
                 None => {}
             }
@@ -229,7 +297,7 @@ pub fn run() {
                     // Convert f32 to SoftF32 - it prints more nicely
                     let new_expected = expected.map(|(flags, f)| (flags, SoftF32::new(f)));
                     let temp = (*flush_denorm_to_zero, *rounding_mode, SoftF32::new(*value1), SoftF32::new(*value2), new_expected);
-                    return format!(" with '{:x?}'", temp);
+                    return format!("{:x?}", temp);
                 }
                 None => {}
             }
@@ -238,7 +306,7 @@ pub fn run() {
                     // Convert f32 to SoftF32 - it prints more nicely
                     let new_expected = expected.map(|(flags, f)| (flags, SoftF32::new(f)));
                     let temp = (*flush_denorm_to_zero, *rounding_mode, SoftF64::new(*value), new_expected);
-                    return format!(" with '{:x?}'", temp);
+                    return format!("{:x?}", temp);
                 }
                 None => {}
             }
@@ -247,7 +315,7 @@ pub fn run() {
                     // Convert f32 to SoftF32 - it prints more nicely
                     let new_expected = expected.map(|(flags, f)| (flags, SoftF64::new(f)));
                     let temp = (*flush_denorm_to_zero, *rounding_mode, SoftF64::new(*value), new_expected);
-                    return format!(" with '{:x?}'", temp);
+                    return format!("{:x?}", temp);
                 }
                 None => {}
             }
@@ -255,7 +323,7 @@ pub fn run() {
                 Some((flush_denorm_to_zero, rounding_mode, value, expected)) => {
                     // Convert f32 to SoftF32 - it prints more nicely
                     let temp = (*flush_denorm_to_zero, *rounding_mode, SoftF64::new(*value), expected);
-                    return format!(" with '{:x?}'", temp);
+                    return format!("{:x?}", temp);
                 }
                 None => {}
             }
@@ -263,7 +331,7 @@ pub fn run() {
                 Some((flush_denorm_to_zero, rounding_mode, value, expected)) => {
                     // Convert f32 to SoftF32 - it prints more nicely
                     let temp = (*flush_denorm_to_zero, *rounding_mode, SoftF64::new(*value), expected);
-                    return format!(" with '{:x?}'", temp);
+                    return format!("{:x?}", temp);
                 }
                 None => {}
             }
@@ -271,7 +339,7 @@ pub fn run() {
                 Some((value, expected)) => {
                     // Convert f32 to SoftF32 - it prints more nicely
                     let temp = (SoftF64::new(*value), expected);
-                    return format!(" with '{:x?}'", temp);
+                    return format!("{:x?}", temp);
                 }
                 None => {}
             }
@@ -280,7 +348,7 @@ pub fn run() {
                     // Convert f32 to SoftF32 - it prints more nicely
                     let new_expected = expected.map(|(flags, f)| (flags, SoftF32::new(f)));
                     let temp = (*flush_denorm_to_zero, *rounding_mode, value, new_expected);
-                    return format!(" with '{:x?}'", temp);
+                    return format!("{:x?}", temp);
                 }
                 None => {}
             }
@@ -289,16 +357,16 @@ pub fn run() {
                     // Convert f32 to SoftF32 - it prints more nicely
                     let new_expected = expected.map(|(flags, f)| (flags, SoftF64::new(f)));
                     let temp = (*flush_denorm_to_zero, *rounding_mode, value, new_expected);
-                    return format!(" with '{:x?}'", temp);
+                    return format!("{:x?}", temp);
                 }
                 None => {}
             }
             match (*value).downcast_ref::<(i32, Result<(FCSRFlags, i32), ()>)>() {
-                Some(v) => return format!(" with '{:x?}'", v),
+                Some(v) => return format!("{:x?}", v),
                 None => {}
             }
             match (*value).downcast_ref::<(i32, Result<(FCSRFlags, i64), ()>)>() {
-                Some(v) => return format!(" with '{:x?}'", v),
+                Some(v) => return format!("{:x?}", v),
                 None => {}
             }
             match (*value).downcast_ref::<(bool, FCSRRoundingMode, i64, Result<(FCSRFlags, f64), ()>)>() {
@@ -306,7 +374,7 @@ pub fn run() {
                     // Convert f32 to SoftF32 - it prints more nicely
                     let new_expected = expected.map(|(flags, f)| (flags, SoftF64::new(f)));
                     let temp = (*flush_denorm_to_zero, *rounding_mode, value, new_expected);
-                    return format!(" with '{:x?}'", temp);
+                    return format!("{:x?}", temp);
                 }
                 None => {}
             }
@@ -315,37 +383,37 @@ pub fn run() {
                     // Convert f32 to SoftF32 - it prints more nicely
                     let new_expected = expected.map(|(flags, f)| (flags, SoftF32::new(f)));
                     let temp = (*flush_denorm_to_zero, *rounding_mode, value, new_expected);
-                    return format!(" with '{:x?}'", temp);
+                    return format!("{:x?}", temp);
                 }
                 None => {}
             }
             match (*value).downcast_ref::<(f32, Result<(FCSRFlags, i32), ()>)>() {
                 Some((value, expected)) => {
                     let temp = (SoftF32::new(*value), expected);
-                    return format!(" with '{:x?}'", temp)
+                    return format!("{:x?}", temp)
                 },
                 None => {}
             }
             match (*value).downcast_ref::<(f64, Result<(FCSRFlags, i32), ()>)>() {
                 Some((value, expected)) => {
                     let temp = (SoftF64::new(*value), expected);
-                    return format!(" with '{:x?}'", temp)
+                    return format!("{:x?}", temp)
                 },
                 None => {}
             }
             match (*value).downcast_ref::<(i64, Result<(FCSRFlags, i32), ()>)>() {
-                Some(v) => return format!(" with '{:x?}'", v),
+                Some(v) => return format!("{:x?}", v),
                 None => {}
             }
             match (*value).downcast_ref::<(i64, Result<(FCSRFlags, i64), ()>)>() {
-                Some(v) => return format!(" with '{:x?}'", v),
+                Some(v) => return format!("{:x?}", v),
                 None => {}
             }
             match (*value).downcast_ref::<(bool, FCSRRoundingMode, f64, f64, Result<(FCSRFlags, f64), ()>)>() {
                 Some((flush_denorm_to_zero, rounding_mode, f1, f2, expected)) => {
                     // Convert f32 to SoftF32 - it prints more nicely
                     let temp = (*flush_denorm_to_zero, *rounding_mode, SoftF64::new(*f1), SoftF64::new(*f2), *expected);
-                    return format!(" with '{:x?}'", temp);
+                    return format!("{:x?}", temp);
                 },
                 None => {}
             }
@@ -353,7 +421,7 @@ pub fn run() {
                 Some((f1, f2, ordering, special)) => {
                     // Convert f32 to SoftF32 - it prints more nicely
                     let temp = (SoftF32::new(*f1), SoftF32::new(*f2), *ordering, *special);
-                    return format!(" with '{:x?}'", temp);
+                    return format!("{:x?}", temp);
                 },
                 None => {}
             }
@@ -361,11 +429,11 @@ pub fn run() {
                 Some((f1, f2, ordering, special)) => {
                     // Convert f64 to SoftF64 - it prints more nicely
                     let temp = (SoftF64::new(*f1), SoftF64::new(*f2), *ordering, *special);
-                    return format!(" with '{:x?}'", temp);
+                    return format!("{:x?}", temp);
                 },
                 None => {}
             }
-            return " with unknown arguments".to_string();
+            return "unknown arguments".to_string();
         }
 
         if test.name() != "StartupTest" {
@@ -378,28 +446,35 @@ pub fn run() {
         let counter_before = crate::cop0::count();
         let test_result = test.run(&value);
         let counter_after = crate::cop0::count();
-        *time += counter_after - counter_before;
+        let cycles = counter_after - counter_before;
+        *time += cycles;
 
         unsafe { set_status(Status::DEFAULT); }
         set_fcsr(FCSR::DEFAULT);
 
+        let desc = value_desc(value);
+        let with_desc = if desc.is_empty() { String::new() } else { format!(" with '{}'", desc) };
         match drain_seen_exception() {
             Some((exception, _)) => {
                 // If the test caused an exception, don't even bother looking at the result. Just count it as failed
-                match exception.cause.exception() {
-                    Ok(e) => println!("Test '{}'{} failed with exception: {:?}\n", test.name(), value_desc(value), e),
-                    Err(e) => println!("Test '{}'{} failed with unknown exception: {:?}\n", test.name(), value_desc(value), e),
-                }
+                let error = match exception.cause.exception() {
+                    Ok(e) => format!("{:?}", e),
+                    Err(e) => format!("unknown exception {:?}", e),
+                };
+                print_human_readable(&format!("Test '{}'{} failed with exception: {}\n\n", test.name(), with_desc, error));
+                results::test_value(test.name(), level, &desc, &results::Outcome::Exception(&error), cycles);
 
                 *failed += 1;
             }
             None => {
                 match test_result {
                     Ok(_) => {
+                        results::test_value(test.name(), level, &desc, &results::Outcome::Pass, cycles);
                         *succeeded += 1
                     }
                     Err(error) => {
-                        println!("Test '{}'{} failed: {}\n", test.name(), value_desc(value), error);
+                        print_human_readable(&format!("Test '{}'{} failed: {}\n\n", test.name(), with_desc, error));
+                        results::test_value(test.name(), level, &desc, &results::Outcome::Fail(&error), cycles);
                         *failed += 1;
                     }
                 }
@@ -411,15 +486,26 @@ pub fn run() {
     let mut test_times: Vec<(usize, u32)> = Vec::new();
     let dummy_test_value: Box<dyn Any> = Box::new(());
     let counter_before = crate::cop0::count();
+    let filter = filter::Filter::load();
+    if filter.is_active() {
+        print_human_readable(&filter.describe());
+    }
+    results::begin();
     for (index, test) in tests.iter().enumerate() {
-        text_out("Running ");
-        text_out(test.name());
-        text_out("...\n");
+        if !filter.includes(index, test.name()) {
+            continue;
+        }
+
+        if results::FORMAT.is_none() {
+            text_out("Running ");
+            text_out(test.name());
+            text_out("...\n");
+        }
 
         let values = test.values();
         let level = test.level();
 
-        let execute_test = match level {
+        let execute_test = filter.level_enabled(level).unwrap_or_else(|| match level {
             Level::BasicFunctionality | Level::RarelyUsed | Level::Weird | Level::RDPBasic | Level::RDPPrecise => configuration::BASE,
             Level::Timing => configuration::TIMING,
             Level::Cycle => configuration::CYCLE,
@@ -431,15 +517,15 @@ pub fn run() {
                 true
             },
             Level::_COUNT => panic!("Don't use _COUNT as Level"),
-        };
+        });
 
         if execute_test {
             let mut time = 0u32;
             if values.len() == 0 {
-                test_value(&test, &dummy_test_value, &mut failed[level as usize], &mut succeeded[level as usize], &mut time);
+                test_value(&test, &level, &dummy_test_value, &mut failed[level as usize], &mut succeeded[level as usize], &mut time);
             } else {
                 for value in values {
-                    test_value(&test, &value, &mut failed[level as usize], &mut succeeded[level as usize], &mut time);
+                    test_value(&test, &level, &value, &mut failed[level as usize], &mut succeeded[level as usize], &mut time);
                 }
             }
             test_times.push((index, time));
@@ -447,11 +533,12 @@ pub fn run() {
     }
     let counter_after = crate::cop0::count();
 
-    println!();
+    print_human_readable("\n");
     let succeeded_total: u32 = succeeded.iter().sum();
     let failed_total: u32 = failed.iter().sum();
+    results::summary(succeeded_total, failed_total, counter_after - counter_before);
     if (failed_total + succeeded_total) == 0 {
-        println!("Done, but no tests were executed");
+        print_human_readable("Done, but no tests were executed\n");
     } else {
         const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -487,23 +574,25 @@ Finished in {:0.2}s. {}{}{}{}{}",
             cycles_to_seconds(counter_after - counter_before),
             base_stat, timing_stat, cycle_stat, cp0_hazards_stat, poorly_understood_quirk_stat
         );
-        // Print to the console, at the end
-        text_out(&debug_msg);
+        // Print to the console, at the end (unless the console carries the machine-readable stream)
+        if results::FORMAT.is_none() {
+            text_out(&debug_msg);
+        }
 
         // For the on-screen console, prepend it. This way it's visible even if there are a lot of failed tests
         FramebufferConsole::instance().lock().prepend(&debug_msg);
 
         test_times.sort_by(|(_, a), (_, b)| { a.cmp(b).reverse() });
-        println!("");
-        print!("Slowest tests: ");
+        let mut slowest = String::from("\nSlowest tests: ");
         for i in 0..min(5, test_times.len()) {
             let (test_index, test_time) = test_times[i];
             let test_name = tests[test_index].name();
             if i > 0 {
-                print!(", ");
+                slowest += ", ";
             }
-            print!("{} ({:0.2}s)", test_name, cycles_to_seconds(test_time));
+            slowest += &format!("{} ({:0.2}s)", test_name, cycles_to_seconds(test_time));
         }
-        println!("");
+        slowest += "\n";
+        print_human_readable(&slowest);
     }
 }
\ No newline at end of file
@@ -0,0 +1,525 @@
+use alloc::boxed::Box;
+use alloc::{format, vec};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::arch::asm;
+
+use crate::cop0;
+use crate::cop0::{CauseException, Context, XContext};
+use crate::exception_handler::expect_exception;
+use crate::MemoryMap;
+use crate::tests::{Level, NamedValue, Test};
+use crate::tests::soft_asserts::{soft_assert_eq, soft_assert_eq2};
+use crate::uncached_memory::UncachedHeapMemory;
+
+// Tests for the unaligned partial load/store instructions (LWL/LWR/LDL/LDR/SWL/SWR/SDL/SDR) in big-endian kernel mode.
+// Notes:
+// - None of them ever cause an address error because of misalignment. They still cause AdEL/AdES for invalid
+//   addresses though, in which case BadVAddr is the exact (unaligned) address
+// - LWL and LWR always sign extend the 32 bit result into the 64 bit register, even if only some bytes are loaded
+// - ROM and SPMEM only allow 32 bit reads. LDL/LDR turn into 64 bit reads, which crash the console (no test for that)
+// - SWL/SWR into SPMEM behave like SB/SH: The whole 32 bit word is overwritten with the shifted register value,
+//   filling everything that isn't written with zeroes
+
+const PATTERN: [u64; 2] = [0x80119223_04B546F7, 0x38C95AEB_7C0D9E2F];
+
+/// Initial register values for loads. The upper half of each doesn't match the sign extension of the lower half,
+/// so that a missing (or wrong) sign extension is visible
+const INITIAL_REGISTER: [u64; 2] = [0x01234567_89ABCDEF, 0xFEDCBA98_76543210];
+
+/// Register value for stores
+const STORE_VALUE: u64 = 0x8899AABB_CCDDEEFF;
+
+#[derive(Copy, Clone, Debug)]
+enum Target {
+    CachedRAM,
+    UncachedRAM,
+    SPMEM,
+    ROM,
+}
+
+impl Target {
+    fn name(&self) -> &'static str {
+        match self {
+            Target::CachedRAM => "cached RAM",
+            Target::UncachedRAM => "uncached RAM",
+            Target::SPMEM => "SPMEM",
+            Target::ROM => "ROM",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Op {
+    LWL,
+    LWR,
+    LDL,
+    LDR,
+    SWL,
+    SWR,
+    SDL,
+    SDR,
+}
+
+impl Op {
+    fn opcode(&self) -> u32 {
+        match self {
+            Op::LWL => 0x22,
+            Op::LWR => 0x26,
+            Op::LDL => 0x1A,
+            Op::LDR => 0x1B,
+            Op::SWL => 0x2A,
+            Op::SWR => 0x2E,
+            Op::SDL => 0x2C,
+            Op::SDR => 0x2D,
+        }
+    }
+
+    fn is_load(&self) -> bool {
+        match self {
+            Op::LWL | Op::LWR | Op::LDL | Op::LDR => true,
+            Op::SWL | Op::SWR | Op::SDL | Op::SDR => false,
+        }
+    }
+
+    /// Reference implementation of a load. `memory` is the aligned word (or doubleword) that is being read from
+    fn reference_load(&self, memory: u64, offset: usize, register: u64) -> u64 {
+        match self {
+            Op::LWL => {
+                let shift = 8 * (offset & 3);
+                let mask = u32::MAX << shift;
+                ((register as u32 & !mask) | ((memory as u32) << shift)) as i32 as u64
+            }
+            Op::LWR => {
+                let shift = 8 * (3 - (offset & 3));
+                let mask = u32::MAX >> shift;
+                ((register as u32 & !mask) | ((memory as u32) >> shift)) as i32 as u64
+            }
+            Op::LDL => {
+                let shift = 8 * (offset & 7);
+                let mask = u64::MAX << shift;
+                (register & !mask) | (memory << shift)
+            }
+            Op::LDR => {
+                let shift = 8 * (7 - (offset & 7));
+                let mask = u64::MAX >> shift;
+                (register & !mask) | (memory >> shift)
+            }
+            _ => panic!("{:?} is not a load", self),
+        }
+    }
+
+    /// Reference implementation of a store. `memory` is the aligned word (or doubleword) that is being written to
+    fn reference_store(&self, memory: u64, offset: usize, register: u64) -> u64 {
+        match self {
+            Op::SWL => {
+                let shift = 8 * (offset & 3);
+                let mask = u32::MAX >> shift;
+                ((memory as u32 & !mask) | ((register as u32) >> shift)) as u64
+            }
+            Op::SWR => {
+                let shift = 8 * (3 - (offset & 3));
+                let mask = u32::MAX << shift;
+                ((memory as u32 & !mask) | ((register as u32) << shift)) as u64
+            }
+            Op::SDL => {
+                let shift = 8 * (offset & 7);
+                let mask = u64::MAX >> shift;
+                (memory & !mask) | (register >> shift)
+            }
+            Op::SDR => {
+                let shift = 8 * (7 - (offset & 7));
+                let mask = u64::MAX << shift;
+                (memory & !mask) | (register << shift)
+            }
+            _ => panic!("{:?} is not a store", self),
+        }
+    }
+
+    /// Size of the aligned memory unit that is accessed
+    fn size(&self) -> usize {
+        match self {
+            Op::LWL | Op::LWR | Op::SWL | Op::SWR => 4,
+            Op::LDL | Op::LDR | Op::SDL | Op::SDR => 8,
+        }
+    }
+}
+
+/// Executes a partial load. The register starts out as `register`; the resulting register value is returned
+fn load(op: Op, address: usize, register: u64) -> u64 {
+    let mut result = register;
+    unsafe {
+        match op {
+            Op::LWL => asm!("
+                .set noat
+                LD $3, 0($4)
+                LWL $3, 0($2)
+                SD $3, 0($4)
+            ", in("$2") address, out("$3") _, in("$4") &mut result),
+            Op::LWR => asm!("
+                .set noat
+                LD $3, 0($4)
+                LWR $3, 0($2)
+                SD $3, 0($4)
+            ", in("$2") address, out("$3") _, in("$4") &mut result),
+            Op::LDL => asm!("
+                .set noat
+                LD $3, 0($4)
+                LDL $3, 0($2)
+                SD $3, 0($4)
+            ", in("$2") address, out("$3") _, in("$4") &mut result),
+            Op::LDR => asm!("
+                .set noat
+                LD $3, 0($4)
+                LDR $3, 0($2)
+                SD $3, 0($4)
+            ", in("$2") address, out("$3") _, in("$4") &mut result),
+            _ => panic!("{:?} is not a load", op),
+        }
+    }
+    result
+}
+
+/// Executes a partial store of the given register value
+fn store(op: Op, address: usize, register: u64) {
+    unsafe {
+        match op {
+            Op::SWL => asm!("
+                .set noat
+                LD $3, 0($4)
+                SWL $3, 0($2)
+            ", in("$2") address, out("$3") _, in("$4") &register),
+            Op::SWR => asm!("
+                .set noat
+                LD $3, 0($4)
+                SWR $3, 0($2)
+            ", in("$2") address, out("$3") _, in("$4") &register),
+            Op::SDL => asm!("
+                .set noat
+                LD $3, 0($4)
+                SDL $3, 0($2)
+            ", in("$2") address, out("$3") _, in("$4") &register),
+            Op::SDR => asm!("
+                .set noat
+                LD $3, 0($4)
+                SDR $3, 0($2)
+            ", in("$2") address, out("$3") _, in("$4") &register),
+            _ => panic!("{:?} is not a store", op),
+        }
+    }
+}
+
+/// Reads the aligned word or doubleword at the given byte offset from PATTERN
+fn pattern_at(offset: usize, size: usize) -> u64 {
+    let doubleword = PATTERN[offset / 8];
+    if size == 8 {
+        doubleword
+    } else {
+        (doubleword >> (32 - 32 * ((offset / 4) & 1))) as u32 as u64
+    }
+}
+
+/// Reads the aligned word or doubleword at the given byte offset. Only 32 bit reads are used, so that this
+/// works for every target
+fn read_at(base: *const u32, offset: usize, size: usize) -> u64 {
+    let index = (offset & !(size - 1)) / 4;
+    let high = unsafe { base.add(index).read_volatile() } as u64;
+    if size == 8 {
+        (high << 32) | (unsafe { base.add(index + 1).read_volatile() } as u64)
+    } else {
+        high
+    }
+}
+
+/// Resets the memory to PATTERN (using 32 bit writes)
+fn preset(base: *mut u32) {
+    for i in 0..PATTERN.len() {
+        unsafe {
+            base.add(i * 2).write_volatile((PATTERN[i] >> 32) as u32);
+            base.add(i * 2 + 1).write_volatile(PATTERN[i] as u32);
+        }
+    }
+}
+
+/// Provides memory of the given target that starts out as PATTERN
+fn with_target<F: FnOnce(*mut u32) -> Result<(), String>>(target: Target, f: F) -> Result<(), String> {
+    match target {
+        Target::CachedRAM => {
+            let mut buffer = [0u64; 2];
+            let base = buffer.as_mut_ptr() as *mut u32;
+            preset(base);
+            f(base)
+        }
+        Target::UncachedRAM => {
+            let memory = UncachedHeapMemory::<u64>::new(PATTERN.len());
+            let base = memory.as_ptr() as *mut u32;
+            preset(base);
+            f(base)
+        }
+        Target::SPMEM => {
+            let base = MemoryMap::uncached_spmem_address::<u32>(0x0);
+            preset(base);
+            f(base)
+        }
+        Target::ROM => {
+            // Read-only: PATTERN is already there
+            f(MemoryMap::uncached_cart_address(&PATTERN[0] as *const u64 as *const u32) as *mut u32)
+        }
+    }
+}
+
+fn test_loads(ops: &[Op], target: Target) -> Result<(), String> {
+    with_target(target, |base| {
+        for op in ops {
+            for offset in 0..PATTERN.len() * 8 {
+                for register in INITIAL_REGISTER {
+                    let result = load(*op, base as usize + offset, register);
+                    let expected = op.reference_load(pattern_at(offset, op.size()), offset, register);
+                    soft_assert_eq2(result, expected, || format!("{:?} from {} at offset {} into register {:#018x}", op, target.name(), offset, register))?;
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Returns the doubleword of PATTERN at the given byte offset after the aligned word (or doubleword) at that
+/// offset was replaced by `value`
+fn pattern_with(offset: usize, size: usize, value: u64) -> u64 {
+    let doubleword = PATTERN[offset / 8];
+    if size == 8 {
+        value
+    } else if (offset & 4) == 0 {
+        (doubleword & 0x00000000_FFFFFFFF) | (value << 32)
+    } else {
+        (doubleword & 0xFFFFFFFF_00000000) | value
+    }
+}
+
+fn test_stores(ops: &[Op], target: Target) -> Result<(), String> {
+    with_target(target, |base| {
+        for op in ops {
+            for offset in 0..PATTERN.len() * 8 {
+                preset(base);
+                store(*op, base as usize + offset, STORE_VALUE);
+
+                // The whole doubleword is checked, so that a word store that touches the other word is caught as well
+                let expected = pattern_with(offset, op.size(), op.reference_store(pattern_at(offset, op.size()), offset, STORE_VALUE));
+                soft_assert_eq2(read_at(base, offset, 8), expected, || format!("{:?} of {:#018x} to {} at offset {}", op, STORE_VALUE, target.name(), offset))?;
+
+                // Nothing else is touched
+                let other = (offset & !7) ^ 8;
+                soft_assert_eq2(read_at(base, other, 8), PATTERN[other / 8], || format!("{:?} to {} at offset {} changed memory at offset {}", op, target.name(), offset, other))?;
+            }
+        }
+        Ok(())
+    })
+}
+
+pub struct LWLLWR {}
+
+impl Test for LWLLWR {
+    fn name(&self) -> &str { "LWL/LWR (all offsets)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        vec! {
+            NamedValue::boxed("CachedRAM", Target::CachedRAM),
+            NamedValue::boxed("UncachedRAM", Target::UncachedRAM),
+            NamedValue::boxed("SPMEM", Target::SPMEM),
+            NamedValue::boxed("ROM", Target::ROM),
+        }
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        test_loads(&[Op::LWL, Op::LWR], *NamedValue::get::<Target>(value))
+    }
+}
+
+pub struct LDLLDR {}
+
+impl Test for LDLLDR {
+    fn name(&self) -> &str { "LDL/LDR (all offsets)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        // SPMEM and ROM are left out: 64 bit reads crash the console
+        vec! {
+            NamedValue::boxed("CachedRAM", Target::CachedRAM),
+            NamedValue::boxed("UncachedRAM", Target::UncachedRAM),
+        }
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        test_loads(&[Op::LDL, Op::LDR], *NamedValue::get::<Target>(value))
+    }
+}
+
+pub struct SWLSWR {}
+
+impl Test for SWLSWR {
+    fn name(&self) -> &str { "SWL/SWR (all offsets)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        vec! {
+            NamedValue::boxed("CachedRAM", Target::CachedRAM),
+            NamedValue::boxed("UncachedRAM", Target::UncachedRAM),
+        }
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        test_stores(&[Op::SWL, Op::SWR], *NamedValue::get::<Target>(value))
+    }
+}
+
+pub struct SDLSDR {}
+
+impl Test for SDLSDR {
+    fn name(&self) -> &str { "SDL/SDR (all offsets)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        vec! {
+            NamedValue::boxed("CachedRAM", Target::CachedRAM),
+            NamedValue::boxed("UncachedRAM", Target::UncachedRAM),
+        }
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        test_stores(&[Op::SDL, Op::SDR], *NamedValue::get::<Target>(value))
+    }
+}
+
+pub struct SWLSWRSPMEM {}
+
+impl Test for SWLSWRSPMEM {
+    fn name(&self) -> &str { "spmem: SWL/SWR (all offsets)" }
+
+    fn level(&self) -> Level { Level::Weird }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let spmem = MemoryMap::uncached_spmem_address::<u32>(0x0);
+        for op in [Op::SWL, Op::SWR] {
+            for offset in 0..8 {
+                preset(spmem);
+                store(op, spmem as usize + offset, STORE_VALUE);
+
+                // Like SB/SH, the whole word is written. What would otherwise be preserved becomes zero
+                let expected = op.reference_store(0, offset, STORE_VALUE);
+                soft_assert_eq2(read_at(spmem, offset, 4), expected, || format!("{:?} of {:#018x} to SPMEM at offset {}", op, STORE_VALUE, offset))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct AddressNotSignExtended {}
+
+impl Test for AddressNotSignExtended {
+    fn name(&self) -> &str { "Partial load/store with address not sign extended" }
+
+    fn level(&self) -> Level { Level::Weird }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        vec! {
+            NamedValue::boxed("LWL", Op::LWL),
+            NamedValue::boxed("LWR", Op::LWR),
+            NamedValue::boxed("LDL", Op::LDL),
+            NamedValue::boxed("LDR", Op::LDR),
+            NamedValue::boxed("SWL", Op::SWL),
+            NamedValue::boxed("SWR", Op::SWR),
+            NamedValue::boxed("SDL", Op::SDL),
+            NamedValue::boxed("SDR", Op::SDR),
+        }
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let op = *NamedValue::get::<Op>(value);
+        let (exception, cause) = if op.is_load() { (CauseException::AdEL, 0x10) } else { (CauseException::AdES, 0x14) };
+        let buffer = [0u64; 2];
+
+        for offset in 0..8 {
+            // Access 0x00000000_80xxxxxx, which causes an address error as the upper bits are 0
+            let p = (buffer.as_ptr() as usize + offset) as u32;
+            unsafe { cop0::set_context_64(0); }
+            unsafe { cop0::set_xcontext_64(0); }
+            let exception_context = expect_exception(exception, 1, || {
+                // The upper bits are zeroed out right before the access
+                unsafe {
+                    match op {
+                        Op::LWL => asm!("
+                            .set noat
+                            DSLL32 $2, $2, 0
+                            DSRL32 $2, $2, 0
+                            LWL $3, 0($2)
+                        ", inout("$2") p => _, out("$3") _),
+                        Op::LWR => asm!("
+                            .set noat
+                            DSLL32 $2, $2, 0
+                            DSRL32 $2, $2, 0
+                            LWR $3, 0($2)
+                        ", inout("$2") p => _, out("$3") _),
+                        Op::LDL => asm!("
+                            .set noat
+                            DSLL32 $2, $2, 0
+                            DSRL32 $2, $2, 0
+                            LDL $3, 0($2)
+                        ", inout("$2") p => _, out("$3") _),
+                        Op::LDR => asm!("
+                            .set noat
+                            DSLL32 $2, $2, 0
+                            DSRL32 $2, $2, 0
+                            LDR $3, 0($2)
+                        ", inout("$2") p => _, out("$3") _),
+                        Op::SWL => asm!("
+                            .set noat
+                            DSLL32 $2, $2, 0
+                            DSRL32 $2, $2, 0
+                            SWL $3, 0($2)
+                        ", inout("$2") p => _, in("$3") 0),
+                        Op::SWR => asm!("
+                            .set noat
+                            DSLL32 $2, $2, 0
+                            DSRL32 $2, $2, 0
+                            SWR $3, 0($2)
+                        ", inout("$2") p => _, in("$3") 0),
+                        Op::SDL => asm!("
+                            .set noat
+                            DSLL32 $2, $2, 0
+                            DSRL32 $2, $2, 0
+                            SDL $3, 0($2)
+                        ", inout("$2") p => _, in("$3") 0),
+                        Op::SDR => asm!("
+                            .set noat
+                            DSLL32 $2, $2, 0
+                            DSRL32 $2, $2, 0
+                            SDR $3, 0($2)
+                        ", inout("$2") p => _, in("$3") 0),
+                    }
+                }
+
+                Ok(())
+            })?;
+
+            let instruction = (op.opcode() << 26) | (2 << 21) | (3 << 16);
+            soft_assert_eq(exception_context.k0_exception_vector, 0xFFFFFFFF_80000180, "Exception Vector")?;
+            soft_assert_eq(exception_context.exceptpc & 0xFFFFFFFF_FF000000, 0xFFFFFFFF_80000000, "ExceptPC")?;
+            soft_assert_eq(unsafe { *(exception_context.exceptpc as *const u32) }, instruction, "ExceptPC points to wrong instruction")?;
+            soft_assert_eq2(exception_context.badvaddr, p as u64 & 0xFFFFFFFF, || format!("BadVAddr at offset {}", offset))?;
+            soft_assert_eq(exception_context.cause.raw_value(), cause, "Cause")?;
+            soft_assert_eq(exception_context.status, 0x24000002, "Status")?;
+            soft_assert_eq(exception_context.context, Context::from_virtual_address(p as u64), "Context during address error exception")?;
+            soft_assert_eq(exception_context.xcontext, XContext::from_virtual_address(p as u64), "XContext during address error exception")?;
+        }
+
+        Ok(())
+    }
+}
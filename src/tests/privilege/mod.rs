@@ -19,7 +19,7 @@ const KUSEG_EXEC_BASE: u32 = 0x0001_0000;
 const PAGE_WORDS: usize = 4096 / 4;
 
 #[naked]
-extern "C" fn return_via_s0_stub() {
+pub(crate) extern "C" fn return_via_s0_stub() {
     unsafe {
         asm!(
             ".set noat",
@@ -63,7 +63,7 @@ fn map_kuseg_page(vaddr: u32, paddr: usize, coherency: u8) {
     }
 }
 
-fn setup_program(program: &[u32], coherency: u8) -> Result<(UncachedHeapMemory<u32>, u32), String> {
+pub(crate) fn setup_program(program: &[u32], coherency: u8) -> Result<(UncachedHeapMemory<u32>, u32), String> {
     if program.len() >= PAGE_WORDS {
         return Err("Program too large for mapped page".into());
     }
@@ -119,6 +119,17 @@ pub(crate) fn run_mode_program_with_cop0(
     if cop0_usable {
         user_status = user_status.with_cop0usable(true);
     }
+    run_mode_program_with_status(user_status, entry, expected_exception, skip_instructions)
+}
+
+/// Enters the program at entry through ERET with the given Status (which should have EXL set) and returns to
+/// kernel mode on the first exception
+pub(crate) fn run_mode_program_with_status(
+    user_status: Status,
+    entry: u32,
+    expected_exception: CauseException,
+    skip_instructions: u64,
+) -> Result<ExceptionContext, String> {
     let kernel_status = Status::DEFAULT.with_exl(true).raw_value();
     let kernel_return_address = return_via_s0_stub as u32 as i32 as i64 as u64;
     let result = expect_exception(expected_exception, skip_instructions, || {
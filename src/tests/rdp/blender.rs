@@ -0,0 +1,256 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+use arbitrary_int::{u12, u5};
+
+use crate::graphics::color::ARGB8888;
+use crate::rdp::fixedpoint::{I12_2, I16_16, U10_2};
+use crate::rdp::modes::{A, AlphaCombiner, AlphaCombinerAddSub, AlphaCombinerMul, AlphaDitherMode, B, Blender, ColorCombiner, CombineMode, CombinerAdd, CombinerMul, CombinerSubA, CombinerSubB, CoverageMode, CycleType, Format, Othermode, PixelSize, PM, RGBDitherMode};
+use crate::rdp::rdp::RDP;
+use crate::rdp::rdp_assembler::{RDPAssembler, RDPRectangle, ShadeCoefficients, TriangleBase};
+use crate::tests::{Level, NamedValue, Test};
+use crate::tests::soft_asserts::soft_assert_eq_2d_array;
+use crate::uncached_memory::UncachedHeapMemory;
+
+// Notes:
+// - The blender calculates P * A + M * B per color channel. A and B are only used with their upper 5 bits and
+//   B is incremented by one: (P * (A >> 3) + M * ((B >> 3) + 1)) >> 5. This means that B = 0 still adds M / 32
+// - With force_blend, that is the result. Without force_blend, the result is divided by (A + B) instead, but
+//   only for pixels with partial coverage - fully covered pixels skip the last blender cycle and output P
+// - In 2 cycle mode, the first cycle always uses the equation above (without a division). In the second cycle,
+//   P/M = "combined color" refers to the output of the first cycle
+// - In 1 cycle mode, the blender settings of the first cycle are used
+// - The memory color is the framebuffer pixel, as read with image_read. For a 32 bit framebuffer, the memory
+//   alpha is the coverage (upper 3 bits of the lowest byte)
+// - Not covered here: memory alpha as B (which is scaled by the depth slope), division without force_blend,
+//   color_on_coverage and alpha compare
+
+const WIDTH: usize = 4;
+const HEIGHT: usize = 2;
+
+/// The combiner outputs the primitive color and alpha in both cycles
+const COMBINE_PRIMITIVE: CombineMode = CombineMode::DEFAULT.with_both_cycles(
+    ColorCombiner::new(CombinerSubA::Zero, CombinerSubB::Zero, CombinerMul::Zero, CombinerAdd::Primitive),
+    AlphaCombiner::new(AlphaCombinerAddSub::Zero, AlphaCombinerAddSub::Zero, AlphaCombinerMul::Zero, AlphaCombinerAddSub::Primitive));
+
+/// All blender inputs that are used by the tests. Colors are R, G, B, A
+struct Inputs {
+    combined: [u8; 4],
+    memory: [u8; 3],
+    blend: [u8; 4],
+    fog: [u8; 4],
+    shade: [u8; 4],
+}
+
+const INPUTS: Inputs = Inputs {
+    combined: [0xC0, 0x40, 0x80, 0x60],
+    memory: [0x30, 0xA0, 0x60],
+    blend: [0x10, 0xF0, 0x90, 0x50],
+    fog: [0x70, 0x20, 0xE0, 0xB0],
+    shade: [0x11, 0x22, 0x33, 0x90],
+};
+
+/// A blender cycle, in the order of the equation: P * A + M * B
+#[derive(Copy, Clone)]
+struct BlenderCycle {
+    p: PM,
+    a: A,
+    m: PM,
+    b: B,
+}
+
+impl BlenderCycle {
+    const fn new(p: PM, a: A, m: PM, b: B) -> Self { Self { p, a, m, b } }
+
+    fn blender(&self) -> Blender { Blender::new(self.a, self.p, self.b, self.m) }
+}
+
+const ONE_CYCLE_CASES: [(&'static str, BlenderCycle, bool); 7] = [
+    ("combined * combined alpha + memory * (1 - a)", BlenderCycle::new(PM::CombineColor, A::CombineAlpha, PM::MemoryColor, B::InverseA), true),
+    ("blend color * shade alpha + memory * (1 - a)", BlenderCycle::new(PM::BlendColor, A::ShadeAlpha, PM::MemoryColor, B::InverseA), true),
+    ("fog color * fog alpha + combined * (1 - a)", BlenderCycle::new(PM::FogColor, A::FogAlpha, PM::CombineColor, B::InverseA), true),
+    ("combined * 0 + memory * 1", BlenderCycle::new(PM::CombineColor, A::Zero, PM::MemoryColor, B::One), true),
+    ("memory * combined alpha + fog color * 0", BlenderCycle::new(PM::MemoryColor, A::CombineAlpha, PM::FogColor, B::Zero), true),
+    ("blend color * 0 + blend color * 0", BlenderCycle::new(PM::BlendColor, A::Zero, PM::BlendColor, B::Zero), true),
+    ("combined * combined alpha + memory * (1 - a) without force_blend", BlenderCycle::new(PM::CombineColor, A::CombineAlpha, PM::MemoryColor, B::InverseA), false),
+];
+
+/// The usual fog setup: Fog in the first cycle, followed by translucency in the second
+const FOG: BlenderCycle = BlenderCycle::new(PM::FogColor, A::ShadeAlpha, PM::CombineColor, B::InverseA);
+const TRANSLUCENT: BlenderCycle = BlenderCycle::new(PM::CombineColor, A::CombineAlpha, PM::MemoryColor, B::InverseA);
+
+const TWO_CYCLE_CASES: [(&'static str, BlenderCycle, BlenderCycle, bool); 4] = [
+    ("fog, then translucent", FOG, TRANSLUCENT, true),
+    ("fog, then bypassed without force_blend", FOG, TRANSLUCENT, false),
+    ("translucent, then fog", TRANSLUCENT, BlenderCycle::new(PM::FogColor, A::FogAlpha, PM::CombineColor, B::InverseA), true),
+    ("memory, then blend color * 0 + combined * 1", BlenderCycle::new(PM::CombineColor, A::Zero, PM::MemoryColor, B::One), BlenderCycle::new(PM::BlendColor, A::Zero, PM::CombineColor, B::One), true),
+];
+
+/// P * A + M * B, using the upper 5 bits of A and B and with B incremented by one
+fn blend(p: u8, a: u8, m: u8, b: u8) -> u8 {
+    let a = (a >> 3) as u32;
+    let b = ((b >> 3) + 1) as u32;
+    ((((p as u32) * a) + ((m as u32) * b)) >> 5) as u8
+}
+
+impl Inputs {
+    /// A color input. `pixel` is the combined color in the first cycle and the blended color in the second cycle
+    fn color(&self, input: PM, pixel: &[u8; 3], channel: usize) -> u8 {
+        match input {
+            PM::CombineColor => pixel[channel],
+            PM::MemoryColor => self.memory[channel],
+            PM::BlendColor => self.blend[channel],
+            PM::FogColor => self.fog[channel],
+        }
+    }
+
+    fn alpha_a(&self, input: A) -> u8 {
+        match input {
+            A::CombineAlpha => self.combined[3],
+            A::FogAlpha => self.fog[3],
+            A::ShadeAlpha => self.shade[3],
+            A::Zero => 0,
+        }
+    }
+
+    fn alpha_b(&self, input: B, a: u8) -> u8 {
+        match input {
+            B::InverseA => !a,
+            B::One => 0xFF,
+            B::Zero => 0,
+            B::MemoryAlpha => panic!("Memory alpha is not supported by this test"),
+        }
+    }
+
+    /// Runs a single blender cycle on all three color channels
+    fn cycle(&self, cycle: &BlenderCycle, pixel: &[u8; 3]) -> [u8; 3] {
+        let a = self.alpha_a(cycle.a);
+        let b = self.alpha_b(cycle.b, a);
+        let mut result = [0u8; 3];
+        for channel in 0..3 {
+            result[channel] = blend(self.color(cycle.p, pixel, channel), a, self.color(cycle.m, pixel, channel), b);
+        }
+        result
+    }
+
+    /// The color of the last blender cycle. Without force_blend, fully covered pixels simply get P
+    fn last_cycle(&self, cycle: &BlenderCycle, pixel: &[u8; 3], force_blend: bool) -> [u8; 3] {
+        if force_blend {
+            self.cycle(cycle, pixel)
+        } else {
+            [self.color(cycle.p, pixel, 0), self.color(cycle.p, pixel, 1), self.color(cycle.p, pixel, 2)]
+        }
+    }
+}
+
+/// The framebuffer contents for the given blender output: RGBA with full coverage
+fn expected_pixels(color: [u8; 3]) -> [[u32; WIDTH]; HEIGHT] {
+    let pixel = ((color[0] as u32) << 24) | ((color[1] as u32) << 16) | ((color[2] as u32) << 8) | 0xE0;
+    [[pixel; WIDTH]; HEIGHT]
+}
+
+/// The RDP takes blend and fog color as RGBA, so the raw value is used as-is
+fn rgba(color: [u8; 4]) -> ARGB8888 {
+    ARGB8888::new_with_raw_value(((color[0] as u32) << 24) | ((color[1] as u32) << 16) | ((color[2] as u32) << 8) | (color[3] as u32))
+}
+
+/// Draws a shaded triangle over a framebuffer that is filled with the memory color
+fn render_on_rdp(othermode: Othermode) -> ([[u32; WIDTH]; HEIGHT], RDPAssembler) {
+    let memory = ((INPUTS.memory[0] as u32) << 24) | ((INPUTS.memory[1] as u32) << 16) | ((INPUTS.memory[2] as u32) << 8) | 0xE0;
+    let mut framebuffer = UncachedHeapMemory::<u32>::new_with_init_value(WIDTH * HEIGHT, memory);
+
+    let shade = ShadeCoefficients::flat(INPUTS.shade[0], INPUTS.shade[1], INPUTS.shade[2], INPUTS.shade[3]);
+    let rectangle = TriangleBase::new(
+        true, 0, 0,
+        I12_2::from_i32(HEIGHT as i32),
+        I12_2::from_i32(HEIGHT as i32),
+        I12_2::from_i32(0),
+        I16_16::from_i32(WIDTH as i32),
+        I16_16::from_i32(WIDTH as i32),
+        I16_16::from_i32(0),
+        I16_16::from_i32(0),
+        I16_16::from_i32(0),
+        I16_16::from_i32(0),
+    );
+
+    let mut assembler = RDPAssembler::new();
+    assembler.set_framebuffer_image(Format::RGBA, PixelSize::Bits32, u12::new((WIDTH - 1) as u16), &mut framebuffer);
+    assembler.set_scissor(&RDPRectangle::new(U10_2::from_usize(0), U10_2::from_usize(0), U10_2::from_usize(WIDTH), U10_2::from_usize(HEIGHT)));
+    assembler.set_othermode(othermode
+        .with_coverage_mode(CoverageMode::Zap)
+        .with_rgb_dither_mode(RGBDitherMode::None)
+        .with_alpha_dither_mode(AlphaDitherMode::None)
+        .with_image_read(true));
+    assembler.set_combine_mode(COMBINE_PRIMITIVE);
+    assembler.set_primitive_color(u5::new(0), 0, ARGB8888::new(INPUTS.combined[0], INPUTS.combined[1], INPUTS.combined[2], INPUTS.combined[3]));
+    assembler.set_blendcolor(rgba(INPUTS.blend));
+    assembler.set_fog_color(rgba(INPUTS.fog));
+    assembler.shaded_triangle(&rectangle, &shade);
+    assembler.sync_pipe();
+    assembler.sync_full();
+
+    RDP::run_and_wait(&mut assembler);
+
+    // Copy into non-uncached array
+    let mut pixels = [[0u32; WIDTH]; HEIGHT];
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            pixels[y][x] = framebuffer.read(y * WIDTH + x);
+        }
+    }
+    (pixels, assembler)
+}
+
+pub struct Blender1Cycle {}
+
+impl Test for Blender1Cycle {
+    fn name(&self) -> &str { "RDP Blender (1 cycle)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        ONE_CYCLE_CASES.iter().map(|case| NamedValue::boxed(case.0, *case)).collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (name, cycle, force_blend) = *NamedValue::get::<(&'static str, BlenderCycle, bool)>(value);
+
+        let (actual, assembler) = render_on_rdp(Othermode::new()
+            .with_cycle_type(CycleType::SingleCycle)
+            .with_blender_0(cycle.blender())
+            .with_force_blend(force_blend));
+        let combined = [INPUTS.combined[0], INPUTS.combined[1], INPUTS.combined[2]];
+        let expected = expected_pixels(INPUTS.last_cycle(&cycle, &combined, force_blend));
+
+        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels for {}. Command list:\n{}", name, assembler.disassemble()))
+    }
+}
+
+pub struct Blender2Cycle {}
+
+impl Test for Blender2Cycle {
+    fn name(&self) -> &str { "RDP Blender (2 cycle)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        TWO_CYCLE_CASES.iter().map(|case| NamedValue::boxed(case.0, *case)).collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (name, cycle_0, cycle_1, force_blend) = *NamedValue::get::<(&'static str, BlenderCycle, BlenderCycle, bool)>(value);
+
+        let (actual, assembler) = render_on_rdp(Othermode::new()
+            .with_cycle_type(CycleType::DualCycle)
+            .with_blender_0(cycle_0.blender())
+            .with_blender_1(cycle_1.blender())
+            .with_force_blend(force_blend));
+        let combined = [INPUTS.combined[0], INPUTS.combined[1], INPUTS.combined[2]];
+        let blended = INPUTS.cycle(&cycle_0, &combined);
+        let expected = expected_pixels(INPUTS.last_cycle(&cycle_1, &blended, force_blend));
+
+        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels for {}. Command list:\n{}", name, assembler.disassemble()))
+    }
+}
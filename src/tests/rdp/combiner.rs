@@ -0,0 +1,373 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+use arbitrary_int::{u12, u5};
+
+use crate::graphics::color::ARGB8888;
+use crate::rdp::fixedpoint::{I12_2, I16_16, U10_2};
+use crate::rdp::modes::{A, AlphaCombiner, AlphaCombinerAddSub, AlphaCombinerMul, AlphaDitherMode, B, Blender, ColorCombiner, CombineMode, CombinerAdd, CombinerMul, CombinerSubA, CombinerSubB, CoverageMode, CycleType, Format, Othermode, PixelSize, PM, RGBDitherMode};
+use crate::rdp::rdp::RDP;
+use crate::rdp::rdp_assembler::{ChromaKey, RDPAssembler, RDPRectangle, ShadeCoefficients, TriangleBase};
+use crate::tests::{Level, NamedValue, Test};
+use crate::tests::soft_asserts::soft_assert_eq_2d_array;
+use crate::uncached_memory::UncachedHeapMemory;
+
+// Notes:
+// - Each combiner cycle calculates ((a - b) * c + (d << 8) + 0x80) >> 8 per channel. a, b and d are 9 bit signed
+//   values (0x180..0x1FF are negative), "one" is 0x100. c is sign extended from 9 bits as well
+// - The 9 bit result is clamped: 0x000..0x0FF are used as-is, 0x100..0x17F saturate to 0xFF and 0x180..0x1FF
+//   (negative) become 0
+// - In 1 cycle mode, only the settings of the second cycle are used
+// - In 2 cycle mode, the result of the first cycle is available to the second cycle as "Combined". The tests keep it
+//   within 0..0xFF, as it is not clear yet how much of an overflow survives into the second cycle
+// - The combined alpha doesn't end up in a 32 bit framebuffer (the lower bits are coverage). To test the alpha
+//   combiner, the second cycle moves it into the color channels: (1 - 0) * CombinedAlpha + 0
+// - Not covered here: Textures, noise, the YUV convert inputs and the per pixel LOD fraction
+
+const WIDTH: usize = 4;
+const HEIGHT: usize = 2;
+
+/// All combiner inputs that are used by the tests. Index 0 to 2 are RGB, 3 is alpha
+struct Inputs {
+    primitive: [u8; 4],
+    environment: [u8; 4],
+    shade: [u8; 4],
+    key_center: [u8; 3],
+    key_scale: [u8; 3],
+    primitive_lod_fraction: u8,
+}
+
+const INPUTS: Inputs = Inputs {
+    primitive: [0xC0, 0x40, 0x80, 0x60],
+    environment: [0x20, 0x90, 0x30, 0xA0],
+    shade: [0x50, 0x70, 0x10, 0x30],
+    key_center: [0x18, 0x28, 0x38],
+    key_scale: [0x80, 0x40, 0xC0],
+    primitive_lod_fraction: 0x44,
+};
+
+/// Alpha for tests that only look at the color
+const ALPHA_ZERO: AlphaCombiner = AlphaCombiner::new(AlphaCombinerAddSub::Zero, AlphaCombinerAddSub::Zero, AlphaCombinerMul::Zero, AlphaCombinerAddSub::Zero);
+
+const RGB_ZERO: ColorCombiner = ColorCombiner::new(CombinerSubA::Zero, CombinerSubB::Zero, CombinerMul::Zero, CombinerAdd::Zero);
+
+const RGB_ONE: ColorCombiner = ColorCombiner::new(CombinerSubA::Zero, CombinerSubB::Zero, CombinerMul::Zero, CombinerAdd::One);
+
+/// Second cycle that makes the combined alpha of the first cycle visible
+const RGB_COMBINED_ALPHA: ColorCombiner = ColorCombiner::new(CombinerSubA::One, CombinerSubB::Zero, CombinerMul::CombinedAlpha, CombinerAdd::Zero);
+
+const fn rgb(a: CombinerSubA, b: CombinerSubB, c: CombinerMul, d: CombinerAdd) -> ColorCombiner { ColorCombiner::new(a, b, c, d) }
+
+const fn alpha(a: AlphaCombinerAddSub, b: AlphaCombinerAddSub, c: AlphaCombinerMul, d: AlphaCombinerAddSub) -> AlphaCombiner { AlphaCombiner::new(a, b, c, d) }
+
+const ONE_CYCLE_CASES: [(&'static str, ColorCombiner); 13] = [
+    ("primitive", rgb(CombinerSubA::Zero, CombinerSubB::Zero, CombinerMul::Zero, CombinerAdd::Primitive)),
+    ("environment", rgb(CombinerSubA::Zero, CombinerSubB::Zero, CombinerMul::Zero, CombinerAdd::Environment)),
+    ("shade", rgb(CombinerSubA::Zero, CombinerSubB::Zero, CombinerMul::Zero, CombinerAdd::Shade)),
+    ("one", rgb(CombinerSubA::Zero, CombinerSubB::Zero, CombinerMul::Zero, CombinerAdd::One)),
+    ("zero", rgb(CombinerSubA::Zero, CombinerSubB::Zero, CombinerMul::Zero, CombinerAdd::Zero)),
+    ("(primitive - environment) * shade alpha + environment", rgb(CombinerSubA::Primitive, CombinerSubB::Environment, CombinerMul::ShadeAlpha, CombinerAdd::Environment)),
+    ("(environment - primitive) * primitive LOD fraction + primitive", rgb(CombinerSubA::Environment, CombinerSubB::Primitive, CombinerMul::PrimitiveLODFraction, CombinerAdd::Primitive)),
+    ("(shade - 0) * environment + 0", rgb(CombinerSubA::Shade, CombinerSubB::Zero, CombinerMul::Environment, CombinerAdd::Zero)),
+    ("(1 - 0) * primitive alpha + 0", rgb(CombinerSubA::One, CombinerSubB::Zero, CombinerMul::PrimitiveAlpha, CombinerAdd::Zero)),
+    ("(1 - environment) * primitive + environment", rgb(CombinerSubA::One, CombinerSubB::Environment, CombinerMul::Primitive, CombinerAdd::Environment)),
+    ("(primitive - shade) * environment alpha + 1 (overflow)", rgb(CombinerSubA::Primitive, CombinerSubB::Shade, CombinerMul::EnvironmentAlpha, CombinerAdd::One)),
+    ("(0 - primitive) * environment + 0 (underflow)", rgb(CombinerSubA::Zero, CombinerSubB::Primitive, CombinerMul::Environment, CombinerAdd::Zero)),
+    ("(primitive - key center) * key scale + 0", rgb(CombinerSubA::Primitive, CombinerSubB::KeyCenter, CombinerMul::KeyScale, CombinerAdd::Zero)),
+];
+
+const TWO_CYCLE_CASES: [(&'static str, ColorCombiner, ColorCombiner); 4] = [
+    ("lerp, then modulate",
+     rgb(CombinerSubA::Primitive, CombinerSubB::Environment, CombinerMul::ShadeAlpha, CombinerAdd::Environment),
+     rgb(CombinerSubA::Combined, CombinerSubB::Zero, CombinerMul::Shade, CombinerAdd::Zero)),
+    ("modulate, then lerp",
+     rgb(CombinerSubA::Shade, CombinerSubB::Zero, CombinerMul::Environment, CombinerAdd::Zero),
+     rgb(CombinerSubA::Combined, CombinerSubB::Primitive, CombinerMul::EnvironmentAlpha, CombinerAdd::Primitive)),
+    ("combined as addend (overflow)",
+     rgb(CombinerSubA::Zero, CombinerSubB::Zero, CombinerMul::Zero, CombinerAdd::Primitive),
+     rgb(CombinerSubA::Environment, CombinerSubB::Zero, CombinerMul::ShadeAlpha, CombinerAdd::Combined)),
+    ("first cycle unused",
+     rgb(CombinerSubA::Zero, CombinerSubB::Zero, CombinerMul::Zero, CombinerAdd::One),
+     rgb(CombinerSubA::Zero, CombinerSubB::Zero, CombinerMul::Zero, CombinerAdd::Shade)),
+];
+
+const ALPHA_CASES: [(&'static str, AlphaCombiner); 7] = [
+    ("primitive", alpha(AlphaCombinerAddSub::Zero, AlphaCombinerAddSub::Zero, AlphaCombinerMul::Zero, AlphaCombinerAddSub::Primitive)),
+    ("environment", alpha(AlphaCombinerAddSub::Zero, AlphaCombinerAddSub::Zero, AlphaCombinerMul::Zero, AlphaCombinerAddSub::Environment)),
+    ("shade", alpha(AlphaCombinerAddSub::Zero, AlphaCombinerAddSub::Zero, AlphaCombinerMul::Zero, AlphaCombinerAddSub::Shade)),
+    ("(primitive - environment) * shade + environment", alpha(AlphaCombinerAddSub::Primitive, AlphaCombinerAddSub::Environment, AlphaCombinerMul::Shade, AlphaCombinerAddSub::Environment)),
+    ("(1 - primitive) * environment + 0", alpha(AlphaCombinerAddSub::One, AlphaCombinerAddSub::Primitive, AlphaCombinerMul::Environment, AlphaCombinerAddSub::Zero)),
+    ("(environment - shade) * primitive LOD fraction + shade", alpha(AlphaCombinerAddSub::Environment, AlphaCombinerAddSub::Shade, AlphaCombinerMul::PrimitiveLODFraction, AlphaCombinerAddSub::Shade)),
+    ("(primitive - 0) * primitive + 0", alpha(AlphaCombinerAddSub::Primitive, AlphaCombinerAddSub::Zero, AlphaCombinerMul::Primitive, AlphaCombinerAddSub::Zero)),
+];
+
+/// Interprets a 9 bit value the way the combiner does for a, b and d
+fn extend_9bit(value: i32) -> i32 {
+    let value = value & 0x1FF;
+    if (value & 0x180) == 0x180 { value - 0x200 } else { value }
+}
+
+/// Clamps a 9 bit combiner result to 8 bit
+fn clamp_9bit(value: i32) -> u8 {
+    let value = value & 0x1FF;
+    if (value & 0x100) == 0 {
+        value as u8
+    } else if (value & 0x80) == 0 {
+        0xFF
+    } else {
+        0
+    }
+}
+
+/// (a - b) * c + d, rounded. Returns the unclamped 9 bit result
+fn equation(a: i32, b: i32, c: i32, d: i32) -> i32 {
+    let c = (c << 23) >> 23;
+    ((((extend_9bit(a) - extend_9bit(b)) * c) + (extend_9bit(d) << 8) + 0x80) >> 8) & 0x1FF
+}
+
+impl Inputs {
+    fn rgb_a(&self, input: CombinerSubA, combined: &[i32; 4], channel: usize) -> i32 {
+        match input {
+            CombinerSubA::Combined => combined[channel],
+            CombinerSubA::Primitive => self.primitive[channel] as i32,
+            CombinerSubA::Shade => self.shade[channel] as i32,
+            CombinerSubA::Environment => self.environment[channel] as i32,
+            CombinerSubA::One => 0x100,
+            CombinerSubA::Zero => 0,
+            _ => panic!("Unhandled combiner input"),
+        }
+    }
+
+    fn rgb_b(&self, input: CombinerSubB, combined: &[i32; 4], channel: usize) -> i32 {
+        match input {
+            CombinerSubB::Combined => combined[channel],
+            CombinerSubB::Primitive => self.primitive[channel] as i32,
+            CombinerSubB::Shade => self.shade[channel] as i32,
+            CombinerSubB::Environment => self.environment[channel] as i32,
+            CombinerSubB::KeyCenter => self.key_center[channel] as i32,
+            CombinerSubB::Zero => 0,
+            _ => panic!("Unhandled combiner input"),
+        }
+    }
+
+    fn rgb_c(&self, input: CombinerMul, combined: &[i32; 4], channel: usize) -> i32 {
+        match input {
+            CombinerMul::Combined => combined[channel],
+            CombinerMul::Primitive => self.primitive[channel] as i32,
+            CombinerMul::Shade => self.shade[channel] as i32,
+            CombinerMul::Environment => self.environment[channel] as i32,
+            CombinerMul::KeyScale => self.key_scale[channel] as i32,
+            CombinerMul::CombinedAlpha => combined[3],
+            CombinerMul::PrimitiveAlpha => self.primitive[3] as i32,
+            CombinerMul::ShadeAlpha => self.shade[3] as i32,
+            CombinerMul::EnvironmentAlpha => self.environment[3] as i32,
+            CombinerMul::PrimitiveLODFraction => self.primitive_lod_fraction as i32,
+            CombinerMul::Zero => 0,
+            _ => panic!("Unhandled combiner input"),
+        }
+    }
+
+    fn rgb_d(&self, input: CombinerAdd, combined: &[i32; 4], channel: usize) -> i32 {
+        match input {
+            CombinerAdd::Combined => combined[channel],
+            CombinerAdd::Primitive => self.primitive[channel] as i32,
+            CombinerAdd::Shade => self.shade[channel] as i32,
+            CombinerAdd::Environment => self.environment[channel] as i32,
+            CombinerAdd::One => 0x100,
+            CombinerAdd::Zero => 0,
+            _ => panic!("Unhandled combiner input"),
+        }
+    }
+
+    fn alpha_abd(&self, input: AlphaCombinerAddSub, combined: &[i32; 4]) -> i32 {
+        match input {
+            AlphaCombinerAddSub::Combined => combined[3],
+            AlphaCombinerAddSub::Primitive => self.primitive[3] as i32,
+            AlphaCombinerAddSub::Shade => self.shade[3] as i32,
+            AlphaCombinerAddSub::Environment => self.environment[3] as i32,
+            AlphaCombinerAddSub::One => 0x100,
+            AlphaCombinerAddSub::Zero => 0,
+            _ => panic!("Unhandled combiner input"),
+        }
+    }
+
+    fn alpha_c(&self, input: AlphaCombinerMul) -> i32 {
+        match input {
+            AlphaCombinerMul::Primitive => self.primitive[3] as i32,
+            AlphaCombinerMul::Shade => self.shade[3] as i32,
+            AlphaCombinerMul::Environment => self.environment[3] as i32,
+            AlphaCombinerMul::PrimitiveLODFraction => self.primitive_lod_fraction as i32,
+            AlphaCombinerMul::Zero => 0,
+            _ => panic!("Unhandled combiner input"),
+        }
+    }
+
+    /// Runs a single combiner cycle. Returns the unclamped 9 bit RGBA result
+    fn cycle(&self, combined: &[i32; 4], rgb: ColorCombiner, alpha: AlphaCombiner) -> [i32; 4] {
+        let mut result = [0i32; 4];
+        for channel in 0..3 {
+            result[channel] = equation(
+                self.rgb_a(rgb.a(), combined, channel),
+                self.rgb_b(rgb.b(), combined, channel),
+                self.rgb_c(rgb.c(), combined, channel),
+                self.rgb_d(rgb.d(), combined, channel));
+        }
+        result[3] = equation(
+            self.alpha_abd(alpha.a(), combined),
+            self.alpha_abd(alpha.b(), combined),
+            self.alpha_c(alpha.c()),
+            self.alpha_abd(alpha.d(), combined));
+        result
+    }
+}
+
+/// The framebuffer contents for the given combiner output: RGBA with full coverage
+fn expected_pixels(combined: [i32; 4]) -> [[u32; WIDTH]; HEIGHT] {
+    let pixel = ((clamp_9bit(combined[0]) as u32) << 24) |
+        ((clamp_9bit(combined[1]) as u32) << 16) |
+        ((clamp_9bit(combined[2]) as u32) << 8) |
+        0xE0;
+    [[pixel; WIDTH]; HEIGHT]
+}
+
+/// Fills the whole framebuffer with a shaded triangle using the given combine mode
+fn render_on_rdp(cycle_type: CycleType, combine_mode: CombineMode) -> ([[u32; WIDTH]; HEIGHT], RDPAssembler) {
+    let mut framebuffer = UncachedHeapMemory::<u32>::new_with_init_value(WIDTH * HEIGHT, 0);
+
+    // In 2 cycle mode, the first blender cycle passes the combined color through: (P * 0 + M * 1). The last
+    // blender cycle is bypassed as there is full coverage and no force_blend
+    let othermode = Othermode::new()
+        .with_cycle_type(cycle_type)
+        .with_coverage_mode(CoverageMode::Zap)
+        .with_rgb_dither_mode(RGBDitherMode::None)
+        .with_alpha_dither_mode(AlphaDitherMode::None)
+        .with_blender_0(if matches!(cycle_type, CycleType::DualCycle) {
+            Blender::new(A::Zero, PM::CombineColor, B::One, PM::CombineColor)
+        } else {
+            Blender::new(A::CombineAlpha, PM::CombineColor, B::InverseA, PM::MemoryColor)
+        })
+        .with_blender_1(Blender::new(A::CombineAlpha, PM::CombineColor, B::InverseA, PM::MemoryColor));
+
+    let key = |channel: usize| ChromaKey::new(u12::new(0), INPUTS.key_center[channel], INPUTS.key_scale[channel]);
+    let color = |value: [u8; 4]| ARGB8888::new(value[0], value[1], value[2], value[3]);
+    let shade = ShadeCoefficients::flat(INPUTS.shade[0], INPUTS.shade[1], INPUTS.shade[2], INPUTS.shade[3]);
+    let rectangle = TriangleBase::new(
+        true, 0, 0,
+        I12_2::from_i32(HEIGHT as i32),
+        I12_2::from_i32(HEIGHT as i32),
+        I12_2::from_i32(0),
+        I16_16::from_i32(WIDTH as i32),
+        I16_16::from_i32(WIDTH as i32),
+        I16_16::from_i32(0),
+        I16_16::from_i32(0),
+        I16_16::from_i32(0),
+        I16_16::from_i32(0),
+    );
+
+    let mut assembler = RDPAssembler::new();
+    assembler.set_framebuffer_image(Format::RGBA, PixelSize::Bits32, u12::new((WIDTH - 1) as u16), &mut framebuffer);
+    assembler.set_scissor(&RDPRectangle::new(U10_2::from_usize(0), U10_2::from_usize(0), U10_2::from_usize(WIDTH), U10_2::from_usize(HEIGHT)));
+    assembler.set_othermode(othermode);
+    assembler.set_combine_mode(combine_mode);
+    assembler.set_primitive_color(u5::new(0), INPUTS.primitive_lod_fraction, color(INPUTS.primitive));
+    assembler.set_env_color(color(INPUTS.environment));
+    assembler.set_key(&key(0), &key(1), &key(2));
+    assembler.shaded_triangle(&rectangle, &shade);
+    assembler.sync_pipe();
+    assembler.sync_full();
+
+    RDP::run_and_wait(&mut assembler);
+
+    // Copy into non-uncached array
+    let mut pixels = [[0u32; WIDTH]; HEIGHT];
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            pixels[y][x] = framebuffer.read(y * WIDTH + x);
+        }
+    }
+    (pixels, assembler)
+}
+
+pub struct Combiner1Cycle {}
+
+impl Test for Combiner1Cycle {
+    fn name(&self) -> &str { "RDP Combiner (1 cycle)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        ONE_CYCLE_CASES.iter().map(|case| NamedValue::boxed(case.0, *case)).collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (name, rgb) = *NamedValue::get::<(&'static str, ColorCombiner)>(value);
+
+        // Only the second cycle matters. Make the first one produce something else to see that it is really ignored
+        let combine_mode = CombineMode::DEFAULT
+            .with_rgb_0(RGB_ONE)
+            .with_alpha_0(ALPHA_ZERO)
+            .with_rgb_1(rgb)
+            .with_alpha_1(ALPHA_ZERO);
+        let (actual, assembler) = render_on_rdp(CycleType::SingleCycle, combine_mode);
+        let expected = expected_pixels(INPUTS.cycle(&[0; 4], rgb, ALPHA_ZERO));
+
+        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels for {}. Command list:\n{}", name, assembler.disassemble()))
+    }
+}
+
+pub struct Combiner2Cycle {}
+
+impl Test for Combiner2Cycle {
+    fn name(&self) -> &str { "RDP Combiner (2 cycle)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        TWO_CYCLE_CASES.iter().map(|case| NamedValue::boxed(case.0, *case)).collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (name, rgb_0, rgb_1) = *NamedValue::get::<(&'static str, ColorCombiner, ColorCombiner)>(value);
+
+        let combine_mode = CombineMode::DEFAULT
+            .with_rgb_0(rgb_0)
+            .with_alpha_0(ALPHA_ZERO)
+            .with_rgb_1(rgb_1)
+            .with_alpha_1(ALPHA_ZERO);
+        let (actual, assembler) = render_on_rdp(CycleType::DualCycle, combine_mode);
+        let first_cycle = INPUTS.cycle(&[0; 4], rgb_0, ALPHA_ZERO);
+        let expected = expected_pixels(INPUTS.cycle(&first_cycle, rgb_1, ALPHA_ZERO));
+
+        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels for {}. Command list:\n{}", name, assembler.disassemble()))
+    }
+}
+
+pub struct CombinerAlpha2Cycle {}
+
+impl Test for CombinerAlpha2Cycle {
+    fn name(&self) -> &str { "RDP Combiner alpha (2 cycle)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        ALPHA_CASES.iter().map(|case| NamedValue::boxed(case.0, *case)).collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (name, alpha_0) = *NamedValue::get::<(&'static str, AlphaCombiner)>(value);
+        let rgb_0 = RGB_ZERO;
+
+        let combine_mode = CombineMode::DEFAULT
+            .with_rgb_0(rgb_0)
+            .with_alpha_0(alpha_0)
+            .with_rgb_1(RGB_COMBINED_ALPHA)
+            .with_alpha_1(ALPHA_ZERO);
+        let (actual, assembler) = render_on_rdp(CycleType::DualCycle, combine_mode);
+        let first_cycle = INPUTS.cycle(&[0; 4], rgb_0, alpha_0);
+        let expected = expected_pixels(INPUTS.cycle(&first_cycle, RGB_COMBINED_ALPHA, ALPHA_ZERO));
+
+        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels for {}. Command list:\n{}", name, assembler.disassemble()))
+    }
+}
@@ -0,0 +1,295 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+use arbitrary_int::{u12, u15};
+
+use crate::graphics::color::ARGB8888;
+use crate::rdp::fixedpoint::{I12_2, I16_16, U10_2};
+use crate::rdp::modes::{A, AlphaCombiner, AlphaCombinerAddSub, AlphaCombinerMul, AlphaDitherMode, B, Blender, ColorCombiner, CombineMode, CombinerAdd, CombinerMul, CombinerSubA, CombinerSubB, CoverageMode, CycleType, Format, Othermode, PixelSize, PM, RGBDitherMode, ZMode};
+use crate::rdp::rdp::RDP;
+use crate::rdp::rdp_assembler::{DepthCoefficients, RDPAssembler, RDPRectangle, ShadeCoefficients, TriangleBase};
+use crate::rdp::reference;
+use crate::tests::{Level, NamedValue, Test};
+use crate::tests::soft_asserts::soft_assert_eq_2d_array;
+use crate::uncached_memory::UncachedHeapMemory;
+
+// Notes:
+// - Depth is 16.16 with a 15 bit integer part. Internally, the RDP works with 18 bit (15.3) depth values
+// - The depth buffer is 16 bit per pixel: A 14 bit floating point depth (3 bit exponent that counts the leading ones,
+//   11 bit mantissa), followed by the upper two bits of the 4 bit delta-z. The lower two bits of delta-z go into the
+//   hidden bits of RDRAM
+// - In opaque mode without antialiasing, a pixel passes the depth test if it is in front (smaller) of what is in the
+//   depth buffer, or if the depth buffer contains the maximum value
+// - All triangles in here have vertical edges at whole pixels (i.e. they are rectangles) and constant depth and
+//   color, so every pixel is either fully covered or not covered at all
+
+const WIDTH: usize = 16;
+const HEIGHT: usize = 8;
+
+/// The depth buffer value for the maximum depth
+const DEPTH_CLEAR: u16 = 0xFFFC;
+
+/// Combine mode that outputs SHADE (color and alpha) in both cycles: (0 - 0) * 0 + SHADE
+const COMBINE_SHADE: CombineMode = CombineMode::DEFAULT.with_both_cycles(
+    ColorCombiner::new(CombinerSubA::Zero, CombinerSubB::Zero, CombinerMul::Zero, CombinerAdd::Shade),
+    AlphaCombiner::new(AlphaCombinerAddSub::Zero, AlphaCombinerAddSub::Zero, AlphaCombinerMul::Zero, AlphaCombinerAddSub::Shade));
+
+/// An axis aligned rectangle (drawn as a triangle) with constant depth and color. right and bottom are exclusive
+#[derive(Copy, Clone)]
+struct Primitive {
+    left: usize,
+    top: usize,
+    right: usize,
+    bottom: usize,
+    z: u15,
+    /// 0xRRGGBB00
+    color: u32,
+}
+
+impl Primitive {
+    const fn new(left: usize, top: usize, right: usize, bottom: usize, z: u16, color: u32) -> Self {
+        Self { left, top, right, bottom, z: u15::new(z), color }
+    }
+
+    /// A right-major triangle whose edges are all vertical
+    fn triangle(&self) -> TriangleBase {
+        TriangleBase::new(
+            true, 0, 0,
+            I12_2::from_i32(self.bottom as i32),
+            I12_2::from_i32(self.bottom as i32),
+            I12_2::from_i32(self.top as i32),
+            I16_16::from_i32(self.right as i32),
+            I16_16::from_i32(self.right as i32),
+            I16_16::from_i32(self.left as i32),
+            I16_16::from_i32(0),
+            I16_16::from_i32(0),
+            I16_16::from_i32(0),
+        )
+    }
+
+    fn shade(&self) -> ShadeCoefficients {
+        ShadeCoefficients::flat((self.color >> 24) as u8, (self.color >> 16) as u8, (self.color >> 8) as u8, 0xFF)
+    }
+
+    fn depth(&self) -> DepthCoefficients {
+        DepthCoefficients::flat(self.z)
+    }
+}
+
+/// Left to right: far red, near green and blue in between. Green and blue overlap each other and the red one
+const PRIMITIVES: [Primitive; 3] = [
+    Primitive::new(0, 0, 8, 6, 0x5000, 0xFF000000),
+    Primitive::new(4, 2, 11, 8, 0x0100, 0x00FF0000),
+    Primitive::new(7, 1, 16, 5, 0x2000, 0x0000FF00),
+];
+
+/// Compresses an 18 bit depth into the 14 bit depth buffer format
+fn z_compress(z: u32) -> u32 {
+    let mut exponent = 0;
+    while exponent < 7 && (z & (1 << (17 - exponent))) != 0 {
+        exponent += 1;
+    }
+    let shift = if exponent < 6 { 6 - exponent } else { 0 };
+    (exponent << 11) | ((z >> shift) & 0x7FF)
+}
+
+/// Decompresses a 14 bit depth buffer value into an 18 bit depth
+fn z_decompress(value: u32) -> u32 {
+    let exponent = value >> 11;
+    let shift = if exponent < 6 { 6 - exponent } else { 0 };
+    let leading_ones = (0x3FFFFu32 << (18 - exponent)) & 0x3FFFF;
+    leading_ones | ((value & 0x7FF) << shift)
+}
+
+/// Renders the primitives on the CPU, returning the color and depth buffer. Colors are written with full coverage
+fn render_on_cpu<const W: usize, const H: usize>(primitives: &[Primitive], z_compare: bool, z_update: bool) -> ([[u32; W]; H], [[u16; W]; H]) {
+    let mut colors = [[0u32; W]; H];
+    let mut depths = [[DEPTH_CLEAR; W]; H];
+    let scissor = RDPRectangle::new(U10_2::from_usize(0), U10_2::from_usize(0), U10_2::from_usize(W), U10_2::from_usize(H));
+    for primitive in primitives {
+        // 15.0 to 15.3
+        let z = (primitive.z.value() as u32) << 3;
+        let coverage = reference::coverage::<W, H>(&primitive.triangle(), &scissor);
+        for y in 0..H {
+            for x in 0..W {
+                if coverage[y][x] == 0 {
+                    continue;
+                }
+                let old_z = z_decompress((depths[y][x] >> 2) as u32);
+                if !z_compare || old_z == 0x3FFFF || z < old_z {
+                    colors[y][x] = primitive.color | 0xE0;
+                    if z_update {
+                        // Delta-z is 0, so the lower bits are 0
+                        depths[y][x] = (z_compress(z) << 2) as u16;
+                    }
+                }
+            }
+        }
+    }
+    (colors, depths)
+}
+
+/// Runs the commands emitted by `draw` on a cleared 32 bit framebuffer and depth buffer. Returns the framebuffer, the
+/// depth buffer and the assembler (to disassemble the command list on failure)
+fn render_on_rdp<F: FnOnce(&mut RDPAssembler), const W: usize, const H: usize>(othermode: Othermode, draw: F) -> ([[u32; W]; H], [[u16; W]; H], RDPAssembler) {
+    let mut framebuffer = UncachedHeapMemory::<u32>::new_with_init_value(W * H, 0);
+    let mut depthbuffer = UncachedHeapMemory::<u16>::new_with_init_value(W * H, DEPTH_CLEAR);
+
+    let mut assembler = RDPAssembler::new();
+    assembler.set_framebuffer_image(Format::RGBA, PixelSize::Bits32, u12::new((W - 1) as u16), &mut framebuffer);
+    assembler.set_depth_image(&mut depthbuffer);
+    assembler.set_scissor(&RDPRectangle::new(U10_2::from_usize(0), U10_2::from_usize(0), U10_2::from_usize(W), U10_2::from_usize(H)));
+    assembler.set_othermode(othermode);
+    draw(&mut assembler);
+    assembler.sync_pipe();
+    assembler.sync_full();
+
+    RDP::run_and_wait(&mut assembler);
+
+    // Copy into non-uncached arrays.
+    let mut colors = [[0u32; W]; H];
+    let mut depths = [[0u16; W]; H];
+    for y in 0..H {
+        for x in 0..W {
+            colors[y][x] = framebuffer.read(y * W + x);
+            depths[y][x] = depthbuffer.read(y * W + x);
+        }
+    }
+
+    (colors, depths, assembler)
+}
+
+/// 1 cycle mode with full coverage and no dithering. The blender passes through pixel_color as-is
+fn othermode(pixel_color: PM, z_compare: bool, z_update: bool) -> Othermode {
+    Othermode::new()
+        .with_cycle_type(CycleType::SingleCycle)
+        .with_coverage_mode(CoverageMode::Zap)
+        .with_rgb_dither_mode(RGBDitherMode::None)
+        .with_alpha_dither_mode(AlphaDitherMode::None)
+        .with_blender_0(Blender::new(A::CombineAlpha, pixel_color, B::Zero, PM::MemoryColor))
+        .with_z_mode(ZMode::Opaque)
+        .with_z_compare(z_compare)
+        .with_z_update(z_update)
+}
+
+fn compare_with_cpu(primitives: &[Primitive], z_compare: bool, z_update: bool, actual: ([[u32; WIDTH]; HEIGHT], [[u16; WIDTH]; HEIGHT], RDPAssembler)) -> Result<(), String> {
+    let expected = render_on_cpu::<WIDTH, HEIGHT>(primitives, z_compare, z_update);
+    soft_assert_eq_2d_array(actual.0, expected.0, || format!("Rendered pixels. Command list:\n{}", actual.2.disassemble()))?;
+    soft_assert_eq_2d_array(actual.1, expected.1, || format!("Depth buffer. Command list:\n{}", actual.2.disassemble()))
+}
+
+pub struct ShadedTriangle {}
+
+impl Test for ShadedTriangle {
+    fn name(&self) -> &str { "RDP ShadedTriangle (flat shade)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        // No depth test: Later triangles simply overwrite earlier ones
+        let actual = render_on_rdp::<_, WIDTH, HEIGHT>(othermode(PM::CombineColor, false, false), |assembler| {
+            assembler.set_combine_mode(COMBINE_SHADE);
+            for primitive in PRIMITIVES {
+                assembler.shaded_triangle(&primitive.triangle(), &primitive.shade());
+            }
+        });
+
+        compare_with_cpu(&PRIMITIVES, false, false, actual)
+    }
+}
+
+/// (name, z_compare, z_update)
+const DEPTH_MODES: [(&str, bool, bool); 3] = [
+    ("compare and update", true, true),
+    ("compare only", true, false),
+    ("update only", false, true),
+];
+
+/// (name, back to front)
+const DRAW_ORDERS: [(&str, bool); 2] = [
+    ("back to front", true),
+    ("front to back", false),
+];
+
+pub struct DepthTriangle {}
+
+impl Test for DepthTriangle {
+    fn name(&self) -> &str { "RDP DepthTriangle (flat depth)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        DEPTH_MODES.iter().map(|case| NamedValue::boxed(case.0, *case)).collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (_, z_compare, z_update) = *NamedValue::get::<(&'static str, bool, bool)>(value);
+        let actual = render_on_rdp::<_, WIDTH, HEIGHT>(othermode(PM::BlendColor, z_compare, z_update), |assembler| {
+            for primitive in PRIMITIVES {
+                // The RDP takes the blend color as RGBA
+                assembler.sync_pipe();
+                assembler.set_blendcolor(ARGB8888::new_with_raw_value(primitive.color | 0xFF));
+                assembler.depth_triangle(&primitive.triangle(), &primitive.depth());
+            }
+        });
+
+        compare_with_cpu(&PRIMITIVES, z_compare, z_update, actual)
+    }
+}
+
+pub struct ShadedDepthTriangle {}
+
+impl Test for ShadedDepthTriangle {
+    fn name(&self) -> &str { "RDP ShadedDepthTriangle (flat shade and depth)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        DRAW_ORDERS.iter().map(|case| NamedValue::boxed(case.0, *case)).collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (_, back_to_front) = *NamedValue::get::<(&'static str, bool)>(value);
+        let mut primitives = PRIMITIVES;
+        primitives.sort_unstable_by_key(|primitive| primitive.z.value());
+        if back_to_front {
+            primitives.reverse();
+        }
+
+        let actual = render_on_rdp::<_, WIDTH, HEIGHT>(othermode(PM::CombineColor, true, true), |assembler| {
+            assembler.set_combine_mode(COMBINE_SHADE);
+            for primitive in primitives {
+                assembler.shaded_depth_triangle(&primitive.triangle(), &primitive.shade(), &primitive.depth());
+            }
+        });
+
+        compare_with_cpu(&primitives, true, true, actual)
+    }
+}
+
+pub struct PrimitiveDepth {}
+
+impl Test for PrimitiveDepth {
+    fn name(&self) -> &str { "RDP FilledTriangle with SET_PRIM_DEPTH" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let actual = render_on_rdp::<_, WIDTH, HEIGHT>(othermode(PM::BlendColor, true, true).with_z_source_primitive(true), |assembler| {
+            for primitive in PRIMITIVES {
+                assembler.sync_pipe();
+                assembler.set_blendcolor(ARGB8888::new_with_raw_value(primitive.color | 0xFF));
+                assembler.set_primitive_depth(primitive.z, 0);
+                assembler.filled_triangle(&primitive.triangle());
+            }
+        });
+
+        compare_with_cpu(&PRIMITIVES, true, true, actual)
+    }
+}
@@ -0,0 +1,307 @@
+use alloc::boxed::Box;
+use alloc::{format, vec};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::fmt::Debug;
+use arbitrary_int::u12;
+
+use crate::graphics::color::{ARGB8888, I8, IA44, IA88, RGBA5551};
+use crate::rdp::fixedpoint::{I12_2, I16_16, U10_2};
+use crate::rdp::modes::{A, AlphaDitherMode, B, Blender, CoverageMode, CycleType, Format, Othermode, PixelSize, PM, RGBDitherMode};
+use crate::rdp::rdp::RDP;
+use crate::rdp::rdp_assembler::{RDPAssembler, RDPRectangle, TriangleBase};
+use crate::tests::{Level, NamedValue, Test};
+use crate::tests::soft_asserts::soft_assert_eq_2d_array;
+use crate::uncached_memory::UncachedHeapMemory;
+
+// Notes:
+// - Fill mode writes 64 bits per clock. The fill color is a 32 bit pattern that is repeated across those 64 bits, so
+//   which part of the fill color a pixel gets depends on its (absolute) position in memory, not on where the
+//   rectangle starts
+// - Fill mode ignores the framebuffer format: Only the pixel size matters. 4 bit framebuffers aren't supported
+// - In fill mode, the right and bottom edge of a rectangle are inclusive. The scissor is applied per pixel
+// - RDRAM has a 9th (hidden) bit per byte. The RDP uses two hidden bits per 16 bit pixel as the lower bits of the
+//   coverage. When writing 8 bit pixels, odd bytes set both hidden bits of their halfword to their lowest bit (and
+//   even bytes leave them alone). The CPU can't see hidden bits, but the RDP can when reading the same memory
+//   as a 16 bit framebuffer
+
+const WIDTH: usize = 16;
+const HEIGHT: usize = 4;
+
+/// Fill color: Every byte is different, so that a pixel that got the wrong part of it is easy to spot
+const FILL_COLOR: u32 = 0x8C35_E169;
+
+/// A framebuffer pixel as far as fill mode is concerned
+trait FillPixel: Copy + Clone + Debug + Eq {
+    const PIXEL_SIZE: PixelSize;
+
+    /// The part of the fill color that ends up in pixel x
+    fn from_fill_color(fill_color: u32, x: usize) -> Self;
+}
+
+impl FillPixel for ARGB8888 {
+    const PIXEL_SIZE: PixelSize = PixelSize::Bits32;
+
+    fn from_fill_color(fill_color: u32, _x: usize) -> Self { ARGB8888::new_with_raw_value(fill_color) }
+}
+
+impl FillPixel for RGBA5551 {
+    const PIXEL_SIZE: PixelSize = PixelSize::Bits16;
+
+    fn from_fill_color(fill_color: u32, x: usize) -> Self { RGBA5551::new_with_raw_value((fill_color >> (16 * (1 - (x & 1)))) as u16) }
+}
+
+impl FillPixel for IA88 {
+    const PIXEL_SIZE: PixelSize = PixelSize::Bits16;
+
+    fn from_fill_color(fill_color: u32, x: usize) -> Self { IA88::new_with_raw_value((fill_color >> (16 * (1 - (x & 1)))) as u16) }
+}
+
+impl FillPixel for I8 {
+    const PIXEL_SIZE: PixelSize = PixelSize::Bits8;
+
+    fn from_fill_color(fill_color: u32, x: usize) -> Self { I8::new_with_raw_value((fill_color >> (8 * (3 - (x & 3)))) as u8) }
+}
+
+impl FillPixel for IA44 {
+    const PIXEL_SIZE: PixelSize = PixelSize::Bits8;
+
+    fn from_fill_color(fill_color: u32, x: usize) -> Self { IA44::new_with_raw_value((fill_color >> (8 * (3 - (x & 3)))) as u8) }
+}
+
+/// A rectangle in whole pixels. For fill rectangles, right and bottom are inclusive. For scissors, they are exclusive
+struct Area {
+    left: usize,
+    top: usize,
+    right: usize,
+    bottom: usize,
+}
+
+impl Area {
+    const fn new(left: usize, top: usize, right: usize, bottom: usize) -> Self { Self { left, top, right, bottom } }
+
+    fn rectangle(&self) -> RDPRectangle {
+        RDPRectangle::new(U10_2::from_usize(self.left), U10_2::from_usize(self.top), U10_2::from_usize(self.right), U10_2::from_usize(self.bottom))
+    }
+}
+
+const FULL_SCISSOR: Area = Area::new(0, 0, WIDTH, HEIGHT);
+
+fn render_on_cpu<T: FillPixel>(background: T, rect: &Area, scissor: &Area) -> [[T; WIDTH]; HEIGHT] {
+    let mut result = [[background; WIDTH]; HEIGHT];
+    for y in rect.top.max(scissor.top)..(rect.bottom + 1).min(scissor.bottom).min(HEIGHT) {
+        for x in rect.left.max(scissor.left)..(rect.right + 1).min(scissor.right).min(WIDTH) {
+            result[y][x] = T::from_fill_color(FILL_COLOR, x);
+        }
+    }
+    result
+}
+
+fn render_on_rdp<T: FillPixel>(background: T, format: Format, rect: &Area, scissor: &Area) -> ([[T; WIDTH]; HEIGHT], RDPAssembler) {
+    let mut framebuffer = UncachedHeapMemory::<T>::new_with_init_value(WIDTH * HEIGHT, background);
+
+    let mut assembler = RDPAssembler::new();
+    assembler.set_framebuffer_image(format, T::PIXEL_SIZE, u12::new((WIDTH - 1) as u16), &mut framebuffer);
+    assembler.set_scissor(&scissor.rectangle());
+    assembler.set_othermode(Othermode::new()
+        .with_cycle_type(CycleType::Fill));
+    assembler.set_fillcolor32(ARGB8888::new_with_raw_value(FILL_COLOR));
+    assembler.filled_rectangle(&rect.rectangle());
+    assembler.sync_pipe();
+    assembler.sync_full();
+
+    RDP::run_and_wait(&mut assembler);
+
+    // Copy into non-uncached array.
+    let mut result = [[background; WIDTH]; HEIGHT];
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            result[y][x] = framebuffer.read(y * WIDTH + x);
+        }
+    }
+    (result, assembler)
+}
+
+fn compare_fill<T: FillPixel>(name: &str, background: T, format: Format, rect: &Area, scissor: &Area) -> Result<(), String> {
+    let (actual, assembler) = render_on_rdp(background, format, rect, scissor);
+    let expected = render_on_cpu(background, rect, scissor);
+    soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels ({}). Command list:\n{}", name, assembler.disassemble()))
+}
+
+/// Every Format/PixelSize combination that fill mode supports
+#[derive(Copy, Clone, Debug)]
+enum FillFormat {
+    RGBA32,
+    RGBA16,
+    IA16,
+    I8,
+    CI8,
+    IA8,
+}
+
+const FILL_FORMATS: [FillFormat; 6] = [FillFormat::RGBA32, FillFormat::RGBA16, FillFormat::IA16, FillFormat::I8, FillFormat::CI8, FillFormat::IA8];
+
+/// Runs a fill for the Format/PixelSize combination that is passed in as value
+fn fill_all_formats(value: &Box<dyn Any>, rect: &Area, scissor: &Area) -> Result<(), String> {
+    let fill_format = *NamedValue::get::<FillFormat>(value);
+    let name = format!("{:?}", fill_format);
+    match fill_format {
+        FillFormat::RGBA32 => compare_fill(&name, ARGB8888::new_with_raw_value(0x01020304), Format::RGBA, rect, scissor),
+        FillFormat::RGBA16 => compare_fill(&name, RGBA5551::new_with_raw_value(0x0102), Format::RGBA, rect, scissor),
+        FillFormat::IA16 => compare_fill(&name, IA88::new_with_raw_value(0x0102), Format::IA, rect, scissor),
+        FillFormat::I8 => compare_fill(&name, I8::new_with_raw_value(0x01), Format::I, rect, scissor),
+        FillFormat::CI8 => compare_fill(&name, I8::new_with_raw_value(0x01), Format::CI, rect, scissor),
+        FillFormat::IA8 => compare_fill(&name, IA44::new_with_raw_value(0x01), Format::IA, rect, scissor),
+    }
+}
+
+fn all_formats() -> Vec<Box<dyn Any>> {
+    FILL_FORMATS.iter().map(|f| NamedValue::boxed(format!("{:?}", f), *f)).collect()
+}
+
+pub struct FillRectangle {}
+
+impl Test for FillRectangle {
+    fn name(&self) -> &str { "RDP FillRectangle (all formats)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { all_formats() }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        fill_all_formats(value, &Area::new(0, 0, WIDTH - 1, HEIGHT - 1), &FULL_SCISSOR)
+    }
+}
+
+pub struct FillRectangleUnaligned {}
+
+impl Test for FillRectangleUnaligned {
+    fn name(&self) -> &str { "RDP FillRectangle (not 64 bit aligned)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { all_formats() }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        // Neither edge is at a 64 bit boundary for any of the pixel sizes
+        fill_all_formats(value, &Area::new(3, 1, 9, 2), &FULL_SCISSOR)
+    }
+}
+
+pub struct FillRectangleScissor {}
+
+impl Test for FillRectangleScissor {
+    fn name(&self) -> &str { "RDP FillRectangle (odd scissor edges)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { all_formats() }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        // The scissor cuts through the middle of 64 bit chunks
+        fill_all_formats(value, &Area::new(0, 0, WIDTH - 1, HEIGHT - 1), &Area::new(5, 1, 13, 3))
+    }
+}
+
+pub struct HiddenBits8BitWrite {}
+
+impl Test for HiddenBits8BitWrite {
+    fn name(&self) -> &str { "RDP hidden bits (8 bit fill)" }
+
+    fn level(&self) -> Level { Level::Weird }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        vec! {
+            Box::new("odd bytes"),
+            Box::new("even bytes"),
+        }
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        // 1. 16 bit fill: The hidden bits become the opposite of what the 8 bit fill is expected to write
+        // 2. 8 bit fill over the same memory. Odd bytes are expected to set the hidden bits, even bytes to clear them
+        // 3. 16 bit render with image_read and CoverageMode::Wrap, with a triangle that covers only the top
+        //    subscanline. That's 2 subsamples, so the new coverage is (memory coverage + 2) & 7. The memory coverage
+        //    is (alpha bit << 2) | hidden bits, so the resulting alpha bit tells us whether the hidden bits were written
+        let odd = *value.downcast_ref::<&'static str>().unwrap() == "odd bytes";
+        let (fill16, fill8): (u32, u32) = if odd { (0x00000000, 0x01010101) } else { (0x00010001, 0x02020202) };
+        const BLEND_COLOR: u32 = 0x8040C0FF;
+
+        let mut framebuffer = UncachedHeapMemory::<u16>::new_with_init_value(WIDTH * HEIGHT, 0);
+        let width16 = u12::new((WIDTH - 1) as u16);
+        let width8 = u12::new((WIDTH * 2 - 1) as u16);
+        let full16 = Area::new(0, 0, WIDTH - 1, HEIGHT - 1);
+        let full8 = Area::new(0, 0, WIDTH * 2 - 1, HEIGHT - 1);
+
+        // The assembler borrows the framebuffer, so use one per step. They're kept for the error message
+        let mut assemblers = Vec::new();
+        for (format, pixel_size, width, fill_color, area) in [(Format::RGBA, PixelSize::Bits16, width16, fill16, &full16), (Format::I, PixelSize::Bits8, width8, fill8, &full8)] {
+            let mut assembler = RDPAssembler::new();
+            assembler.set_framebuffer_image(format, pixel_size, width, &mut framebuffer);
+            assembler.set_scissor(&RDPRectangle::new(U10_2::from_usize(0), U10_2::from_usize(0), U10_2::from_usize(area.right + 1), U10_2::from_usize(HEIGHT)));
+            assembler.set_othermode(Othermode::new()
+                .with_cycle_type(CycleType::Fill));
+            assembler.set_fillcolor32(ARGB8888::new_with_raw_value(fill_color));
+            assembler.filled_rectangle(&area.rectangle());
+            assembler.sync_pipe();
+            assembler.sync_full();
+            RDP::run_and_wait(&mut assembler);
+            assemblers.push(assembler);
+        }
+
+        {
+            let quarter_pixel = I12_2::new_with_raw_value(1);
+            let triangle = TriangleBase::new(
+                true, 0, 0,
+                quarter_pixel,
+                quarter_pixel,
+                I12_2::from_i32(0),
+                I16_16::from_i32(WIDTH as i32),
+                I16_16::from_i32(WIDTH as i32),
+                I16_16::from_i32(0),
+                I16_16::from_i32(0),
+                I16_16::from_i32(0),
+                I16_16::from_i32(0),
+            );
+
+            // P * 1 + P * (0 + 1) / 32: With P = M, the result is exactly P
+            let mut assembler = RDPAssembler::new();
+            assembler.set_framebuffer_image(Format::RGBA, PixelSize::Bits16, width16, &mut framebuffer);
+            assembler.set_scissor(&FULL_SCISSOR.rectangle());
+            assembler.set_othermode(Othermode::new()
+                .with_cycle_type(CycleType::SingleCycle)
+                .with_rgb_dither_mode(RGBDitherMode::None)
+                .with_alpha_dither_mode(AlphaDitherMode::None)
+                .with_coverage_mode(CoverageMode::Wrap)
+                .with_antialias(true)
+                .with_image_read(true)
+                .with_force_blend(true)
+                .with_blender_0(Blender::new(A::FogAlpha, PM::BlendColor, B::Zero, PM::BlendColor)));
+            assembler.set_blendcolor(ARGB8888::new_with_raw_value(BLEND_COLOR));
+            assembler.set_fog_color(ARGB8888::new_with_raw_value(0x000000FF));
+            assembler.filled_triangle(&triangle);
+            assembler.sync_pipe();
+            assembler.sync_full();
+            RDP::run_and_wait(&mut assembler);
+            assemblers.push(assembler);
+        }
+
+        let mut actual = [[0u16; WIDTH]; HEIGHT];
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                actual[y][x] = framebuffer.read(y * WIDTH + x);
+            }
+        }
+
+        // The hidden bits equal the lowest bit, so memory coverage is either 0 or 7. Adding 2 never sets bit 2
+        let memory = fill8 as u16;
+        let hidden = if (memory & 1) != 0 { 3 } else { 0 };
+        let coverage = ((((memory & 1) << 2) | hidden) + 2) & 7;
+        let blended = RGBA5551::from_argb8888(ARGB8888::new((BLEND_COLOR >> 24) as u8, (BLEND_COLOR >> 16) as u8, (BLEND_COLOR >> 8) as u8, 0));
+        let mut expected = [[memory; WIDTH]; HEIGHT];
+        expected[0] = [(blended.raw_value() & !1) | (coverage >> 2); WIDTH];
+
+        soft_assert_eq_2d_array(actual, expected, || format!("Framebuffer after 16 bit fill, 8 bit fill ({}) and 16 bit render with coverage wrap. Command lists:\n{}", if odd { "odd bytes" } else { "even bytes" }, assembler.disassemble()))
+    }
+}
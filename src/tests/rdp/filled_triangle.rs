@@ -12,110 +12,17 @@ use crate::rdp::fixedpoint::{I12_2, I16_16, U10_2};
 use crate::rdp::modes::{A, B, Blender, CoverageMode, CycleType, Format, Othermode, PixelSize, PM};
 use crate::rdp::rdp::RDP;
 use crate::rdp::rdp_assembler::{RDPAssembler, RDPRectangle, TriangleBase};
+use crate::rdp::reference;
 use crate::tests::{Level, Test};
-use crate::tests::soft_asserts::soft_assert_eq_2d_array;
 use crate::uncached_memory::UncachedHeapMemory;
 
-fn render_on_cpu<T: Color + Copy + Clone + From<ARGB8888>, const WIDTH: usize, const HEIGHT: usize>(base: &TriangleBase, scissor: &RDPRectangle, color32: ARGB8888, coverage_mode: CoverageMode) -> [[T; WIDTH]; HEIGHT] {
-    let color: T = color32.into();
-    let mut result: [[T; WIDTH]; HEIGHT] = [[T::BLACK; WIDTH]; HEIGHT];
-    let mut subpixel_coverage: [[u8; WIDTH]; HEIGHT] = [[0; WIDTH]; HEIGHT];
-
-    let is_right_major = base.is_right_major();
-
-    let yl: i32 = base.yl().raw_value();
-    let ym: i32 = base.ym().raw_value();
-    let yh: i32 = base.yh().raw_value();
-
-    // TODO: Is 64 bit precision correct here or should it be i32 only?
-    let xl: i64 = (base.xl().raw_value() as i64) << 2;
-    let xm: i64 = (base.xm().raw_value() as i64) << 2;
-    let xh: i64 = (base.xh().raw_value() as i64) << 2;
-
-    let dl: i64 = base.dl().raw_value() as i64;
-    let dm: i64 = base.dm().raw_value() as i64;
-    let dh: i64 = base.dh().raw_value() as i64;
-
-    let mut major_x = xh;
-    let major_inc = dh;
-    let mut y = yh;
-
-    let sections = [(ym, xm, dm), (yl, xl, dl)];
-    for (y_target, mut minor_x, minor_inc) in sections {
-        while y < y_target {
-            // Top scissor is "just" pixel accurate
-            if (y >> 2) >= (((scissor.top().raw_value() as i32) + 3) >> 2) {
-                if y >= scissor.bottom().raw_value() as i32 {
-                    break;
-                }
-
-                let (left, right) = if is_right_major { (major_x, minor_x) } else { (minor_x, major_x) };
-
-                if right >= left {
-                    let subpixel_left = left >> 16;
-                    let subpixel_right = (right - (2 << 2)) >> 16;
-                    for x in subpixel_left..=subpixel_right {
-                        // Left scissor is "just" pixel accurate
-                        if (x >> 2) < (((scissor.left().raw_value() as i64) + 3) >> 2) {
-                            continue;
-                        }
-
-                        // Right scissor is subpixel accurate
-                        if x >= scissor.right().raw_value() as i64 {
-                            break;
-                        }
-                        let y_pixel = y as usize >> 2;
-                        let x_pixel = x as usize >> 2;
-                        if y_pixel < HEIGHT && x_pixel < WIDTH {
-                            subpixel_coverage[y_pixel][x_pixel] += 1;
-                        }
-                    }
-                }
-            }
-
-            let next_y = y + 1;
-            let next_major_x = major_x + major_inc;
-            let next_minor_x = minor_x + minor_inc;
-
-            minor_x = next_minor_x;
-            major_x = next_major_x;
-            y = next_y;
-        }
-    }
-
-    for y in 0..HEIGHT {
-        for x in 0..WIDTH {
-            let coverage = subpixel_coverage[y][x];
-            if coverage != 0 {
-                let alpha = match coverage_mode {
-                    CoverageMode::Clamp => match coverage {
-                        4 => 0x20,
-                        7 => 0x40,
-                        8 => 0x60,
-                        9 => 0x60,
-                        10 => 0x60,
-                        12 => 0xA0,
-                        16 => 0xE0,
-                        _ => coverage,  // placeholder until all are filled out
-                    },
-                    CoverageMode::Zap => 0xE0,
-                    CoverageMode::Wrap => 0xE0,
-                    CoverageMode::Save => subpixel_coverage[y][x],
-                };
-                result[y as usize][x as usize] = color.with_alpha(alpha);
-            }
-        }
-    }
-
-    result
-}
-
-fn render_on_rdp<T: Color + Copy + Clone, const WIDTH: usize, const HEIGHT: usize>(triangle: &TriangleBase, scissor: &RDPRectangle, color: ARGB8888, coverage_mode: CoverageMode) -> [[T; WIDTH]; HEIGHT] {
+fn render_on_rdp<T: Color + Copy + Clone, const WIDTH: usize, const HEIGHT: usize>(triangle: &TriangleBase, scissor: &RDPRectangle, color: ARGB8888, coverage_mode: CoverageMode) -> ([[T; WIDTH]; HEIGHT], RDPAssembler) {
     let mut framebuffer = UncachedHeapMemory::<T>::new_with_init_value(WIDTH * HEIGHT, T::BLACK);
 
     let mut assembler = RDPAssembler::new();
 
     let image_size = match size_of::<T>() {
+        1 => PixelSize::Bits8,
         2 => PixelSize::Bits16,
         4 => PixelSize::Bits32,
         _ => panic!("Unhandled color format"),
@@ -152,7 +59,7 @@ fn render_on_rdp<T: Color + Copy + Clone, const WIDTH: usize, const HEIGHT: usiz
         }
     }
 
-    result
+    (result, assembler)
 }
 
 pub struct FilledTriangle1CycleDegenerateRect {}
@@ -181,10 +88,10 @@ impl Test for FilledTriangle1CycleDegenerateRect {
             I16_16::from_i32(0),
         );
 
-        let actual = render_on_rdp::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
-        let expected = render_on_cpu::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
+        let (actual, assembler) = render_on_rdp::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
+        let expected = reference::render_triangle::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
 
-        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels"))?;
+        expected.compare(&actual, || format!("Rendered pixels. Command list:\n{}", assembler.disassemble()))?;
 
         Ok(())
     }
@@ -217,10 +124,10 @@ impl Test for FilledTriangle1CycleRightMajorFlatTop {
             I16_16::from_i32(0),
         );
 
-        let actual = render_on_rdp::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
-        let expected = render_on_cpu::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
+        let (actual, assembler) = render_on_rdp::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
+        let expected = reference::render_triangle::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
 
-        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels"))?;
+        expected.compare(&actual, || format!("Rendered pixels. Command list:\n{}", assembler.disassemble()))?;
 
         Ok(())
     }
@@ -254,10 +161,10 @@ impl Test for FilledTriangle1CycleRightMajor {
             I16_16::from_i32(0),
         );
 
-        let actual = render_on_rdp::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
-        let expected = render_on_cpu::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
+        let (actual, assembler) = render_on_rdp::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
+        let expected = reference::render_triangle::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
 
-        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels"))?;
+        expected.compare(&actual, || format!("Rendered pixels. Command list:\n{}", assembler.disassemble()))?;
 
         Ok(())
     }
@@ -293,10 +200,10 @@ impl Test for FilledTriangle1CycleScissorLeft {
                 I16_16::from_i32(0),
             );
 
-            let actual = render_on_rdp::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
-            let expected = render_on_cpu::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
+            let (actual, assembler) = render_on_rdp::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
+            let expected = reference::render_triangle::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
 
-            soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels with left scissor={:?}", left_scissor))?;
+            expected.compare(&actual, || format!("Rendered pixels with left scissor={:?}. Command list:\n{}", left_scissor, assembler.disassemble()))?;
         }
 
         Ok(())
@@ -332,10 +239,10 @@ impl Test for FilledTriangle1CycleScissorTop {
                 I16_16::from_i32(0),
             );
 
-            let actual = render_on_rdp::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
-            let expected = render_on_cpu::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
+            let (actual, assembler) = render_on_rdp::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
+            let expected = reference::render_triangle::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
 
-            soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels with top scissor={:?}", top_scissor))?;
+            expected.compare(&actual, || format!("Rendered pixels with top scissor={:?}. Command list:\n{}", top_scissor, assembler.disassemble()))?;
         }
 
         Ok(())
@@ -361,10 +268,10 @@ fn test_right_scissor(step_by: usize) -> Result<(), String> {
             I16_16::from_i32(0),
         );
 
-        let actual = render_on_rdp::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
-        let expected = render_on_cpu::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
+        let (actual, assembler) = render_on_rdp::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
+        let expected = reference::render_triangle::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
 
-        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels with right scissor={:?}", right_scissor))?;
+        expected.compare(&actual, || format!("Rendered pixels with right scissor={:?}. Command list:\n{}", right_scissor, assembler.disassemble()))?;
     }
     Ok(())
 }
@@ -418,10 +325,10 @@ fn test_bottom_scissor(step_by: usize) -> Result<(), String> {
             I16_16::from_i32(0),
         );
 
-        let actual = render_on_rdp::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
-        let expected = render_on_cpu::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
+        let (actual, assembler) = render_on_rdp::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
+        let expected = reference::render_triangle::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
 
-        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels with bottom scissor={:?}", bottom_scissor))?;
+        expected.compare(&actual, || format!("Rendered pixels with bottom scissor={:?}. Command list:\n{}", bottom_scissor, assembler.disassemble()))?;
     }
     Ok(())
 }
@@ -482,10 +389,10 @@ impl Test for FilledTriangle1CycleNegativeYH {
             I16_16::from_i32(0),
         );
 
-        let actual = render_on_rdp::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
-        let expected = render_on_cpu::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
+        let (actual, assembler) = render_on_rdp::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
+        let expected = reference::render_triangle::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
 
-        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels"))?;
+        expected.compare(&actual, || format!("Rendered pixels. Command list:\n{}", assembler.disassemble()))?;
 
         Ok(())
     }
@@ -517,10 +424,10 @@ impl Test for FilledTriangle1CycleNegativeXL {
             I16_16::from_i32(0),
         );
 
-        let actual = render_on_rdp::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
-        let expected = render_on_cpu::<ARGB8888, 8, 8>(&triangle, &scissor, ARGB8888::BLUE, coverage_mode);
+        let (actual, assembler) = render_on_rdp::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
+        let expected = reference::render_triangle::<ARGB8888, 8, 8>(&triangle, &scissor, ARGB8888::BLUE, coverage_mode);
 
-        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels"))?;
+        expected.compare(&actual, || format!("Rendered pixels. Command list:\n{}", assembler.disassemble()))?;
 
         Ok(())
     }
@@ -566,10 +473,10 @@ impl Test for FilledTriangle1CycleRandomized {
                 I16_16::from_i32(0),
             );
 
-            let actual = render_on_rdp::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
-            let expected = render_on_cpu::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
+            let (actual, assembler) = render_on_rdp::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
+            let expected = reference::render_triangle::<ARGB8888, 8, 8>(&triangle, &scissor, color, coverage_mode);
 
-            soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels for {:?}", triangle))?;
+            expected.compare(&actual, || format!("Rendered pixels for {:?}. Command list:\n{}", triangle, assembler.disassemble()))?;
         }
 
         Ok(())
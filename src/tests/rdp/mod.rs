@@ -6,6 +6,7 @@ use core::any::Any;
 use arbitrary_int::u12;
 
 use crate::graphics::color::{Color, RGBA5551};
+use crate::rdp::disassembler;
 use crate::rdp::fixedpoint::U10_2;
 use crate::rdp::modes::{CycleType, Format, Othermode, PixelSize};
 use crate::rdp::rdp::{DP_SET_STATUS_CLEAR_FREEZE, DP_SET_STATUS_CLEAR_XBUS, DP_SET_STATUS_SET_FREEZE, DP_SET_STATUS_SET_XBUS, DP_STATUS_COMMAND_BUFFER_READY, DP_STATUS_END_VALID, DP_STATUS_FREEZE, DP_STATUS_PIPE_BUSY, DP_STATUS_START_GCLK, DP_STATUS_START_VALID, DP_STATUS_XBUS, RDP};
@@ -15,13 +16,18 @@ use crate::tests::{Level, Test};
 use crate::tests::soft_asserts::{soft_assert_eq, soft_assert_eq2};
 use crate::uncached_memory::UncachedHeapMemory;
 
+pub mod blender;
+pub mod combiner;
+pub mod depth;
+pub mod fill;
 pub mod filled_triangle;
+pub mod texture_rect;
+pub mod xbus;
 
 // TODO:
 //  - Make a test that uses FREEZE. It should not execute the RDP list until the RDP is unfrozen
 //  - For freeze test perfection: CURRENT should advance up to START+240 as the commands are
 //    dma'ed even if the RDP is frozen
-//  - It is possible to DMA two command lists to the RDP. Do a test for double-buffering
 //  - Similar to RSP side, it is possible to set and clear bits at the same time. Write a test to see what happens
 
 fn wait_for_status(goal: u32) -> Result<(), String> {
@@ -240,7 +246,7 @@ fn run_from_dmem_test<F: FnOnce(u32) -> (u32, u32)>(get_dmem_range: F) -> Result
 
     soft_assert_eq(RDP::current(), dmem_end, "RDP current should be equal to END after writing END (and waiting for the RDP to finish)")?;
 
-    soft_assert_eq(framebuffer.read(0), RGBA5551::GREEN, "Auxiliary framebuffer should be filled with GREEN")?;
+    soft_assert_eq2(framebuffer.read(0), RGBA5551::GREEN, || format!("Auxiliary framebuffer should be filled with GREEN. Command list in DMEM:\n{}", disassembler::disassemble_dmem(dmem_start as usize, (length >> 3) as usize)))?;
 
     Ok(())
 }
@@ -0,0 +1,597 @@
+use alloc::boxed::Box;
+use alloc::{format, vec};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+use arbitrary_int::{u10, u12, u3, u4, u9};
+
+use crate::graphics::color::{I8, IA44, IA88};
+use crate::rdp::fixedpoint::{I11_5, I6_10, U10_2};
+use crate::rdp::modes::{AlphaCombiner, AlphaCombinerAddSub, AlphaCombinerMul, AlphaDitherMode, ColorCombiner, CombineMode, CombinerAdd, CombinerMul, CombinerSubA, CombinerSubB, CoverageMode, CycleType, Format, Othermode, PixelSize, RGBDitherMode, TileDescriptor};
+use crate::rdp::rdp::RDP;
+use crate::rdp::rdp_assembler::{RDPAssembler, RDPRectangle, TextureCoordinates};
+use crate::tests::{Level, NamedValue, Test};
+use crate::tests::soft_asserts::soft_assert_eq_2d_array;
+use crate::uncached_memory::UncachedHeapMemory;
+
+// Notes:
+// - In copy mode, the rectangle includes the right and bottom edge. Four texels are copied per clock, so dsdx is 4.0
+//   for a 1:1 copy. Texels are written as-is (including the alpha bit), palette lookups still happen
+// - In 1-cycle mode, the rectangle excludes the right and bottom edge. Texels go through the combiner and blender
+// - All tests use point sampling without perspective correction, so every pixel maps to exactly one texel
+
+const WIDTH: usize = 16;
+const HEIGHT: usize = 8;
+
+/// All textures are this many texels wide and high
+const TEXTURE_SIZE: usize = 8;
+
+const LOAD_TILE: u3 = u3::new(7);
+const RENDER_TILE: u3 = u3::new(0);
+
+/// TLUTs live in the upper half of TMEM. Address in 64 bit words
+const TLUT_ADDRESS: u9 = u9::new(0x100);
+
+/// Combine mode that outputs TEXEL0 (color and alpha) in both cycles: (0 - 0) * 0 + TEXEL0
+const COMBINE_TEXEL0: CombineMode = CombineMode::DEFAULT.with_both_cycles(
+    ColorCombiner::new(CombinerSubA::Zero, CombinerSubB::Zero, CombinerMul::Zero, CombinerAdd::Texel0),
+    AlphaCombiner::new(AlphaCombinerAddSub::Zero, AlphaCombinerAddSub::Zero, AlphaCombinerMul::Zero, AlphaCombinerAddSub::Texel0));
+
+/// Palette index used for CI4 textures
+const CI4_PALETTE: u4 = u4::new(5);
+
+/// How the texture gets into TMEM
+#[derive(Clone, Copy, Debug)]
+enum TextureLoad {
+    LoadTile,
+    LoadBlock,
+}
+
+/// RGBA 5551 texel. Red follows s, green follows t so that a misplaced texel is easy to spot
+fn rgba16_texel(s: i32, t: i32) -> u16 {
+    let r = (s * 4) as u16;
+    let g = (t * 4) as u16;
+    let b = (31 - s - t) as u16;
+    let a = ((s ^ t) & 1) as u16;
+    (r << 11) | (g << 6) | (b << 1) | a
+}
+
+fn palette_entry(index: usize) -> u16 {
+    (index as u16).wrapping_mul(0x0101) ^ 0xA5C3
+}
+
+fn ci8_texel(s: i32, t: i32) -> u8 {
+    ((t * TEXTURE_SIZE as i32 + s) * 37) as u8
+}
+
+fn ci4_texel(s: i32, t: i32) -> u8 {
+    ((s + 3 * t) & 0xF) as u8
+}
+
+fn i8_texel(s: i32, t: i32) -> I8 {
+    I8::new(((t * TEXTURE_SIZE as i32 + s) * 29 + 7) as u8)
+}
+
+/// Intensity follows s and t, alpha goes the other way
+fn ia8_texel(s: i32, t: i32) -> IA44 {
+    IA44::new(u4::new((s + t) as u8), u4::new((15 - s) as u8))
+}
+
+/// Intensity follows s and t, alpha goes the other way
+fn ia16_texel(s: i32, t: i32) -> IA88 {
+    IA88::new((s * 32 + t) as u8, (255 - t * 16 - s) as u8)
+}
+
+/// A 16 bit texel as it ends up in a 32 bit framebuffer in 1-cycle mode. Coverage is full and the coverage mode
+/// is Zap, so the alpha bits are 7 << 5
+fn expand_rgba16(texel: u16) -> u32 {
+    let widen = |value5: u16| ((value5 << 3) | (value5 >> 2)) as u32 & 0xFF;
+    (widen((texel >> 11) & 0x1F) << 24) | (widen((texel >> 6) & 0x1F) << 16) | (widen((texel >> 1) & 0x1F) << 8) | 0xE0
+}
+
+fn rgba16_texture() -> UncachedHeapMemory<u16> {
+    let mut texture = UncachedHeapMemory::<u16>::new(TEXTURE_SIZE * TEXTURE_SIZE);
+    for t in 0..TEXTURE_SIZE {
+        for s in 0..TEXTURE_SIZE {
+            texture.write(t * TEXTURE_SIZE + s, rgba16_texel(s as i32, t as i32));
+        }
+    }
+    texture
+}
+
+fn ci8_texture() -> UncachedHeapMemory<u8> {
+    let mut texture = UncachedHeapMemory::<u8>::new(TEXTURE_SIZE * TEXTURE_SIZE);
+    for t in 0..TEXTURE_SIZE {
+        for s in 0..TEXTURE_SIZE {
+            texture.write(t * TEXTURE_SIZE + s, ci8_texel(s as i32, t as i32));
+        }
+    }
+    texture
+}
+
+fn ci4_texture() -> UncachedHeapMemory<u8> {
+    let mut texture = UncachedHeapMemory::<u8>::new(TEXTURE_SIZE * TEXTURE_SIZE / 2);
+    for t in 0..TEXTURE_SIZE {
+        for s in (0..TEXTURE_SIZE).step_by(2) {
+            let value = (ci4_texel(s as i32, t as i32) << 4) | ci4_texel(s as i32 + 1, t as i32);
+            texture.write((t * TEXTURE_SIZE + s) / 2, value);
+        }
+    }
+    texture
+}
+
+fn i8_texture() -> UncachedHeapMemory<I8> {
+    let mut texture = UncachedHeapMemory::<I8>::new(TEXTURE_SIZE * TEXTURE_SIZE);
+    for t in 0..TEXTURE_SIZE {
+        for s in 0..TEXTURE_SIZE {
+            texture.write(t * TEXTURE_SIZE + s, i8_texel(s as i32, t as i32));
+        }
+    }
+    texture
+}
+
+fn ia8_texture() -> UncachedHeapMemory<IA44> {
+    let mut texture = UncachedHeapMemory::<IA44>::new(TEXTURE_SIZE * TEXTURE_SIZE);
+    for t in 0..TEXTURE_SIZE {
+        for s in 0..TEXTURE_SIZE {
+            texture.write(t * TEXTURE_SIZE + s, ia8_texel(s as i32, t as i32));
+        }
+    }
+    texture
+}
+
+fn ia16_texture() -> UncachedHeapMemory<IA88> {
+    let mut texture = UncachedHeapMemory::<IA88>::new(TEXTURE_SIZE * TEXTURE_SIZE);
+    for t in 0..TEXTURE_SIZE {
+        for s in 0..TEXTURE_SIZE {
+            texture.write(t * TEXTURE_SIZE + s, ia16_texel(s as i32, t as i32));
+        }
+    }
+    texture
+}
+
+fn palette() -> UncachedHeapMemory<u16> {
+    let mut palette = UncachedHeapMemory::<u16>::new(256);
+    for i in 0..256 {
+        palette.write(i, palette_entry(i));
+    }
+    palette
+}
+
+/// Size of a texture line in TMEM, in 64 bit words
+fn line_words(width: usize, pixel_size: PixelSize) -> usize {
+    let bits = match pixel_size {
+        PixelSize::Bits4 => 4,
+        PixelSize::Bits8 => 8,
+        PixelSize::Bits16 => 16,
+        PixelSize::Bits32 => 32,
+    };
+    (width * bits / 8 + 7) / 8
+}
+
+fn texel_rectangle(width: usize, height: usize) -> RDPRectangle {
+    RDPRectangle::new(U10_2::from_usize(0), U10_2::from_usize(0), U10_2::from_usize(width - 1), U10_2::from_usize(height - 1))
+}
+
+/// Loads a texture (width x height texels) into TMEM address 0 using LOAD_TILE
+fn load_with_load_tile<T: Copy + Clone>(assembler: &mut RDPAssembler, format: Format, pixel_size: PixelSize, width: usize, height: usize, texture: &mut UncachedHeapMemory<T>) {
+    assembler.set_texture_image(format, pixel_size, u10::new((width - 1) as u16), texture);
+    assembler.set_tile(TileDescriptor::new()
+        .with_format(format)
+        .with_pixel_size(pixel_size)
+        .with_line(u9::new(line_words(width, pixel_size) as u16))
+        .with_tile(LOAD_TILE));
+    assembler.sync_load();
+    assembler.load_tile(LOAD_TILE, &texel_rectangle(width, height));
+    assembler.sync_pipe();
+}
+
+/// Loads a texture (width x height texels) into TMEM address 0 using LOAD_BLOCK
+fn load_with_load_block<T: Copy + Clone>(assembler: &mut RDPAssembler, format: Format, pixel_size: PixelSize, width: usize, height: usize, texture: &mut UncachedHeapMemory<T>) {
+    let line = line_words(width, pixel_size);
+    assembler.set_texture_image(format, pixel_size, u10::new((width - 1) as u16), texture);
+    assembler.set_tile(TileDescriptor::new()
+        .with_format(format)
+        .with_pixel_size(pixel_size)
+        .with_tile(LOAD_TILE));
+    assembler.sync_load();
+    // dxt is 1.11: The reciprocal of the number of words per line, rounded up
+    let dxt = (2048 + line - 1) / line;
+    assembler.load_block(LOAD_TILE, u12::new(0), u12::new(0), u12::new((width * height - 1) as u16), u12::new(dxt as u16));
+    assembler.sync_pipe();
+}
+
+/// Loads a palette with 256 RGBA16 entries into the upper half of TMEM
+fn load_palette(assembler: &mut RDPAssembler, palette: &mut UncachedHeapMemory<u16>) {
+    assembler.set_texture_image(Format::RGBA, PixelSize::Bits16, u10::new(255), palette);
+    assembler.sync_tile();
+    assembler.set_tile(TileDescriptor::new()
+        .with_format(Format::RGBA)
+        .with_pixel_size(PixelSize::Bits16)
+        .with_tmem_address(TLUT_ADDRESS)
+        .with_tile(LOAD_TILE));
+    assembler.sync_load();
+    assembler.load_tlut(LOAD_TILE, &RDPRectangle::new(U10_2::from_usize(0), U10_2::from_usize(0), U10_2::from_usize(255), U10_2::from_usize(0)));
+    assembler.sync_pipe();
+}
+
+/// Sets up RENDER_TILE to sample a TEXTURE_SIZE x TEXTURE_SIZE texture at TMEM address 0
+fn set_render_tile(assembler: &mut RDPAssembler, format: Format, pixel_size: PixelSize, palette: u4) {
+    assembler.set_tile(TileDescriptor::new()
+        .with_format(format)
+        .with_pixel_size(pixel_size)
+        .with_line(u9::new(line_words(TEXTURE_SIZE, pixel_size) as u16))
+        .with_palette(palette)
+        .with_tile(RENDER_TILE));
+    assembler.set_tile_size(RENDER_TILE, &texel_rectangle(TEXTURE_SIZE, TEXTURE_SIZE));
+}
+
+fn copy_othermode() -> Othermode {
+    Othermode::new()
+        .with_cycle_type(CycleType::Copy)
+}
+
+fn one_cycle_othermode() -> Othermode {
+    Othermode::new()
+        .with_cycle_type(CycleType::SingleCycle)
+        .with_bi_lerp_0(true)
+        .with_bi_lerp_1(true)
+        .with_rgb_dither_mode(RGBDitherMode::None)
+        .with_alpha_dither_mode(AlphaDitherMode::None)
+        .with_coverage_mode(CoverageMode::Zap)
+}
+
+/// Renders a texture rectangle on the CPU. `texel` returns the resulting pixel for a given (integer) s and t
+fn render_on_cpu<T: Copy, F: Fn(i32, i32) -> T, const W: usize, const H: usize>(background: T, rect: &RDPRectangle, coordinates: &TextureCoordinates, cycle_type: CycleType, flip: bool, texel: F) -> [[T; W]; H] {
+    let mut result = [[background; W]; H];
+
+    // Copy mode includes the right and bottom edge and handles four texels per clock
+    let (inclusive, dsdx_divider) = match cycle_type {
+        CycleType::Copy => (1, 4),
+        _ => (0, 1),
+    };
+    let left = (rect.left().raw_value() >> 2) as usize;
+    let top = (rect.top().raw_value() >> 2) as usize;
+    let right = (rect.right().raw_value() >> 2) as usize + inclusive;
+    let bottom = (rect.bottom().raw_value() >> 2) as usize + inclusive;
+
+    for y in top..bottom.min(H) {
+        for x in left..right.min(W) {
+            let (along_s, along_t) = if flip { (y - top, x - left) } else { (x - left, y - top) };
+            // s and t are 10.5, dsdx and dtdy are 5.10
+            let s = ((coordinates.s().raw_value() << 5) + (along_s as i32) * coordinates.dsdx().raw_value() / dsdx_divider) >> 10;
+            let t = ((coordinates.t().raw_value() << 5) + (along_t as i32) * coordinates.dtdy().raw_value()) >> 10;
+            result[y][x] = texel(s, t);
+        }
+    }
+
+    result
+}
+
+/// Runs the commands emitted by `draw` on a framebuffer that starts out as `background` and returns the framebuffer
+/// together with the assembler (to disassemble the command list on failure)
+fn render_on_rdp<T: Copy + Clone, F: FnOnce(&mut RDPAssembler), const W: usize, const H: usize>(background: T, pixel_size: PixelSize, draw: F) -> ([[T; W]; H], RDPAssembler) {
+    let mut framebuffer = UncachedHeapMemory::<T>::new_with_init_value(W * H, background);
+
+    let mut assembler = RDPAssembler::new();
+    assembler.set_framebuffer_image(Format::RGBA, pixel_size, u12::new((W - 1) as u16), &mut framebuffer);
+    assembler.set_scissor(&RDPRectangle::new(U10_2::from_usize(0), U10_2::from_usize(0), U10_2::from_usize(W), U10_2::from_usize(H)));
+    draw(&mut assembler);
+    assembler.sync_pipe();
+    assembler.sync_full();
+
+    RDP::run_and_wait(&mut assembler);
+
+    // Copy into non-uncached array.
+    let mut result: [[T; W]; H] = [[background; W]; H];
+    for y in 0..H {
+        for x in 0..W {
+            result[y][x] = framebuffer.read(y * W + x);
+        }
+    }
+
+    (result, assembler)
+}
+
+fn one_to_one_copy() -> TextureCoordinates {
+    TextureCoordinates::new(I11_5::from_i32(0), I11_5::from_i32(0), I6_10::from_i32(4), I6_10::from_i32(1))
+}
+
+pub struct TextureRectangleCopyRGBA16 {}
+
+impl Test for TextureRectangleCopyRGBA16 {
+    fn name(&self) -> &str { "RDP TextureRectangle Copy (RGBA16)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        vec! {
+            NamedValue::boxed("LOAD_TILE", TextureLoad::LoadTile),
+            NamedValue::boxed("LOAD_BLOCK", TextureLoad::LoadBlock),
+        }
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let load = *NamedValue::get::<TextureLoad>(value);
+        let mut texture = rgba16_texture();
+        let rect = RDPRectangle::new(U10_2::from_usize(4), U10_2::from_usize(0), U10_2::from_usize(4 + TEXTURE_SIZE - 1), U10_2::from_usize(TEXTURE_SIZE - 1));
+        let coordinates = one_to_one_copy();
+
+        let (actual, assembler) = render_on_rdp::<u16, _, WIDTH, HEIGHT>(0, PixelSize::Bits16, |assembler| {
+            match load {
+                TextureLoad::LoadTile => load_with_load_tile(assembler, Format::RGBA, PixelSize::Bits16, TEXTURE_SIZE, TEXTURE_SIZE, &mut texture),
+                TextureLoad::LoadBlock => load_with_load_block(assembler, Format::RGBA, PixelSize::Bits16, TEXTURE_SIZE, TEXTURE_SIZE, &mut texture),
+            }
+            set_render_tile(assembler, Format::RGBA, PixelSize::Bits16, u4::new(0));
+            assembler.set_othermode(copy_othermode());
+            assembler.texture_rectangle(&rect, RENDER_TILE, &coordinates);
+        });
+        let expected = render_on_cpu::<u16, _, WIDTH, HEIGHT>(0, &rect, &coordinates, CycleType::Copy, false, rgba16_texel);
+
+        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels ({:?}). Command list:\n{}", load, assembler.disassemble()))
+    }
+}
+
+pub struct TextureRectangleCopyOffset {}
+
+impl Test for TextureRectangleCopyOffset {
+    fn name(&self) -> &str { "RDP TextureRectangle Copy (s/t offset)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let mut texture = rgba16_texture();
+        // Draw the lower right 4x4 texels of the texture
+        let rect = RDPRectangle::new(U10_2::from_usize(1), U10_2::from_usize(2), U10_2::from_usize(4), U10_2::from_usize(5));
+        let coordinates = TextureCoordinates::new(I11_5::from_i32(4), I11_5::from_i32(4), I6_10::from_i32(4), I6_10::from_i32(1));
+
+        let (actual, assembler) = render_on_rdp::<u16, _, WIDTH, HEIGHT>(0, PixelSize::Bits16, |assembler| {
+            load_with_load_tile(assembler, Format::RGBA, PixelSize::Bits16, TEXTURE_SIZE, TEXTURE_SIZE, &mut texture);
+            set_render_tile(assembler, Format::RGBA, PixelSize::Bits16, u4::new(0));
+            assembler.set_othermode(copy_othermode());
+            assembler.texture_rectangle(&rect, RENDER_TILE, &coordinates);
+        });
+        let expected = render_on_cpu::<u16, _, WIDTH, HEIGHT>(0, &rect, &coordinates, CycleType::Copy, false, rgba16_texel);
+
+        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels. Command list:\n{}", assembler.disassemble()))
+    }
+}
+
+pub struct TextureRectangleCopyCI8 {}
+
+impl Test for TextureRectangleCopyCI8 {
+    fn name(&self) -> &str { "RDP TextureRectangle Copy (CI8 with TLUT)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let mut texture = ci8_texture();
+        let mut palette = palette();
+        let rect = RDPRectangle::new(U10_2::from_usize(0), U10_2::from_usize(0), U10_2::from_usize(TEXTURE_SIZE - 1), U10_2::from_usize(TEXTURE_SIZE - 1));
+        let coordinates = one_to_one_copy();
+
+        let (actual, assembler) = render_on_rdp::<u16, _, WIDTH, HEIGHT>(0, PixelSize::Bits16, |assembler| {
+            load_palette(assembler, &mut palette);
+            load_with_load_tile(assembler, Format::CI, PixelSize::Bits8, TEXTURE_SIZE, TEXTURE_SIZE, &mut texture);
+            set_render_tile(assembler, Format::CI, PixelSize::Bits8, u4::new(0));
+            assembler.set_othermode(copy_othermode()
+                .with_tlut_enable(true));
+            assembler.texture_rectangle(&rect, RENDER_TILE, &coordinates);
+        });
+        let expected = render_on_cpu::<u16, _, WIDTH, HEIGHT>(0, &rect, &coordinates, CycleType::Copy, false, |s, t| palette_entry(ci8_texel(s, t) as usize));
+
+        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels. Command list:\n{}", assembler.disassemble()))
+    }
+}
+
+pub struct TextureRectangleCopyI8 {}
+
+impl Test for TextureRectangleCopyI8 {
+    fn name(&self) -> &str { "RDP TextureRectangle Copy (I8)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let mut texture = i8_texture();
+        // An odd left edge, so that the 8 texels don't line up with the 64 bit words of the framebuffer
+        let rect = RDPRectangle::new(U10_2::from_usize(3), U10_2::from_usize(0), U10_2::from_usize(3 + TEXTURE_SIZE - 1), U10_2::from_usize(TEXTURE_SIZE - 1));
+        let coordinates = one_to_one_copy();
+
+        let (actual, assembler) = render_on_rdp::<I8, _, WIDTH, HEIGHT>(I8::new(0), PixelSize::Bits8, |assembler| {
+            load_with_load_tile(assembler, Format::I, PixelSize::Bits8, TEXTURE_SIZE, TEXTURE_SIZE, &mut texture);
+            set_render_tile(assembler, Format::I, PixelSize::Bits8, u4::new(0));
+            assembler.set_othermode(copy_othermode());
+            assembler.texture_rectangle(&rect, RENDER_TILE, &coordinates);
+        });
+        let expected = render_on_cpu::<I8, _, WIDTH, HEIGHT>(I8::new(0), &rect, &coordinates, CycleType::Copy, false, i8_texel);
+
+        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels. Command list:\n{}", assembler.disassemble()))
+    }
+}
+
+pub struct TextureRectangleCopyIA8 {}
+
+impl Test for TextureRectangleCopyIA8 {
+    fn name(&self) -> &str { "RDP TextureRectangle Copy (IA8)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let mut texture = ia8_texture();
+        let rect = RDPRectangle::new(U10_2::from_usize(5), U10_2::from_usize(0), U10_2::from_usize(5 + TEXTURE_SIZE - 1), U10_2::from_usize(TEXTURE_SIZE - 1));
+        let coordinates = one_to_one_copy();
+
+        let (actual, assembler) = render_on_rdp::<IA44, _, WIDTH, HEIGHT>(IA44::new_with_raw_value(0), PixelSize::Bits8, |assembler| {
+            load_with_load_tile(assembler, Format::IA, PixelSize::Bits8, TEXTURE_SIZE, TEXTURE_SIZE, &mut texture);
+            set_render_tile(assembler, Format::IA, PixelSize::Bits8, u4::new(0));
+            assembler.set_othermode(copy_othermode());
+            assembler.texture_rectangle(&rect, RENDER_TILE, &coordinates);
+        });
+        let expected = render_on_cpu::<IA44, _, WIDTH, HEIGHT>(IA44::new_with_raw_value(0), &rect, &coordinates, CycleType::Copy, false, ia8_texel);
+
+        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels. Command list:\n{}", assembler.disassemble()))
+    }
+}
+
+pub struct TextureRectangleCopyCI8WithoutTLUT {}
+
+impl Test for TextureRectangleCopyCI8WithoutTLUT {
+    fn name(&self) -> &str { "RDP TextureRectangle Copy (CI8 without TLUT)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        // Without TLUT, the color indices are copied as-is into an 8 bit framebuffer
+        let mut texture = ci8_texture();
+        let rect = RDPRectangle::new(U10_2::from_usize(2), U10_2::from_usize(0), U10_2::from_usize(2 + TEXTURE_SIZE - 1), U10_2::from_usize(TEXTURE_SIZE - 1));
+        let coordinates = one_to_one_copy();
+
+        let (actual, assembler) = render_on_rdp::<u8, _, WIDTH, HEIGHT>(0, PixelSize::Bits8, |assembler| {
+            load_with_load_tile(assembler, Format::CI, PixelSize::Bits8, TEXTURE_SIZE, TEXTURE_SIZE, &mut texture);
+            set_render_tile(assembler, Format::CI, PixelSize::Bits8, u4::new(0));
+            assembler.set_othermode(copy_othermode());
+            assembler.texture_rectangle(&rect, RENDER_TILE, &coordinates);
+        });
+        let expected = render_on_cpu::<u8, _, WIDTH, HEIGHT>(0, &rect, &coordinates, CycleType::Copy, false, ci8_texel);
+
+        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels. Command list:\n{}", assembler.disassemble()))
+    }
+}
+
+pub struct TextureRectangleCopyIA16 {}
+
+impl Test for TextureRectangleCopyIA16 {
+    fn name(&self) -> &str { "RDP TextureRectangle Copy (IA16)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let mut texture = ia16_texture();
+        let rect = RDPRectangle::new(U10_2::from_usize(1), U10_2::from_usize(0), U10_2::from_usize(1 + TEXTURE_SIZE - 1), U10_2::from_usize(TEXTURE_SIZE - 1));
+        let coordinates = one_to_one_copy();
+
+        let (actual, assembler) = render_on_rdp::<IA88, _, WIDTH, HEIGHT>(IA88::new(0, 0), PixelSize::Bits16, |assembler| {
+            load_with_load_tile(assembler, Format::IA, PixelSize::Bits16, TEXTURE_SIZE, TEXTURE_SIZE, &mut texture);
+            set_render_tile(assembler, Format::IA, PixelSize::Bits16, u4::new(0));
+            assembler.set_othermode(copy_othermode());
+            assembler.texture_rectangle(&rect, RENDER_TILE, &coordinates);
+        });
+        let expected = render_on_cpu::<IA88, _, WIDTH, HEIGHT>(IA88::new(0, 0), &rect, &coordinates, CycleType::Copy, false, ia16_texel);
+
+        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels. Command list:\n{}", assembler.disassemble()))
+    }
+}
+
+pub struct TextureRectangle1Cycle {}
+
+impl Test for TextureRectangle1Cycle {
+    fn name(&self) -> &str { "RDP TextureRectangle 1 Cycle (RGBA16)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        let case = |name: &'static str, rect: RDPRectangle, coordinates: TextureCoordinates| NamedValue::boxed(name, (name, rect, coordinates));
+        vec! {
+            case("1:1",
+                RDPRectangle::new(U10_2::from_usize(0), U10_2::from_usize(0), U10_2::from_usize(TEXTURE_SIZE), U10_2::from_usize(TEXTURE_SIZE)),
+                TextureCoordinates::new(I11_5::from_i32(0), I11_5::from_i32(0), I6_10::from_i32(1), I6_10::from_i32(1))),
+            case("2x horizontally",
+                RDPRectangle::new(U10_2::from_usize(0), U10_2::from_usize(0), U10_2::from_usize(2 * TEXTURE_SIZE), U10_2::from_usize(TEXTURE_SIZE)),
+                TextureCoordinates::new(I11_5::from_i32(0), I11_5::from_i32(0), I6_10::new_with_raw_value(1 << 9), I6_10::from_i32(1))),
+            case("every other texel",
+                RDPRectangle::new(U10_2::from_usize(2), U10_2::from_usize(2), U10_2::from_usize(6), U10_2::from_usize(6)),
+                TextureCoordinates::new(I11_5::from_i32(0), I11_5::from_i32(0), I6_10::from_i32(2), I6_10::from_i32(2))),
+            case("offset",
+                RDPRectangle::new(U10_2::from_usize(3), U10_2::from_usize(1), U10_2::from_usize(8), U10_2::from_usize(5)),
+                TextureCoordinates::new(I11_5::from_i32(2), I11_5::from_i32(3), I6_10::from_i32(1), I6_10::from_i32(1))),
+        }
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (case, rect, coordinates) = NamedValue::get::<(&'static str, RDPRectangle, TextureCoordinates)>(value);
+        let mut texture = rgba16_texture();
+
+        let (actual, assembler) = render_on_rdp::<u32, _, WIDTH, HEIGHT>(0, PixelSize::Bits32, |assembler| {
+            load_with_load_tile(assembler, Format::RGBA, PixelSize::Bits16, TEXTURE_SIZE, TEXTURE_SIZE, &mut texture);
+            set_render_tile(assembler, Format::RGBA, PixelSize::Bits16, u4::new(0));
+            assembler.set_othermode(one_cycle_othermode());
+            assembler.set_combine_mode(COMBINE_TEXEL0);
+            assembler.texture_rectangle(rect, RENDER_TILE, coordinates);
+        });
+        let expected = render_on_cpu::<u32, _, WIDTH, HEIGHT>(0, rect, coordinates, CycleType::SingleCycle, false, |s, t| expand_rgba16(rgba16_texel(s, t)));
+
+        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels ({}). Command list:\n{}", case, assembler.disassemble()))
+    }
+}
+
+pub struct TextureRectangleFlipped1Cycle {}
+
+impl Test for TextureRectangleFlipped1Cycle {
+    fn name(&self) -> &str { "RDP TextureRectangleFlip 1 Cycle (RGBA16)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let rect = RDPRectangle::new(U10_2::from_usize(4), U10_2::from_usize(0), U10_2::from_usize(4 + TEXTURE_SIZE), U10_2::from_usize(TEXTURE_SIZE));
+        let coordinates = TextureCoordinates::new(I11_5::from_i32(0), I11_5::from_i32(0), I6_10::from_i32(1), I6_10::from_i32(1));
+        let mut texture = rgba16_texture();
+
+        let (actual, assembler) = render_on_rdp::<u32, _, WIDTH, HEIGHT>(0, PixelSize::Bits32, |assembler| {
+            load_with_load_tile(assembler, Format::RGBA, PixelSize::Bits16, TEXTURE_SIZE, TEXTURE_SIZE, &mut texture);
+            set_render_tile(assembler, Format::RGBA, PixelSize::Bits16, u4::new(0));
+            assembler.set_othermode(one_cycle_othermode());
+            assembler.set_combine_mode(COMBINE_TEXEL0);
+            assembler.texture_rectangle_flipped(&rect, RENDER_TILE, &coordinates);
+        });
+        let expected = render_on_cpu::<u32, _, WIDTH, HEIGHT>(0, &rect, &coordinates, CycleType::SingleCycle, true, |s, t| expand_rgba16(rgba16_texel(s, t)));
+
+        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels. Command list:\n{}", assembler.disassemble()))
+    }
+}
+
+pub struct TextureRectangle1CycleCI4 {}
+
+impl Test for TextureRectangle1CycleCI4 {
+    fn name(&self) -> &str { "RDP TextureRectangle 1 Cycle (CI4 with TLUT)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let rect = RDPRectangle::new(U10_2::from_usize(0), U10_2::from_usize(0), U10_2::from_usize(TEXTURE_SIZE), U10_2::from_usize(TEXTURE_SIZE));
+        let coordinates = TextureCoordinates::new(I11_5::from_i32(0), I11_5::from_i32(0), I6_10::from_i32(1), I6_10::from_i32(1));
+        let mut texture = ci4_texture();
+        let mut palette = palette();
+
+        let (actual, assembler) = render_on_rdp::<u32, _, WIDTH, HEIGHT>(0, PixelSize::Bits32, |assembler| {
+            load_palette(assembler, &mut palette);
+            // 4 bit textures can't be loaded with LOAD_TILE directly. Load them as 8 bit with half the width instead
+            load_with_load_tile(assembler, Format::CI, PixelSize::Bits8, TEXTURE_SIZE / 2, TEXTURE_SIZE, &mut texture);
+            set_render_tile(assembler, Format::CI, PixelSize::Bits4, CI4_PALETTE);
+            assembler.set_othermode(one_cycle_othermode()
+                .with_tlut_enable(true));
+            assembler.set_combine_mode(COMBINE_TEXEL0);
+            assembler.texture_rectangle(&rect, RENDER_TILE, &coordinates);
+        });
+        let expected = render_on_cpu::<u32, _, WIDTH, HEIGHT>(0, &rect, &coordinates, CycleType::SingleCycle, false, |s, t| {
+            expand_rgba16(palette_entry(((CI4_PALETTE.value() as usize) << 4) | ci4_texel(s, t) as usize))
+        });
+
+        soft_assert_eq_2d_array(actual, expected, || format!("Rendered pixels. Command list:\n{}", assembler.disassemble()))
+    }
+}
@@ -0,0 +1,198 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+use arbitrary_int::u12;
+
+use crate::graphics::color::{Color, RGBA5551};
+use crate::rdp::disassembler;
+use crate::rdp::fixedpoint::U10_2;
+use crate::rdp::modes::{CycleType, Format, Othermode, PixelSize};
+use crate::rdp::rdp::{DP_SET_STATUS_CLEAR_XBUS, DP_SET_STATUS_SET_XBUS, DP_STATUS_COMMAND_BUFFER_READY, DP_STATUS_COMMAND_BUSY, DP_STATUS_DMA_BUSY, DP_STATUS_END_VALID, DP_STATUS_PIPE_BUSY, DP_STATUS_START_GCLK, DP_STATUS_START_VALID, DP_STATUS_XBUS, RDP};
+use crate::rdp::rdp_assembler::{RDPAssembler, RDPRectangle};
+use crate::rsp::rsp::{RSP, SP_STATUS_HALT, SP_STATUS_SET_SET_HALT};
+use crate::rsp::rsp_assembler::{CP0Register, GPR, RSPAssembler};
+use crate::rsp::spmem::SPMEM;
+use crate::tests::{Level, Test};
+use crate::tests::soft_asserts::{soft_assert_eq, soft_assert_eq2};
+use crate::uncached_memory::UncachedHeapMemory;
+
+// Command lists that are written by the RSP into DMEM and started through the RSP's COP0 registers 8 to 15, which is
+// how games usually drive the RDP. The RSP also polls DP_STATUS and writes everything it sees into DMEM
+// (starting at 0x0), so that the CPU can check it afterwards.
+
+/// Where the RSP writes the first command list
+const FIRST_LIST: u32 = 0x100;
+
+/// Where the RSP writes the second command list. This doesn't follow the first list so that an implementation that
+/// simply keeps reading past the end of the first list doesn't pass by accident
+const SECOND_LIST: u32 = 0x800;
+
+/// DP_STATUS bits that are set while the RDP has anything left to do
+const BUSY_MASK: u32 = DP_STATUS_START_GCLK | DP_STATUS_PIPE_BUSY | DP_STATUS_COMMAND_BUSY | DP_STATUS_DMA_BUSY;
+
+/// Assembles a list that fills a 16 bit framebuffer with a single color
+fn fill_list<const WIDTH: usize, const HEIGHT: usize>(framebuffer: &mut UncachedHeapMemory<RGBA5551>, color: RGBA5551) -> Vec<u64> {
+    let mut assembler = RDPAssembler::new();
+    let rect = RDPRectangle::new(U10_2::from_usize(0), U10_2::from_usize(0), U10_2::from_usize(WIDTH - 1), U10_2::from_usize(HEIGHT - 1));
+    assembler.set_framebuffer_image(Format::RGBA, PixelSize::Bits16, u12::new((WIDTH - 1) as u16), framebuffer);
+    assembler.set_scissor(&RDPRectangle::new(U10_2::from_usize(0), U10_2::from_usize(0), U10_2::from_usize(WIDTH), U10_2::from_usize(HEIGHT)));
+    assembler.set_othermode(Othermode::new()
+        .with_cycle_type(CycleType::Fill));
+    assembler.set_fillcolor16(color, color);
+    assembler.filled_rectangle(&rect);
+    assembler.sync_pipe();
+    assembler.sync_full();
+    assembler.commands()
+}
+
+/// Emits RSP code that writes the given commands into DMEM
+fn write_commands_into_dmem(assembler: &mut RSPAssembler, offset: u32, commands: &[u64]) {
+    for (i, command) in commands.iter().enumerate() {
+        let address = (offset as usize + (i << 3)) as i16;
+        assembler.write_li(GPR::A0, (*command >> 32) as u32);
+        assembler.write_sw(GPR::A0, GPR::R0, address);
+        assembler.write_li(GPR::A0, *command as u32);
+        assembler.write_sw(GPR::A0, GPR::R0, address + 4);
+    }
+}
+
+/// Runs the RSP program at IMEM 0. If it doesn't finish (e.g. because DP_STATUS never becomes idle), the RSP is
+/// halted and XBUS is cleared so that later tests can still run
+fn run_rsp_with_timeout() -> Result<(), String> {
+    RSP::clear_broke();
+    RSP::start_running(0);
+    for _ in 0..1_000_000 {
+        if (RSP::status() & SP_STATUS_HALT) != 0 {
+            return Ok(());
+        }
+    }
+
+    RSP::set_status(SP_STATUS_SET_SET_HALT);
+    unsafe { RDP::set_status(DP_SET_STATUS_CLEAR_XBUS); }
+    Err(format!("Time out waiting for the RSP. It is probably waiting for the RDP. RSP PC: 0x{:x}. RDP status: 0x{:x}, start: 0x{:x}, current: 0x{:x}, end: 0x{:x}",
+                RSP::pc(), RDP::status(), RDP::start(), RDP::current(), RDP::end()))
+}
+
+fn assert_filled<const WIDTH: usize, const HEIGHT: usize>(framebuffer: &mut UncachedHeapMemory<RGBA5551>, color: RGBA5551, offset: u32, count: usize, help: &str) -> Result<(), String> {
+    for i in 0..WIDTH * HEIGHT {
+        soft_assert_eq2(framebuffer.read(i), color, || format!("{} at pixel {}. Command list in DMEM:\n{}", help, i, disassembler::disassemble_dmem(offset as usize, count)))?;
+    }
+    Ok(())
+}
+
+pub struct RunFromDMEMWrittenByRSP {}
+
+impl Test for RunFromDMEMWrittenByRSP {
+    fn name(&self) -> &str { "RDP STATUS: Run from DMEM (xbus) (list written and started by RSP)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        const WIDTH: usize = 8;
+        const HEIGHT: usize = 8;
+        let mut framebuffer = UncachedHeapMemory::<RGBA5551>::new_with_init_value(WIDTH * HEIGHT, RGBA5551::BLACK);
+        let commands = fill_list::<WIDTH, HEIGHT>(&mut framebuffer, RGBA5551::GREEN);
+        let end = FIRST_LIST + (commands.len() << 3) as u32;
+
+        let mut assembler = RSPAssembler::new(0);
+        write_commands_into_dmem(&mut assembler, FIRST_LIST, &commands);
+        assembler.write_set_dp_status(DP_SET_STATUS_SET_XBUS, GPR::A0);
+        assembler.write_li(GPR::S0, FIRST_LIST);
+        assembler.write_li(GPR::S1, end);
+        assembler.write_run_rdp(GPR::S0, GPR::S1);
+        assembler.write_wait_for_dp_current(GPR::S1, GPR::A0);
+        assembler.write_wait_for_dp_status_clear(BUSY_MASK as u16, GPR::S2, GPR::A0);
+        assembler.write_mfc0(CP0Register::DPStart, GPR::S3);
+        assembler.write_mfc0(CP0Register::DPEnd, GPR::S4);
+        assembler.write_mfc0(CP0Register::DPCurrent, GPR::S5);
+        assembler.write_sw(GPR::S2, GPR::R0, 0x0);
+        assembler.write_sw(GPR::S3, GPR::R0, 0x4);
+        assembler.write_sw(GPR::S4, GPR::R0, 0x8);
+        assembler.write_sw(GPR::S5, GPR::R0, 0xC);
+        assembler.write_set_dp_status(DP_SET_STATUS_CLEAR_XBUS, GPR::A0);
+        assembler.write_break();
+
+        run_rsp_with_timeout()?;
+
+        soft_assert_eq(SPMEM::read(0x0), DP_STATUS_COMMAND_BUFFER_READY | DP_STATUS_XBUS, "DP_STATUS (read via RSP MFC0) after the RDP is done")?;
+        soft_assert_eq(SPMEM::read(0x4), FIRST_LIST, "DP_START (read via RSP MFC0) after the RDP is done")?;
+        soft_assert_eq(SPMEM::read(0x8), end, "DP_END (read via RSP MFC0) after the RDP is done")?;
+        soft_assert_eq(SPMEM::read(0xC), end, "DP_CURRENT (read via RSP MFC0) after the RDP is done")?;
+        soft_assert_eq((RDP::status() & DP_STATUS_XBUS) != 0, false, "XBUS should be cleared by the RSP (DP_STATUS read via CPU)")?;
+
+        assert_filled::<WIDTH, HEIGHT>(&mut framebuffer, RGBA5551::GREEN, FIRST_LIST, commands.len(), "Framebuffer should be filled with GREEN")
+    }
+}
+
+/// While the RDP is busy, writing DP_START and DP_END queues up a second list. DP_STATUS shows this through the
+/// start-valid and end-valid bits, which are cleared once the second list begins
+pub struct QueueSecondListFromRSP {}
+
+impl Test for QueueSecondListFromRSP {
+    fn name(&self) -> &str { "RDP STATUS: Queue second list from RSP (xbus)" }
+
+    fn level(&self) -> Level { Level::RDPBasic }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        // The first list fills a whole screen, which keeps the RDP busy long enough for the RSP to queue the second
+        const LARGE_WIDTH: usize = 320;
+        const LARGE_HEIGHT: usize = 240;
+        const WIDTH: usize = 8;
+        const HEIGHT: usize = 8;
+        let mut large_framebuffer = UncachedHeapMemory::<RGBA5551>::new_with_init_value(LARGE_WIDTH * LARGE_HEIGHT, RGBA5551::BLACK);
+        let mut framebuffer = UncachedHeapMemory::<RGBA5551>::new_with_init_value(WIDTH * HEIGHT, RGBA5551::BLACK);
+        let first_commands = fill_list::<LARGE_WIDTH, LARGE_HEIGHT>(&mut large_framebuffer, RGBA5551::BLUE);
+        let second_commands = fill_list::<WIDTH, HEIGHT>(&mut framebuffer, RGBA5551::GREEN);
+        let first_end = FIRST_LIST + (first_commands.len() << 3) as u32;
+        let second_end = SECOND_LIST + (second_commands.len() << 3) as u32;
+
+        let mut assembler = RSPAssembler::new(0);
+        write_commands_into_dmem(&mut assembler, FIRST_LIST, &first_commands);
+        write_commands_into_dmem(&mut assembler, SECOND_LIST, &second_commands);
+        assembler.write_set_dp_status(DP_SET_STATUS_SET_XBUS, GPR::A0);
+        assembler.write_li(GPR::S0, FIRST_LIST);
+        assembler.write_li(GPR::S1, first_end);
+        assembler.write_li(GPR::S2, SECOND_LIST);
+        assembler.write_li(GPR::S3, second_end);
+        assembler.write_run_rdp(GPR::S0, GPR::S1);
+        assembler.write_mfc0(CP0Register::DPStatus, GPR::T0);
+        assembler.write_mtc0(CP0Register::DPStart, GPR::S2);
+        assembler.write_mfc0(CP0Register::DPStatus, GPR::T1);
+        assembler.write_mtc0(CP0Register::DPEnd, GPR::S3);
+        assembler.write_mfc0(CP0Register::DPStatus, GPR::T2);
+        assembler.write_wait_for_dp_current(GPR::S3, GPR::A0);
+        assembler.write_wait_for_dp_status_clear(BUSY_MASK as u16, GPR::T3, GPR::A0);
+        assembler.write_mfc0(CP0Register::DPStart, GPR::T4);
+        assembler.write_mfc0(CP0Register::DPEnd, GPR::T5);
+        assembler.write_mfc0(CP0Register::DPCurrent, GPR::T6);
+        assembler.write_sw(GPR::T0, GPR::R0, 0x0);
+        assembler.write_sw(GPR::T1, GPR::R0, 0x4);
+        assembler.write_sw(GPR::T2, GPR::R0, 0x8);
+        assembler.write_sw(GPR::T3, GPR::R0, 0xC);
+        assembler.write_sw(GPR::T4, GPR::R0, 0x10);
+        assembler.write_sw(GPR::T5, GPR::R0, 0x14);
+        assembler.write_sw(GPR::T6, GPR::R0, 0x18);
+        assembler.write_set_dp_status(DP_SET_STATUS_CLEAR_XBUS, GPR::A0);
+        assembler.write_break();
+
+        run_rsp_with_timeout()?;
+
+        let valid_bits = |offset: usize| SPMEM::read(offset) & (DP_STATUS_START_VALID | DP_STATUS_END_VALID);
+        soft_assert_eq(valid_bits(0x0), 0, "DP_STATUS start-valid/end-valid while the first list is running")?;
+        soft_assert_eq(valid_bits(0x4), DP_STATUS_START_VALID, "DP_STATUS start-valid/end-valid after writing DP_START of the second list while the first is running")?;
+        soft_assert_eq(valid_bits(0x8), DP_STATUS_START_VALID | DP_STATUS_END_VALID, "DP_STATUS start-valid/end-valid after writing DP_END of the second list while the first is running")?;
+        soft_assert_eq(SPMEM::read(0xC), DP_STATUS_COMMAND_BUFFER_READY | DP_STATUS_XBUS, "DP_STATUS after both lists are done")?;
+        soft_assert_eq(SPMEM::read(0x10), SECOND_LIST, "DP_START after both lists are done")?;
+        soft_assert_eq(SPMEM::read(0x14), second_end, "DP_END after both lists are done")?;
+        soft_assert_eq(SPMEM::read(0x18), second_end, "DP_CURRENT after both lists are done")?;
+
+        assert_filled::<LARGE_WIDTH, LARGE_HEIGHT>(&mut large_framebuffer, RGBA5551::BLUE, FIRST_LIST, first_commands.len(), "First framebuffer should be filled with BLUE")?;
+        assert_filled::<WIDTH, HEIGHT>(&mut framebuffer, RGBA5551::GREEN, SECOND_LIST, second_commands.len(), "Second framebuffer should be filled with GREEN")
+    }
+}
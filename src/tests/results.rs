@@ -0,0 +1,139 @@
+//! Machine-readable test results.
+//!
+//! When compiled with the `results_jsonl` or `results_tap` feature, every test value produces one
+//! record that is written through [`text_out`] (and therefore to EMUX, ISViewer or SC64), followed
+//! by a final summary record. This allows emulator harnesses to parse results without having to
+//! scrape the human-readable messages.
+
+use alloc::format;
+use alloc::string::String;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::tests::Level;
+use crate::text_out::text_out;
+
+#[cfg(all(feature = "results_jsonl", feature = "results_tap"))]
+compile_error!("Features results_jsonl and results_tap are mutually exclusive");
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum Format {
+    /// One JSON object per line
+    JsonLines,
+
+    /// Test Anything Protocol, version 13
+    Tap,
+}
+
+/// The format of the result stream, or None if only human-readable output is requested.
+pub const FORMAT: Option<Format> =
+    if cfg!(feature = "results_jsonl") {
+        Some(Format::JsonLines)
+    } else if cfg!(feature = "results_tap") {
+        Some(Format::Tap)
+    } else {
+        None
+    };
+
+/// The result of running a test with a single value.
+pub enum Outcome<'a> {
+    Pass,
+    Fail(&'a str),
+    Exception(&'a str),
+}
+
+impl<'a> Outcome<'a> {
+    fn name(&self) -> &'static str {
+        match self {
+            Outcome::Pass => "pass",
+            Outcome::Fail(_) => "fail",
+            Outcome::Exception(_) => "exception",
+        }
+    }
+
+    fn error(&self) -> Option<&'a str> {
+        match self {
+            Outcome::Pass => None,
+            Outcome::Fail(e) | Outcome::Exception(e) => Some(*e),
+        }
+    }
+}
+
+/// Number of records written so far. TAP requires consecutive numbers, starting at 1
+static RECORD_COUNT: AtomicU32 = AtomicU32::new(0);
+
+fn json_escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => { write!(result, "\\u{:04x}", c as u32).unwrap(); }
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Called once before the first test runs.
+pub fn begin() {
+    if FORMAT == Some(Format::Tap) {
+        text_out("TAP version 13\n");
+    }
+}
+
+/// Writes the record for a single test value. `value` is the description of the value as printed
+/// by the human-readable output (empty if the test doesn't take values).
+pub fn test_value(name: &str, level: &Level, value: &str, outcome: &Outcome, cycles: u32) {
+    let number = RECORD_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+    match FORMAT {
+        None => {}
+        Some(Format::JsonLines) => {
+            let error = match outcome.error() {
+                Some(e) => json_escape(e),
+                None => String::from("null"),
+            };
+            text_out(&format!(
+                "{{\"type\":\"test\",\"index\":{},\"name\":{},\"level\":\"{:?}\",\"value\":{},\"result\":\"{}\",\"cycles\":{},\"error\":{}}}\n",
+                number, json_escape(name), level, json_escape(value), outcome.name(), cycles, error));
+        }
+        Some(Format::Tap) => {
+            let mut s = String::new();
+            let ok = if let Outcome::Pass = outcome { "ok" } else { "not ok" };
+            write!(s, "{} {} - {}", ok, number, name).unwrap();
+            if !value.is_empty() {
+                write!(s, " with '{}'", value).unwrap();
+            }
+            write!(s, "\n  ---\n  level: {:?}\n  result: {}\n  cycles: {}\n", level, outcome.name(), cycles).unwrap();
+            if let Some(error) = outcome.error() {
+                // Use a YAML block scalar so that multi-line errors (e.g. pixel dumps) survive
+                s.push_str("  error: |\n");
+                for line in error.lines() {
+                    writeln!(s, "    {}", line).unwrap();
+                }
+            }
+            s.push_str("  ...\n");
+            text_out(&s);
+        }
+    }
+}
+
+/// Writes the summary record after all tests have run.
+pub fn summary(succeeded: u32, failed: u32, cycles: u32) {
+    match FORMAT {
+        None => {}
+        Some(Format::JsonLines) => {
+            text_out(&format!(
+                "{{\"type\":\"summary\",\"version\":\"{}\",\"tests\":{},\"passed\":{},\"failed\":{},\"cycles\":{}}}\n",
+                env!("CARGO_PKG_VERSION"), succeeded + failed, succeeded, failed, cycles));
+        }
+        Some(Format::Tap) => {
+            text_out(&format!("1..{}\n# passed {} failed {} cycles {}\n", RECORD_COUNT.load(Ordering::Relaxed), succeeded, failed, cycles));
+        }
+    }
+}
@@ -64,6 +64,7 @@ pub mod op_vsar;
 pub mod op_xor;
 pub mod op_xori;
 pub mod stresstests;
+pub mod stresstests_compare;
 pub mod stresstests_div;
 pub mod wrap_around;
 
@@ -1,15 +1,17 @@
 use alloc::boxed::Box;
+use alloc::format;
 use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::any::Any;
 
+use crate::cop0;
 use crate::mi;
-use crate::rsp::rsp::{RSP, SP_STATUS_HALT, SP_STATUS_SET_SET_HALT};
+use crate::rsp::rsp::{RSP, SP_STATUS_BROKE, SP_STATUS_HALT, SP_STATUS_INTERRUPT_ON_BREAK, SP_STATUS_SET_SET_HALT};
 use crate::rsp::rsp_assembler::{CP0Register, GPR, RSPAssembler};
 use crate::rsp::spmem::SPMEM;
-use crate::tests::{Level, Test};
-use crate::tests::soft_asserts::soft_assert_eq;
+use crate::tests::{COUNT_PER_MILLISECOND, Level, Test};
+use crate::tests::soft_asserts::{soft_assert_eq, soft_assert_eq2, soft_assert_neq};
 
 // Write various memory mapped registers, both from CPU and RSP. Lessons learned:
 // - SP.STATUS had various bits to clear and set. What happens if both are set at once? The target bit
@@ -17,6 +19,13 @@ use crate::tests::soft_asserts::soft_assert_eq;
 // - If Semaphore is written to (value doesn't matter), the next read will return 0. Otherwise it returns 1
 // - Semaphore doesn't distinguish between CPU and RSP in any way - if the CPU writes something, the RSP will read 0 (and vice versa)
 // - The RSP can stop itself by setting STATUS.HALT. When doing this, Status.broke will not be set (unlike BREAK)
+// - Writes to SP.STATUS from the RSP (through COP0) follow the same rules as writes from the CPU: Set and clear of the
+//   same signal is a no-op, while setting one signal and clearing another in the same write changes both
+// - A single write that clears one signal and sets another is seen by the other side as one change: Whoever sees the
+//   new signal also sees the old one cleared
+// - With both sides polling, a round trip (CPU sets a signal, RSP answers with another one) takes well below a
+//   millisecond
+// - BREAK sets Status.broke whether or not interrupt-on-break is enabled. Clearing broke doesn't clear the interrupt
 
 pub struct SetClearInterrupt {
 
@@ -404,3 +413,200 @@ impl Test for RSPHaltItselfWithoutBreak {
     }
 }
 
+/// Mask of all eight signal bits as read from SP_STATUS
+const SIGNAL_MASK: u32 = 0xFF << 7;
+
+pub struct SignalMailbox {
+
+}
+
+impl Test for SignalMailbox {
+    fn name(&self) -> &str { "SP signals as mailbox (CPU and RSP running in parallel)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        const MAX_ITERATIONS: u32 = 10000;
+
+        // Four round trips: The CPU sets an even signal. The RSP waits for it and answers by clearing it and
+        // setting the next (odd) signal with a single write
+        let mut assembler = RSPAssembler::new(0);
+        for k in 0..4u32 {
+            assembler.write_li(GPR::S1, MAX_ITERATIONS);
+
+            let loop_start = assembler.get_jump_target();
+            assembler.write_mfc0(CP0Register::SPStatus, GPR::S0);
+            assembler.write_andi(GPR::S0, GPR::S0, RSP::get_is_signal_bit(k * 2) as u16);
+            assembler.write_bne(GPR::S0, GPR::R0, 4);
+            assembler.write_nop();
+            assembler.write_addiu(GPR::S1, GPR::S1, -1);
+            assembler.write_bgtz_backwards(GPR::S1, &loop_start);
+            assembler.write_nop();
+
+            // BNE target: Write counter to see whether we timed out or got the signal
+            assembler.write_sw(GPR::S1, GPR::R0, (k << 2) as i16);
+            assembler.write_li(GPR::A0, RSP::get_clear_signal_bit(k * 2) | RSP::get_set_signal_bit(k * 2 + 1));
+            assembler.write_mtc0(CP0Register::SPStatus, GPR::A0);
+        }
+        assembler.write_break();
+
+        for i in 0..8 {
+            RSP::clear_signal(i);
+        }
+        for k in 0..4 {
+            SPMEM::write(k << 2, 0);
+        }
+        RSP::start_running(0);
+
+        let mut counters = [0u32; 4];
+        let mut statuses = [0u32; 4];
+        let mut round_trips = [0u32; 4];
+        for k in 0..4u32 {
+            let start = cop0::count();
+            RSP::set_signal(k * 2);
+
+            let mut counter = MAX_ITERATIONS;
+            while (!RSP::is_signal(k * 2 + 1)) && (counter > 0) {
+                counter -= 1;
+            }
+            round_trips[k as usize] = cop0::count().wrapping_sub(start);
+            counters[k as usize] = counter;
+            statuses[k as usize] = RSP::status();
+        }
+
+        RSP::wait_until_rsp_is_halted();
+
+        for k in 0..4u32 {
+            soft_assert_neq(SPMEM::read((k << 2) as usize), 0, format!("Timed out on RSP waiting for signal {} from CPU", k * 2).as_str())?;
+            soft_assert_neq(counters[k as usize], 0, format!("Timed out in CPU waiting for signal {} from RSP", k * 2 + 1).as_str())?;
+            soft_assert_eq2(statuses[k as usize] & RSP::get_is_signal_bit(k * 2), 0, || format!("Signal {} must already be clear when signal {} becomes visible (both were written at once). SP_STATUS=0x{:x}", k * 2, k * 2 + 1, statuses[k as usize]))?;
+            soft_assert_eq2(round_trips[k as usize] < COUNT_PER_MILLISECOND, true, || format!("Round trip from signal {} to signal {} took {} COP0 Count ticks (CPU polled {} times)", k * 2, k * 2 + 1, round_trips[k as usize], MAX_ITERATIONS - counters[k as usize]))?;
+        }
+
+        let odd_signals = RSP::get_is_signal_bit(1) | RSP::get_is_signal_bit(3) | RSP::get_is_signal_bit(5) | RSP::get_is_signal_bit(7);
+        soft_assert_eq(RSP::status() & SIGNAL_MASK, odd_signals, "Signals after all round trips")?;
+
+        for i in 0..8 {
+            RSP::clear_signal(i);
+        }
+
+        Ok(())
+    }
+}
+
+pub struct SetClearSignalFromRSP {
+
+}
+
+impl Test for SetClearSignalFromRSP {
+    fn name(&self) -> &str { "SP Set/Clear Signal from RSP" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        vec! {
+            Box::new(0u32),
+            Box::new(1u32),
+            Box::new(2u32),
+            Box::new(3u32),
+            Box::new(4u32),
+            Box::new(5u32),
+            Box::new(6u32),
+            Box::new(7u32),
+        }
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let i = *(*value).downcast_ref::<u32>().unwrap();
+        // Another signal that is set by the CPU up front and cleared by the RSP in the last step
+        let other = (i + 1) & 7;
+
+        // (SP_STATUS write, expected signals afterwards, description)
+        let steps = [
+            (RSP::get_set_signal_bit(i), RSP::get_is_signal_bit(i) | RSP::get_is_signal_bit(other), "set"),
+            (RSP::get_set_signal_bit(i) | RSP::get_clear_signal_bit(i), RSP::get_is_signal_bit(i) | RSP::get_is_signal_bit(other), "set and clear while set"),
+            (RSP::get_clear_signal_bit(i), RSP::get_is_signal_bit(other), "clear"),
+            (RSP::get_set_signal_bit(i) | RSP::get_clear_signal_bit(i), RSP::get_is_signal_bit(other), "set and clear while clear"),
+            (RSP::get_set_signal_bit(i) | RSP::get_clear_signal_bit(other), RSP::get_is_signal_bit(i), "set and clear of another signal"),
+        ];
+
+        let mut assembler = RSPAssembler::new(0);
+        for (index, (write, _, _)) in steps.iter().enumerate() {
+            assembler.write_li(GPR::A0, *write);
+            assembler.write_mtc0(CP0Register::SPStatus, GPR::A0);
+            assembler.write_mfc0(CP0Register::SPStatus, GPR::S0);
+            assembler.write_sw(GPR::S0, GPR::R0, (index << 2) as i16);
+        }
+        assembler.write_break();
+
+        for s in 0..8 {
+            RSP::clear_signal(s);
+        }
+        RSP::set_signal(other);
+        RSP::run_and_wait(0);
+
+        for (index, (_, expected, description)) in steps.iter().enumerate() {
+            let status = SPMEM::read(index << 2);
+            soft_assert_eq2(status & SIGNAL_MASK, *expected, || format!("Signals read back by RSP after '{}' (signal {}, other signal {}). SP_STATUS=0x{:x}", description, i, other, status))?;
+        }
+        soft_assert_eq(RSP::status() & SIGNAL_MASK, RSP::get_is_signal_bit(i), "Signals seen by the CPU after the RSP is done")?;
+
+        for s in 0..8 {
+            RSP::clear_signal(s);
+        }
+
+        Ok(())
+    }
+}
+
+pub struct BreakSetsBroke {
+
+}
+
+impl Test for BreakSetsBroke {
+    fn name(&self) -> &str { "SP BREAK sets broke (with and without interrupt on break)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        vec! {
+            Box::new(false),
+            Box::new(true),
+        }
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let interrupt_on_break = *(*value).downcast_ref::<bool>().unwrap();
+
+        RSP::clear_broke();
+        RSP::clear_interrupt();
+        if interrupt_on_break {
+            RSP::set_interrupt_on_break();
+        } else {
+            RSP::clear_interrupt_on_break();
+        }
+
+        let mut assembler = RSPAssembler::new(0);
+        assembler.write_nop();
+        assembler.write_break();
+        RSP::run_and_wait(0);
+
+        let expected_status = SP_STATUS_HALT | SP_STATUS_BROKE | if interrupt_on_break { SP_STATUS_INTERRUPT_ON_BREAK } else { 0 };
+        soft_assert_eq(RSP::status() & (SP_STATUS_HALT | SP_STATUS_BROKE | SP_STATUS_INTERRUPT_ON_BREAK), expected_status, "SP_STATUS after BREAK")?;
+        soft_assert_eq(mi::is_sp_interrupt(), interrupt_on_break, "MI INTR should contain SP after BREAK exactly if interrupt on break is set")?;
+
+        // Broke and the interrupt are separate: Clearing one doesn't touch the other
+        RSP::clear_broke();
+        soft_assert_eq(RSP::status() & SP_STATUS_BROKE, 0, "SP_STATUS.broke after clearing it")?;
+        soft_assert_eq(mi::is_sp_interrupt(), interrupt_on_break, "Clearing broke should not clear the SP interrupt")?;
+        RSP::clear_interrupt();
+        soft_assert_eq(mi::is_sp_interrupt(), false, "MI INTR should not contain SP after clearing within SP_STATUS")?;
+        soft_assert_eq(RSP::status() & SP_STATUS_BROKE, 0, "Clearing the interrupt should not set broke again")?;
+
+        RSP::clear_interrupt_on_break();
+
+        Ok(())
+    }
+}
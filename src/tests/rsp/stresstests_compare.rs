@@ -0,0 +1,220 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+use oorandom::Rand64;
+
+use crate::VIDEO;
+use crate::graphics::color::Color;
+use crate::graphics::color::RGBA5551;
+use crate::graphics::cursor::Cursor;
+use crate::graphics::font::Font;
+use crate::graphics::system_font::FONT_GENEVA_9;
+use crate::math::vector::{Vector, VectorCompareOp, VectorFlags};
+use crate::rsp::rsp::RSP;
+use crate::rsp::rsp_assembler::{CP2FlagsRegister, E, Element, GPR, RSPAssembler, VR, VSARAccumulator};
+use crate::rsp::spmem::SPMEM;
+use crate::tests::{Level, NamedValue, Test};
+use crate::tests::soft_asserts::{soft_assert_eq2, soft_assert_eq_vector};
+
+// Runs random inputs and random VCO/VCC/VCE preloads through the select and clip instructions and compares the
+// result vector, the low accumulator and all three flag registers against the model in math::vector.
+// The second input is derived from the first one (equal, negated, inverted, off by one, ...) most of the time, as
+// purely random inputs would almost never hit the equal and sum-is-zero cases, which is where the flags matter most.
+
+/// Number of inputs that the RSP processes per run
+const CASES_PER_RUN: usize = 32;
+
+/// Every case occupies this many bytes in DMEM:
+/// 0x00: vs, 0x10: vt, 0x20: VCO, 0x24: VCC, 0x28: VCE (inputs)
+/// 0x30: vd, 0x40: accumulator low, 0x50: VCO, 0x54: VCC, 0x58: VCE (outputs)
+const CASE_SIZE: usize = 0x60;
+
+const OPS: [(&str, VectorCompareOp); 7] = [
+    ("VLT", VectorCompareOp::VLT),
+    ("VEQ", VectorCompareOp::VEQ),
+    ("VNE", VectorCompareOp::VNE),
+    ("VGE", VectorCompareOp::VGE),
+    ("VCL", VectorCompareOp::VCL),
+    ("VCH", VectorCompareOp::VCH),
+    ("VCR", VectorCompareOp::VCR),
+];
+
+const INTERESTING_VALUES: [u16; 8] = [0x0000, 0x0001, 0x7FFE, 0x7FFF, 0x8000, 0x8001, 0xFFFE, 0xFFFF];
+
+fn emit(assembler: &mut RSPAssembler, op: VectorCompareOp, vd: VR, vt: VR, vs: VR, e: Element) {
+    match op {
+        VectorCompareOp::VLT => assembler.write_vlt(vd, vt, vs, e),
+        VectorCompareOp::VEQ => assembler.write_veq(vd, vt, vs, e),
+        VectorCompareOp::VNE => assembler.write_vne(vd, vt, vs, e),
+        VectorCompareOp::VGE => assembler.write_vge(vd, vt, vs, e),
+        VectorCompareOp::VCL => assembler.write_vcl(vd, vt, vs, e),
+        VectorCompareOp::VCH => assembler.write_vch(vd, vt, vs, e),
+        VectorCompareOp::VCR => assembler.write_vcr(vd, vt, vs, e),
+    }
+}
+
+fn random_value(random: &mut Rand64) -> u16 {
+    let r = random.rand_u64();
+    if (r & 3) == 0 { INTERESTING_VALUES[((r >> 2) & 7) as usize] } else { (r >> 16) as u16 }
+}
+
+/// A value for vs that is related to the vt element it is compared against
+fn related_value(random: &mut Rand64, other: u16) -> u16 {
+    match random.rand_u64() % 8 {
+        0 | 1 => other,
+        2 => other.wrapping_neg(),
+        3 => !other,
+        4 => other.wrapping_add(1),
+        5 => other.wrapping_sub(1),
+        _ => random_value(random),
+    }
+}
+
+fn random_case(random: &mut Rand64, e: Element) -> (Vector, Vector, VectorFlags) {
+    let mut vt = Vector::new();
+    for i in 0..8 {
+        vt.set16(i, random_value(random));
+    }
+    let mut vs = Vector::new();
+    for i in 0..8 {
+        vs.set16(i, related_value(random, vt.get16(e.get_effective_element_index(i))));
+    }
+    let r = random.rand_u64();
+    let flags = VectorFlags { vco: r as u16, vcc: (r >> 16) as u16, vce: (r >> 32) as u8 };
+    (vs, vt, flags)
+}
+
+fn assemble(op: VectorCompareOp, e: Element) {
+    let mut assembler = RSPAssembler::new(0);
+
+    // GPRs:
+    // - S0: Address of the current case
+    // - S1: Decrementing loop counter
+    assembler.write_li(GPR::S0, 0);
+    assembler.write_li(GPR::S1, CASES_PER_RUN as u32);
+
+    let loop_beginning = assembler.get_jump_target();
+    assembler.write_lw(GPR::AT, GPR::S0, 0x20);
+    assembler.write_ctc2(CP2FlagsRegister::VCO, GPR::AT);
+    assembler.write_lw(GPR::AT, GPR::S0, 0x24);
+    assembler.write_ctc2(CP2FlagsRegister::VCC, GPR::AT);
+    assembler.write_lw(GPR::AT, GPR::S0, 0x28);
+    assembler.write_ctc2(CP2FlagsRegister::VCE, GPR::AT);
+    assembler.write_lqv(VR::V1, E::_0, 0x00, GPR::S0);
+    assembler.write_lqv(VR::V2, E::_0, 0x10, GPR::S0);
+
+    emit(&mut assembler, op, VR::V3, VR::V2, VR::V1, e);
+
+    assembler.write_vsar(VR::V4, VSARAccumulator::Low);
+    assembler.write_cfc2(CP2FlagsRegister::VCO, GPR::T0);
+    assembler.write_cfc2(CP2FlagsRegister::VCC, GPR::T1);
+    assembler.write_cfc2(CP2FlagsRegister::VCE, GPR::T2);
+    assembler.write_sqv(VR::V3, E::_0, 0x30, GPR::S0);
+    assembler.write_sqv(VR::V4, E::_0, 0x40, GPR::S0);
+    assembler.write_sw(GPR::T0, GPR::S0, 0x50);
+    assembler.write_sw(GPR::T1, GPR::S0, 0x54);
+    assembler.write_sw(GPR::T2, GPR::S0, 0x58);
+
+    assembler.write_addiu(GPR::S1, GPR::S1, -1);
+    assembler.write_bgtz_backwards(GPR::S1, &loop_beginning);
+    assembler.write_addiu(GPR::S0, GPR::S0, CASE_SIZE as i16);  // delay slot
+
+    assembler.write_break();
+}
+
+fn run_randomized(value: &Box<dyn Any>, progress_indicator: bool, runs_per_element: usize) -> Result<(), String> {
+    let (name, op) = *NamedValue::get::<(&'static str, VectorCompareOp)>(value);
+    let mut random = Rand64::new(0);
+
+    let font = Font::from_data(&FONT_GENEVA_9).unwrap();
+    let mut cursor = Cursor::new_with_font(&font, RGBA5551::BLACK);
+
+    for e in Element::range() {
+        assemble(op, e);
+
+        for run in 0..runs_per_element {
+            if progress_indicator && ((run & 63) == 0) {
+                let v = VIDEO.lock();
+                {
+                    let mut lock = v.framebuffers().backbuffer().lock();
+                    let buffer = lock.as_mut().unwrap();
+                    buffer.clear_with_color(RGBA5551::WHITE);
+
+                    cursor.x = 16;
+                    cursor.y = 16;
+                    cursor.draw_text(buffer, format!("Stress testing {}. {}% complete", name, ((e as usize) * runs_per_element + run) * 100 / (16 * runs_per_element)).as_str());
+                }
+                v.swap_buffers();
+            }
+
+            let mut cases = [(Vector::new(), Vector::new(), VectorFlags::default()); CASES_PER_RUN];
+            for (i, case) in cases.iter_mut().enumerate() {
+                *case = random_case(&mut random, e);
+                let address = i * CASE_SIZE;
+                SPMEM::write_vector_into_dmem(address, &case.0);
+                SPMEM::write_vector_into_dmem(address + 0x10, &case.1);
+                SPMEM::write(address + 0x20, case.2.vco as u32);
+                SPMEM::write(address + 0x24, case.2.vcc as u32);
+                SPMEM::write(address + 0x28, case.2.vce as u32);
+            }
+
+            RSP::run_and_wait(0);
+
+            for (i, (vs, vt, flags)) in cases.iter().enumerate() {
+                let address = i * CASE_SIZE;
+                let (expected_result, expected_flags) = op.compute(vs, vt, e, *flags);
+                let result = SPMEM::read_vector_from_dmem(address + 0x30);
+                let accumulator = SPMEM::read_vector_from_dmem(address + 0x40);
+                let actual_flags = VectorFlags {
+                    vco: SPMEM::read(address + 0x50) as u16,
+                    vcc: SPMEM::read(address + 0x54) as u16,
+                    vce: SPMEM::read(address + 0x58) as u8,
+                };
+                if (result != expected_result) || (accumulator != expected_result) || (actual_flags != expected_flags) {
+                    let inputs = || format!("{} vs={:x?} vt={:x?}[{:?}] VCO=0x{:04x} VCC=0x{:04x} VCE=0x{:02x}", name, vs, vt, e, flags.vco, flags.vcc, flags.vce);
+                    soft_assert_eq_vector(result, expected_result, || format!("Result vector for {}", inputs()))?;
+                    soft_assert_eq_vector(accumulator, expected_result, || format!("Acc[0..16] for {}", inputs()))?;
+                    soft_assert_eq2(actual_flags.vco, expected_flags.vco, || format!("VCO for {}", inputs()))?;
+                    soft_assert_eq2(actual_flags.vcc, expected_flags.vcc, || format!("VCC for {}", inputs()))?;
+                    soft_assert_eq2(actual_flags.vce, expected_flags.vce, || format!("VCE for {}", inputs()))?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub struct VectorCompareRandomized {}
+
+impl Test for VectorCompareRandomized {
+    fn name(&self) -> &str { "RSP VLT/VEQ/VNE/VGE/VCL/VCH/VCR (randomized flags - quick)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        OPS.iter().map(|case| NamedValue::boxed(case.0, *case)).collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        run_randomized(value, false, 8)
+    }
+}
+
+pub struct VectorCompareStresstest {}
+
+impl Test for VectorCompareStresstest {
+    fn name(&self) -> &str { "RSP VLT/VEQ/VNE/VGE/VCL/VCH/VCR (randomized flags - stresstest)" }
+
+    fn level(&self) -> Level { Level::StressTest }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        OPS.iter().map(|case| NamedValue::boxed(case.0, *case)).collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        run_randomized(value, true, 4096)
+    }
+}
@@ -0,0 +1,396 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+
+use crate::MemoryMap;
+use crate::mi;
+use crate::si::{PIF_RAM_SIZE, Si};
+use crate::tests::{delay_ms, Level, Test, wait_for};
+use crate::tests::soft_asserts::{soft_assert_eq, soft_assert_eq2};
+use crate::uncached_memory::UncachedHeapMemory;
+
+// Joybus protocol in PIF RAM:
+// - Each command is: tx length, rx length, tx bytes (starting with the command), rx bytes
+// - After each command, the channel is incremented. 0x00 skips a channel, 0xFF is padding that doesn't
+//   increment the channel and 0xFE ends processing
+// - If there is no device on a channel, bit 7 of the rx length byte is set and the rx bytes are left alone
+// - Processing is started by setting bit 0 of the last byte (control byte) before DMAing into PIF RAM
+
+const CONTROL_BYTE_OFFSET: usize = PIF_RAM_SIZE - 1;
+const CONTROL_RUN_JOYBUS: u8 = 0x01;
+
+const RX_ERROR_NO_DEVICE: u8 = 0x80;
+const RX_ERROR_MASK: u8 = 0xC0;
+
+const COMMAND_INFO: u8 = 0x00;
+const COMMAND_READ_BUTTONS: u8 = 0x01;
+const COMMAND_EEPROM_READ: u8 = 0x04;
+const COMMAND_EEPROM_WRITE: u8 = 0x05;
+const COMMAND_RESET: u8 = 0xFF;
+
+const CHANNEL_EEPROM: usize = 4;
+
+/// An EEPROM write takes up to 15ms, during which the EEPROM doesn't respond
+const EEPROM_WRITE_MILLISECONDS: u32 = 20;
+
+/// A DMA (including the joybus processing that follows it) takes well below a millisecond
+const DMA_TIMEOUT_MILLISECONDS: u32 = 10;
+
+/// Builds the PIF RAM contents for a list of joybus commands
+struct JoybusBlock {
+    data: [u8; PIF_RAM_SIZE],
+    position: usize,
+}
+
+impl JoybusBlock {
+    fn new() -> Self {
+        let mut data = [0u8; PIF_RAM_SIZE];
+        data[CONTROL_BYTE_OFFSET] = CONTROL_RUN_JOYBUS;
+        Self { data, position: 0 }
+    }
+
+    fn push(&mut self, value: u8) {
+        assert!(self.position < CONTROL_BYTE_OFFSET);
+        self.data[self.position] = value;
+        self.position += 1;
+    }
+
+    fn skip_channel(&mut self) { self.push(0x00); }
+
+    fn padding(&mut self) { self.push(0xFF); }
+
+    fn end(&mut self) { self.push(0xFE); }
+
+    /// Adds a command and returns its offset, which can be passed to [`response`]. The rx bytes are
+    /// prefilled with 0xFF to see whether they are being written.
+    fn command(&mut self, tx: &[u8], rx_length: usize) -> usize {
+        let offset = self.position;
+        self.push(tx.len() as u8);
+        self.push(rx_length as u8);
+        for b in tx {
+            self.push(*b);
+        }
+        for _ in 0..rx_length {
+            self.push(0xFF);
+        }
+        offset
+    }
+}
+
+/// Returns the rx length byte (including error bits) and the rx bytes of the command at the given offset
+fn response(result: &[u8; PIF_RAM_SIZE], offset: usize) -> (u8, &[u8]) {
+    let tx_length = result[offset] as usize;
+    let rx_byte = result[offset + 1];
+    let rx_length = (rx_byte & !RX_ERROR_MASK) as usize;
+    let rx_start = offset + 2 + tx_length;
+    (rx_byte, &result[rx_start..rx_start + rx_length])
+}
+
+fn wait_until_idle() -> Result<(), String> {
+    wait_for("SI to become idle", DMA_TIMEOUT_MILLISECONDS, Si::is_idle)
+}
+
+fn dma_to_pif(data: &[u8; PIF_RAM_SIZE]) -> Result<(), String> {
+    let mut buffer = UncachedHeapMemory::<u8>::new_with_align(PIF_RAM_SIZE, 8);
+    for (i, b) in data.iter().enumerate() {
+        buffer.write(i, *b);
+    }
+    Si::set_dram_address(buffer.start_phyiscal() as u32);
+    Si::start_dram_to_pif();
+    let result = wait_until_idle();
+    Si::clear_interrupt();
+    result
+}
+
+fn dma_from_pif() -> Result<[u8; PIF_RAM_SIZE], String> {
+    let mut buffer = UncachedHeapMemory::<u8>::new_with_init_value(PIF_RAM_SIZE, 0xAA);
+    Si::set_dram_address(buffer.start_phyiscal() as u32);
+    Si::start_pif_to_dram();
+    let result = wait_until_idle();
+    Si::clear_interrupt();
+    result?;
+    let mut result = [0u8; PIF_RAM_SIZE];
+    for i in 0..PIF_RAM_SIZE {
+        result[i] = buffer.read(i);
+    }
+    Ok(result)
+}
+
+/// Executes the joybus commands and returns the PIF RAM contents afterwards
+fn run_joybus(block: &JoybusBlock) -> Result<[u8; PIF_RAM_SIZE], String> {
+    dma_to_pif(&block.data)?;
+    dma_from_pif()
+}
+
+fn is_controller_status(rx_byte: u8, rx: &[u8]) -> bool {
+    rx_byte == 3 && rx[0] == 0x05 && rx[1] == 0x00
+}
+
+pub struct DMARoundTrip {}
+
+impl Test for DMARoundTrip {
+    fn name(&self) -> &str { "SI: DMA RDRAM -> PIF RAM -> RDRAM" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        Si::clear_interrupt();
+        soft_assert_eq(Si::status().interrupt(), false, "SI_STATUS.interrupt after acknowledging")?;
+
+        // Control byte of 0 means that the PIF won't touch the data
+        let mut source = UncachedHeapMemory::<u8>::new_with_align(PIF_RAM_SIZE, 8);
+        for i in 0..PIF_RAM_SIZE {
+            source.write(i, if i == CONTROL_BYTE_OFFSET { 0 } else { (i as u8).wrapping_mul(7).wrapping_add(3) });
+        }
+        Si::set_dram_address(source.start_phyiscal() as u32);
+        Si::start_dram_to_pif();
+        soft_assert_eq(Si::status().dma_busy(), true, "SI_STATUS.dma_busy right after starting DMA")?;
+        wait_until_idle()?;
+        soft_assert_eq(Si::status().interrupt(), true, "SI_STATUS.interrupt after DMA")?;
+        soft_assert_eq(mi::is_si_interrupt(), true, "MI_INTR.SI after DMA")?;
+        Si::clear_interrupt();
+        soft_assert_eq(Si::status().interrupt(), false, "SI_STATUS.interrupt after writing SI_STATUS")?;
+        soft_assert_eq(mi::is_si_interrupt(), false, "MI_INTR.SI after writing SI_STATUS")?;
+
+        // Read back through the CPU
+        for i in 0..(PIF_RAM_SIZE / 4) - 1 {
+            let value = unsafe { MemoryMap::uncached_pifram_address::<u32>(i * 4).read_volatile() };
+            let expected = u32::from_be_bytes([source.read(i * 4), source.read(i * 4 + 1), source.read(i * 4 + 2), source.read(i * 4 + 3)]);
+            soft_assert_eq2(value, expected, || format!("PIF RAM word {} read through LW after DMA", i))?;
+        }
+
+        // Read back through DMA
+        let result = dma_from_pif()?;
+        for i in 0..CONTROL_BYTE_OFFSET {
+            soft_assert_eq2(result[i], source.read(i), || format!("PIF RAM byte {} after DMA to RDRAM", i))?;
+        }
+        soft_assert_eq(Si::status().interrupt(), false, "SI_STATUS.interrupt after DMA (acknowledged)")?;
+
+        Ok(())
+    }
+}
+
+pub struct ControllerStatus {}
+
+impl Test for ControllerStatus {
+    fn name(&self) -> &str { "SI: Joybus controller status (0x00)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let mut block = JoybusBlock::new();
+        let offset = block.command(&[COMMAND_INFO], 3);
+        block.end();
+        let result = run_joybus(&block)?;
+
+        let (rx_byte, rx) = response(&result, offset);
+        soft_assert_eq2(is_controller_status(rx_byte, rx), true, || format!("Controller 1 is expected to respond with 05 00 xx. Rx length byte: {:#x}, rx: {:x?}", rx_byte, rx))?;
+        soft_assert_eq(result[CONTROL_BYTE_OFFSET] & CONTROL_RUN_JOYBUS, 0, "Control byte after joybus processing")?;
+        Ok(())
+    }
+}
+
+pub struct ControllerReset {}
+
+impl Test for ControllerReset {
+    fn name(&self) -> &str { "SI: Joybus controller reset (0xFF)" }
+
+    fn level(&self) -> Level { Level::RarelyUsed }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let mut block = JoybusBlock::new();
+        let offset = block.command(&[COMMAND_RESET], 3);
+        block.end();
+        let result = run_joybus(&block)?;
+
+        let (rx_byte, rx) = response(&result, offset);
+        soft_assert_eq2(is_controller_status(rx_byte, rx), true, || format!("Controller 1 is expected to respond to reset with 05 00 xx. Rx length byte: {:#x}, rx: {:x?}", rx_byte, rx))
+    }
+}
+
+pub struct ControllerReadButtons {}
+
+impl Test for ControllerReadButtons {
+    fn name(&self) -> &str { "SI: Joybus read buttons (0x01)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let mut block = JoybusBlock::new();
+        let offset = block.command(&[COMMAND_READ_BUTTONS], 4);
+        block.end();
+        let result = run_joybus(&block)?;
+
+        let (rx_byte, rx) = response(&result, offset);
+        soft_assert_eq(rx_byte, 4, "Rx length byte of read buttons (no error bits expected)")?;
+        // The second byte contains L/R/C buttons. Bit 6 is unused and always 0
+        soft_assert_eq2(rx[1] & 0x40, 0, || format!("Reserved button bit is expected to be 0. Response: {:x?}", rx))
+    }
+}
+
+pub struct AbsentDevices {}
+
+impl Test for AbsentDevices {
+    fn name(&self) -> &str { "SI: Joybus all controller channels" }
+
+    fn level(&self) -> Level { Level::RarelyUsed }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let mut block = JoybusBlock::new();
+        let offsets: Vec<usize> = (0..4).map(|_| block.command(&[COMMAND_INFO], 3)).collect();
+        block.end();
+        let result = run_joybus(&block)?;
+
+        for (channel, offset) in offsets.iter().enumerate() {
+            let (rx_byte, rx) = response(&result, *offset);
+            if (rx_byte & RX_ERROR_NO_DEVICE) != 0 {
+                soft_assert_eq2(channel != 0, true, || format!("Controller 1 is expected to be present"))?;
+                // Nothing is written into the response if there's no device
+                soft_assert_eq2(rx, &[0xFFu8, 0xFF, 0xFF][..], || format!("Rx bytes of absent controller {}", channel + 1))?;
+            } else {
+                soft_assert_eq2(is_controller_status(rx_byte, rx), true, || format!("Controller {} is expected to either be absent (bit 7 set in rx length byte) or respond with 05 00 xx. Rx length byte: {:#x}, rx: {:x?}", channel + 1, rx_byte, rx))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct ChannelSkipAndPadding {}
+
+impl Test for ChannelSkipAndPadding {
+    fn name(&self) -> &str { "SI: Joybus channel skip (0x00) and padding (0xFF)" }
+
+    fn level(&self) -> Level { Level::Weird }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        // Padding doesn't increment the channel, so this still talks to controller 1
+        let mut block = JoybusBlock::new();
+        block.padding();
+        block.padding();
+        block.padding();
+        let offset = block.command(&[COMMAND_INFO], 3);
+        block.end();
+        let result = run_joybus(&block)?;
+        let (rx_byte, rx) = response(&result, offset);
+        soft_assert_eq2(is_controller_status(rx_byte, rx), true, || format!("After padding, controller 1 is expected to respond with 05 00 xx. Rx length byte: {:#x}, rx: {:x?}", rx_byte, rx))?;
+
+        // Skipping all four controller channels ends up at the cartridge (EEPROM) channel
+        let mut block = JoybusBlock::new();
+        for _ in 0..CHANNEL_EEPROM {
+            block.skip_channel();
+        }
+        let offset = block.command(&[COMMAND_INFO], 3);
+        block.end();
+        let result = run_joybus(&block)?;
+        let (rx_byte, rx) = response(&result, offset);
+        let absent = (rx_byte & RX_ERROR_NO_DEVICE) != 0;
+        soft_assert_eq2(absent || rx[0] == 0x00, true, || format!("After skipping four channels, the info command should go to the cartridge, which either doesn't exist or is an EEPROM (00 80 xx or 00 C0 xx). Rx length byte: {:#x}, rx: {:x?}", rx_byte, rx))
+    }
+}
+
+pub struct EndOfCommands {}
+
+impl Test for EndOfCommands {
+    fn name(&self) -> &str { "SI: Joybus end of commands (0xFE)" }
+
+    fn level(&self) -> Level { Level::Weird }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let mut block = JoybusBlock::new();
+        let offset1 = block.command(&[COMMAND_INFO], 3);
+        block.end();
+        let offset2 = block.command(&[COMMAND_INFO], 3);
+        let result = run_joybus(&block)?;
+
+        let (rx_byte, rx) = response(&result, offset1);
+        soft_assert_eq2(is_controller_status(rx_byte, rx), true, || format!("Command before 0xFE should execute. Rx length byte: {:#x}, rx: {:x?}", rx_byte, rx))?;
+        let (rx_byte, rx) = response(&result, offset2);
+        soft_assert_eq(rx_byte, 3, "Rx length byte of command after 0xFE (no error bits expected as it isn't executed)")?;
+        soft_assert_eq(rx, &[0xFFu8, 0xFF, 0xFF][..], "Rx bytes of command after 0xFE should be untouched")?;
+        Ok(())
+    }
+}
+
+fn eeprom_command(tx: &[u8], rx_length: usize) -> Result<(u8, Vec<u8>), String> {
+    let mut block = JoybusBlock::new();
+    for _ in 0..CHANNEL_EEPROM {
+        block.skip_channel();
+    }
+    let offset = block.command(tx, rx_length);
+    block.end();
+    let result = run_joybus(&block)?;
+    let (rx_byte, rx) = response(&result, offset);
+    Ok((rx_byte, Vec::from(rx)))
+}
+
+/// Writes an EEPROM block and waits until the write is done. Returns the rx length byte and the response
+fn eeprom_write(block: u8, data: &[u8]) -> Result<(u8, Vec<u8>), String> {
+    let mut tx = Vec::from([COMMAND_EEPROM_WRITE, block]);
+    tx.extend_from_slice(data);
+    let result = eeprom_command(&tx, 1);
+    delay_ms(EEPROM_WRITE_MILLISECONDS);
+    result
+}
+
+pub struct EepromInfoReadWrite {}
+
+impl Test for EepromInfoReadWrite {
+    fn name(&self) -> &str { "SI: Joybus EEPROM info/read/write" }
+
+    fn level(&self) -> Level { Level::RarelyUsed }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let (rx_byte, info) = eeprom_command(&[COMMAND_INFO], 3)?;
+        if (rx_byte & RX_ERROR_NO_DEVICE) != 0 {
+            // No EEPROM. Reading should fail the same way
+            let (rx_byte, rx) = eeprom_command(&[COMMAND_EEPROM_READ, 0], 8)?;
+            soft_assert_eq2(rx_byte & RX_ERROR_NO_DEVICE, RX_ERROR_NO_DEVICE, || format!("EEPROM info reported no device, so reading should as well. Rx length byte: {:#x}, rx: {:x?}", rx_byte, rx))?;
+            return Ok(());
+        }
+
+        soft_assert_eq2(rx_byte, 3, || format!("Rx length byte of EEPROM info. Response: {:x?}", info))?;
+        // 0x80 for 4 kbit, 0xC0 for 16 kbit
+        soft_assert_eq2(info[0] == 0x00 && (info[1] == 0x80 || info[1] == 0xC0), true, || format!("EEPROM info is expected to be 00 80 xx (4K) or 00 C0 xx (16K), but was {:x?}", info))?;
+
+        const BLOCK: u8 = 1;
+        let (rx_byte, original) = eeprom_command(&[COMMAND_EEPROM_READ, BLOCK], 8)?;
+        soft_assert_eq(rx_byte, 8, "Rx length byte of EEPROM read")?;
+
+        fn write_and_read_back(pattern: &[u8]) -> Result<(), String> {
+            let (rx_byte, rx) = eeprom_write(BLOCK, pattern)?;
+            soft_assert_eq(rx_byte, 1, "Rx length byte of EEPROM write")?;
+            soft_assert_eq(rx[0] & 0x80, 0, "EEPROM write response (busy bit)")?;
+
+            let (rx_byte, readback) = eeprom_command(&[COMMAND_EEPROM_READ, BLOCK], 8)?;
+            soft_assert_eq(rx_byte, 8, "Rx length byte of EEPROM read after write")?;
+            soft_assert_eq2(&readback[..], pattern, || format!("EEPROM block {} after writing", BLOCK))
+        }
+
+        let result = write_and_read_back(&[0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC, 0xDE, 0xF0]);
+
+        // Restore the original contents, even if the test failed
+        let restore_result = eeprom_write(BLOCK, &original);
+
+        result?;
+        restore_result?;
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 pub mod dma;
+pub mod rsp_dma;
 
 use alloc::boxed::Box;
 use alloc::string::String;
@@ -0,0 +1,180 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+
+use crate::rsp::rsp::RSP;
+use crate::rsp::rsp_assembler::{CP0Register, GPR, RSPAssembler};
+use crate::rsp::spmem::SPMEM;
+use crate::tests::{Level, NamedValue, Test};
+use crate::tests::soft_asserts::{soft_assert_eq, soft_assert_eq2};
+use crate::uncached_memory::UncachedHeapMemory;
+
+// DMAs that are started by the RSP itself, by writing the DMA registers through COP0 (as microcode does):
+// - Writing a length while a DMA is running queues up the new DMA. DMA_FULL is set until the queued DMA starts
+// - DMA_BUSY stays set until all DMAs are done
+// - Length, count and skip behave exactly like for a DMA that was started by the CPU
+
+/// Number of words that are compared after a strided DMA (0x400 bytes at the start of DMEM)
+const COMPARE_WORDS: usize = 0x100;
+
+const fn pattern(i: usize) -> u32 {
+    (0x0101_0101u32.wrapping_mul(i as u32)) ^ 0xA5C3_0F00
+}
+
+const fn length_register(length: u32, count: u32, skip: u32) -> u32 {
+    (length - 1) | ((count - 1) << 12) | (skip << 20)
+}
+
+pub struct RSPDMAQueued {}
+
+impl Test for RSPDMAQueued {
+    fn name(&self) -> &str { "spmem: DMA RDRAM -> DMEM started by RSP (two queued back-to-back)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let mut source = UncachedHeapMemory::<u32>::new(0x80);
+        for i in 0..0x80 {
+            source.write(i, pattern(i));
+        }
+        for i in 0..0x100 {
+            SPMEM::write(i << 2, 0xBADDECAF);
+        }
+        let dram = source.start_phyiscal() as u32;
+
+        let mut assembler = RSPAssembler::new(0);
+        assembler.write_li(GPR::S0, 0x100);
+        assembler.write_li(GPR::S1, dram);
+        assembler.write_li(GPR::S2, length_register(0x100, 1, 0));
+        assembler.write_li(GPR::S3, 0x200);
+        assembler.write_li(GPR::S4, dram + 0x100);
+        assembler.write_dma_to_spmem(GPR::S0, GPR::S1, GPR::S2);
+        assembler.write_dma_to_spmem(GPR::S3, GPR::S4, GPR::S2);
+        assembler.write_mfc0(CP0Register::DmaFull, GPR::T0);
+        assembler.write_mfc0(CP0Register::DmaBusy, GPR::T1);
+        assembler.write_wait_for_dma_not_full(GPR::A0);
+        assembler.write_wait_for_dma_idle(GPR::A0);
+        assembler.write_mfc0(CP0Register::DmaFull, GPR::T2);
+        assembler.write_mfc0(CP0Register::DmaBusy, GPR::T3);
+        assembler.write_mfc0(CP0Register::SPAddress, GPR::T4);
+        assembler.write_mfc0(CP0Register::DRAMAddress, GPR::T5);
+        assembler.write_sw(GPR::T0, GPR::R0, 0x0);
+        assembler.write_sw(GPR::T1, GPR::R0, 0x4);
+        assembler.write_sw(GPR::T2, GPR::R0, 0x8);
+        assembler.write_sw(GPR::T3, GPR::R0, 0xC);
+        assembler.write_sw(GPR::T4, GPR::R0, 0x10);
+        assembler.write_sw(GPR::T5, GPR::R0, 0x14);
+        assembler.write_break();
+
+        RSP::run_and_wait(0);
+
+        soft_assert_eq(SPMEM::read(0x0), 1, "DMA_FULL right after queueing a second DMA")?;
+        soft_assert_eq(SPMEM::read(0x4), 1, "DMA_BUSY right after queueing a second DMA")?;
+        soft_assert_eq(SPMEM::read(0x8), 0, "DMA_FULL after waiting for DMA_BUSY")?;
+        soft_assert_eq(SPMEM::read(0xC), 0, "DMA_BUSY after waiting for DMA_BUSY")?;
+        soft_assert_eq(SPMEM::read(0x10), 0x300, "SP address after both DMAs")?;
+        soft_assert_eq(SPMEM::read(0x14), dram + 0x200, "DRAM address after both DMAs")?;
+        for i in 0..0x80 {
+            soft_assert_eq2(SPMEM::read(0x100 + (i << 2)), pattern(i), || format!("DMEM[0x{:x}] after both DMAs", 0x100 + (i << 2)))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// (name, towards SPMEM, value of SP_RD_LEN/SP_WR_LEN)
+const STRIDED_CASES: [(&str, bool, u32); 8] = [
+    ("RDRAM -> DMEM: 1 row of 0x40", true, length_register(0x40, 1, 0)),
+    ("RDRAM -> DMEM: 8 rows of 0x10, no skip", true, length_register(0x10, 8, 0)),
+    ("RDRAM -> DMEM: 4 rows of 0x20, skip 0x10", true, length_register(0x20, 4, 0x10)),
+    ("RDRAM -> DMEM: 3 rows of 0x18, skip 0x28", true, length_register(0x18, 3, 0x28)),
+    ("RDRAM -> DMEM: 2 rows of 0xB, skip 0x5", true, length_register(0xB, 2, 0x5)),
+    ("RDRAM <- DMEM: 4 rows of 0x20, skip 0x10", false, length_register(0x20, 4, 0x10)),
+    ("RDRAM <- DMEM: 3 rows of 0x18, skip 0x28", false, length_register(0x18, 3, 0x28)),
+    ("RDRAM <- DMEM: 2 rows of 0xB, skip 0x5", false, length_register(0xB, 2, 0x5)),
+];
+
+/// What is left after a DMA: The destination memory, SP address and DRAM address (relative to the RDRAM buffer)
+struct DMAResult {
+    memory: [u32; COMPARE_WORDS],
+    sp_address: u32,
+    dram_offset: u32,
+}
+
+/// Fills the source with a pattern and the destination with garbage, runs a DMA between DMEM 0 and RDRAM and
+/// returns the destination
+fn run_dma(by_rsp: bool, to_spmem: bool, length: u32) -> DMAResult {
+    let mut rdram = UncachedHeapMemory::<u32>::new_with_init_value(COMPARE_WORDS, 0xBADDECAF);
+    for i in 0..COMPARE_WORDS {
+        if to_spmem {
+            rdram.write(i, pattern(i));
+            SPMEM::write(i << 2, 0xBADDECAF);
+        } else {
+            SPMEM::write(i << 2, pattern(i));
+        }
+    }
+
+    if by_rsp {
+        let mut assembler = RSPAssembler::new(0);
+        assembler.write_li(GPR::S0, 0);
+        assembler.write_li(GPR::S1, rdram.start_phyiscal() as u32);
+        assembler.write_li(GPR::S2, length);
+        if to_spmem {
+            assembler.write_dma_to_spmem(GPR::S0, GPR::S1, GPR::S2);
+        } else {
+            assembler.write_dma_from_spmem(GPR::S0, GPR::S1, GPR::S2);
+        }
+        assembler.write_wait_for_dma_idle(GPR::A0);
+        assembler.write_break();
+
+        RSP::run_and_wait(0);
+    } else {
+        if to_spmem {
+            RSP::start_dma_cpu_to_sp(rdram.as_ptr() as *const u8, 0, length);
+        } else {
+            unsafe { RSP::start_dma_sp_to_cpu(0, rdram.as_ptr() as *mut u8, length); }
+        }
+        RSP::wait_until_dma_completed();
+    }
+
+    let mut memory = [0u32; COMPARE_WORDS];
+    for i in 0..COMPARE_WORDS {
+        memory[i] = if to_spmem { SPMEM::read(i << 2) } else { rdram.read(i) };
+    }
+
+    // The CPU passes a virtual address, the RSP a physical one. Only the lower 24 bits are relevant
+    let dram_offset = RSP::dram_address().wrapping_sub(rdram.start_phyiscal() as u32) & 0xFF_FFFF;
+    DMAResult { memory, sp_address: RSP::sp_address(), dram_offset }
+}
+
+pub struct RSPDMAStridedMatchesCPU {}
+
+impl Test for RSPDMAStridedMatchesCPU {
+    fn name(&self) -> &str { "spmem: DMA started by RSP matches DMA started by CPU (count/skip)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        STRIDED_CASES.iter().map(|case| NamedValue::boxed(case.0, *case)).collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (_, to_spmem, length) = *NamedValue::get::<(&'static str, bool, u32)>(value);
+
+        let cpu = run_dma(false, to_spmem, length);
+        let rsp = run_dma(true, to_spmem, length);
+
+        let memory_name = if to_spmem { "DMEM" } else { "RDRAM" };
+        for i in 0..COMPARE_WORDS {
+            soft_assert_eq2(rsp.memory[i], cpu.memory[i], || format!("{}[0x{:x}] after DMA started by RSP (left) vs CPU (right)", memory_name, i << 2))?;
+        }
+        soft_assert_eq(rsp.sp_address, cpu.sp_address, "SP address after DMA started by RSP (left) vs CPU (right)")?;
+        soft_assert_eq(rsp.dram_offset, cpu.dram_offset, "DRAM address (relative to the buffer) after DMA started by RSP (left) vs CPU (right)")?;
+
+        Ok(())
+    }
+}
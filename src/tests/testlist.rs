@@ -35,6 +35,8 @@ fn append_stress_tests(_target: &mut Vec<Box<dyn Test>>) {
     _target.push(Box::new(super::rsp::stresstests_div::VRCP32 {}));
     #[cfg(feature = "vrsq32_stress_test")]
     _target.push(Box::new(super::rsp::stresstests_div::VRSQ32 {}));
+    #[cfg(feature = "vcompare_stress_test")]
+    _target.push(Box::new(super::rsp::stresstests_compare::VectorCompareStresstest {}));
     #[cfg(feature = "rcp_rsq_dump")]
     _target.push(Box::new(super::rsp::op_vmov_vrcp::GenerateDump {}));
     #[cfg(feature = "cop1_stress_test")]
@@ -52,6 +54,13 @@ fn append_stress_tests(_target: &mut Vec<Box<dyn Test>>) {
         _target.push(Box::new(super::cop1::randomized::StresstestCvtSFromW {}));
         _target.push(Box::new(super::cop1::randomized::StresstestCvtWFromS {}));
     }
+    #[cfg(feature = "mult_stress_test")]
+    {
+        _target.push(Box::new(super::arithmetic::mult::StresstestMULT {}));
+        _target.push(Box::new(super::arithmetic::mult::StresstestMULTU {}));
+        _target.push(Box::new(super::arithmetic::mult::StresstestDMULT {}));
+        _target.push(Box::new(super::arithmetic::mult::StresstestDMULTU {}));
+    }
 }
 
 #[cfg(feature = "quick")]
@@ -67,6 +76,10 @@ fn default_tests() -> Vec<Box<dyn Test>> {
         Box::new(super::address_error_exception::UnalignedJumpWithDelaySlot {}),
         Box::new(super::address_error_exception::LWAddressNotSignExtended {}),
         Box::new(super::address_error_exception::SWAddressNotSignExtended {}),
+        Box::new(super::ai::DMAFifoFullAndBusy {}),
+        Box::new(super::ai::LengthDecrements {}),
+        Box::new(super::ai::LengthMasking {}),
+        Box::new(super::ai::InterruptOnBuffer {}),
         Box::new(super::arithmetic::nemu_port::LUIOpcodeTest1 {}),
         Box::new(super::arithmetic::nemu_port::LUIOpcodeTest2 {}),
         Box::new(super::arithmetic::nemu_port::LUIOpcodeTestIntoR0 {}),
@@ -446,6 +459,18 @@ fn default_tests() -> Vec<Box<dyn Test>> {
         Box::new(super::arithmetic::div::DIVU {}),
         Box::new(super::arithmetic::div::DDIV {}),
         Box::new(super::arithmetic::div::DDIVU {}),
+        Box::new(super::arithmetic::mult::MULT {}),
+        Box::new(super::arithmetic::mult::MULTU {}),
+        Box::new(super::arithmetic::mult::MULTNotSignExtended {}),
+        Box::new(super::arithmetic::mult::DMULT {}),
+        Box::new(super::arithmetic::mult::DMULTU {}),
+        Box::new(super::arithmetic::mult::MTHIMTLO {}),
+        Box::new(super::arithmetic::mult::MFLOFollowedByMULT {}),
+        Box::new(super::arithmetic::mult::MTLOAfterMULT {}),
+        Box::new(super::arithmetic::mult::MULTRandomized),
+        Box::new(super::arithmetic::mult::MULTURandomized),
+        Box::new(super::arithmetic::mult::DMULTRandomized),
+        Box::new(super::arithmetic::mult::DMULTURandomized),
         Box::new(super::arithmetic::ll_sc::LL {}),
         Box::new(super::arithmetic::ll_sc::SC {}),
         Box::new(super::arithmetic::ll_sc::LLD {}),
@@ -453,6 +478,12 @@ fn default_tests() -> Vec<Box<dyn Test>> {
         Box::new(super::arithmetic::ll_sc::SCAliasOnSamePhysicalViaTLB {}),
         Box::new(super::arithmetic::ll_sc::SCAfterERET {}),
         Box::new(super::arithmetic::ll_sc::SCDAfterERET {}),
+        Box::new(super::partial_load_store::LWLLWR {}),
+        Box::new(super::partial_load_store::LDLLDR {}),
+        Box::new(super::partial_load_store::SWLSWR {}),
+        Box::new(super::partial_load_store::SDLSDR {}),
+        Box::new(super::partial_load_store::SWLSWRSPMEM {}),
+        Box::new(super::partial_load_store::AddressNotSignExtended {}),
         Box::new(super::cart_memory::LW {}),
         Box::new(super::cart_memory::LH {}),
         Box::new(super::cart_memory::LB {}),
@@ -475,6 +506,9 @@ fn default_tests() -> Vec<Box<dyn Test>> {
         Box::new(super::cart_memory::dma::PIDMAMisaligned {}),
         Box::new(super::cart_memory::dma::PIDMAMisalignedCrossPage {}),
         Box::new(super::cart_memory::dma::PIDMAMisalignedEndOfPage {}),
+        Box::new(super::bus_error::UnmappedLoad {}),
+        Box::new(super::bus_error::UnmappedStore {}),
+        Box::new(super::bus_error::UnmappedInstructionFetch {}),
         Box::new(super::cop0::IndexMasking),
         Box::new(super::cop0::RandomDecrement),
         Box::new(super::cop0::RandomMasking),
@@ -495,6 +529,15 @@ fn default_tests() -> Vec<Box<dyn Test>> {
         Box::new(super::cop0::UnusedRegistersExtraMtc0),
         Box::new(super::cop0::UnusedRegistersExtraUnrelated),
         Box::new(super::cop0::UnusedRegistersWriteRead),
+        Box::new(super::cop0::CompareReadWrite {}),
+        Box::new(super::cop0::CompareMatchSetsIP7 {}),
+        Box::new(super::cop0::CompareWriteClearsIP7 {}),
+        Box::new(super::cop0::CountIncrementRate {}),
+        Box::new(super::cop0::TimerInterrupt {}),
+        Box::new(super::cop0::TimerInterruptMasked {}),
+        Box::new(super::cop0::WatchLoHiMasking {}),
+        Box::new(super::cop0::WatchException {}),
+        Box::new(super::cop0::WatchExceptionSuppressedByEXL {}),
         Box::new(super::cop0::ParityErrorMasking),
         Box::new(super::cop0::CacheErrorMasking),
         Box::new(super::cop0::IcacheStoreTagThenLoadTag {}),
@@ -559,6 +602,7 @@ fn default_tests() -> Vec<Box<dyn Test>> {
         Box::new(super::cop1::CvtD),
         Box::new(super::cop1::ConvertToW),
         Box::new(super::cop1::ConvertToL),
+        Box::new(super::cop1::unimplemented::UnimplementedOperationMatrix {}),
         Box::new(super::cop1::compares::C_F),
         Box::new(super::cop1::compares::C_UN),
         Box::new(super::cop1::compares::C_EQ),
@@ -634,6 +678,10 @@ fn default_tests() -> Vec<Box<dyn Test>> {
         Box::new(super::exception_instructions::SyscallDelay {}),
         Box::new(super::exception_instructions::Reserved31 {}),
         Box::new(super::exception_instructions::Reserved31Delay {}),
+        Box::new(super::exception_instructions::reserved::ReservedInstructionSweep {}),
+        Box::new(super::exception_instructions::reserved::COP1UnimplementedSweep {}),
+        Box::new(super::exception_instructions::reserved::COP2Sweep {}),
+        Box::new(super::exception_instructions::reserved::OddEncodingsDontTrap {}),
         Box::new(super::jumps::conditionals::BEQWithinDelay {}),
         Box::new(super::jumps::conditionals::BEQNotTakenWithinDelay {}),
         Box::new(super::jumps::conditionals::BEQWithinDelayOfJR {}),
@@ -671,6 +719,11 @@ fn default_tests() -> Vec<Box<dyn Test>> {
         Box::new(super::overflow_exception::AddImmediateOverflowIntoR0 {}),
         Box::new(super::overflow_exception::DoubleAddImmediateOverflow {}),
         Box::new(super::overflow_exception::DoubleAddImmediateOverflowIntoR0 {}),
+        Box::new(super::mi::interrupts::MaskRegister {}),
+        Box::new(super::mi::interrupts::PendingWithoutDelivery {}),
+        Box::new(super::mi::interrupts::Delivery {}),
+        Box::new(super::mi::interrupts::NotDeliveredWhenMasked {}),
+        Box::new(super::mi::interrupts::DeliveryInDelaySlot {}),
         Box::new(super::mi::repeat::SB {}),
         Box::new(super::mi::repeat::SH {}),
         Box::new(super::mi::repeat::SW {}),
@@ -685,6 +738,19 @@ fn default_tests() -> Vec<Box<dyn Test>> {
         Box::new(super::pif_memory::SB3 {}),
         Box::new(super::pif_memory::LB {}),
         Box::new(super::pif_memory::LH {}),
+        Box::new(super::si::DMARoundTrip {}),
+        Box::new(super::si::ControllerStatus {}),
+        Box::new(super::si::ControllerReset {}),
+        Box::new(super::si::ControllerReadButtons {}),
+        Box::new(super::si::AbsentDevices {}),
+        Box::new(super::si::ChannelSkipAndPadding {}),
+        Box::new(super::si::EndOfCommands {}),
+        Box::new(super::si::EepromInfoReadWrite {}),
+        Box::new(super::vi::RegisterMasking {}),
+        Box::new(super::vi::CurrentAdvancesAndWraps {}),
+        Box::new(super::vi::FieldToggles {}),
+        Box::new(super::vi::VIntrRaisesInterrupt {}),
+        Box::new(super::vi::CurrentWriteAcknowledges {}),
 
         Box::new(super::rdp::StartAndEndMasking {}),
         Box::new(super::rdp::StartIsValidFlag {}),
@@ -692,6 +758,31 @@ fn default_tests() -> Vec<Box<dyn Test>> {
         Box::new(super::rdp::RunFromDMEM {}),
         Box::new(super::rdp::RunFromDMEMEnd {}),
         Box::new(super::rdp::RunFromDMEMOverflow {}),
+        Box::new(super::rdp::xbus::RunFromDMEMWrittenByRSP {}),
+        Box::new(super::rdp::xbus::QueueSecondListFromRSP {}),
+        Box::new(super::rdp::texture_rect::TextureRectangleCopyRGBA16 {}),
+        Box::new(super::rdp::texture_rect::TextureRectangleCopyOffset {}),
+        Box::new(super::rdp::texture_rect::TextureRectangleCopyCI8 {}),
+        Box::new(super::rdp::texture_rect::TextureRectangleCopyI8 {}),
+        Box::new(super::rdp::texture_rect::TextureRectangleCopyIA8 {}),
+        Box::new(super::rdp::texture_rect::TextureRectangleCopyCI8WithoutTLUT {}),
+        Box::new(super::rdp::texture_rect::TextureRectangleCopyIA16 {}),
+        Box::new(super::rdp::texture_rect::TextureRectangle1Cycle {}),
+        Box::new(super::rdp::texture_rect::TextureRectangleFlipped1Cycle {}),
+        Box::new(super::rdp::texture_rect::TextureRectangle1CycleCI4 {}),
+        Box::new(super::rdp::depth::ShadedTriangle {}),
+        Box::new(super::rdp::depth::DepthTriangle {}),
+        Box::new(super::rdp::depth::ShadedDepthTriangle {}),
+        Box::new(super::rdp::depth::PrimitiveDepth {}),
+        Box::new(super::rdp::combiner::Combiner1Cycle {}),
+        Box::new(super::rdp::combiner::Combiner2Cycle {}),
+        Box::new(super::rdp::combiner::CombinerAlpha2Cycle {}),
+        Box::new(super::rdp::blender::Blender1Cycle {}),
+        Box::new(super::rdp::blender::Blender2Cycle {}),
+        Box::new(super::rdp::fill::FillRectangle {}),
+        Box::new(super::rdp::fill::FillRectangleUnaligned {}),
+        Box::new(super::rdp::fill::FillRectangleScissor {}),
+        Box::new(super::rdp::fill::HiddenBits8BitWrite {}),
 
         // The following are disabled for the time being as they are not stable on hardware yet
         // Box::new(super::rdp::filled_triangle::FilledTriangle1CycleDegenerateRect {}),
@@ -856,6 +947,7 @@ fn default_tests() -> Vec<Box<dyn Test>> {
         Box::new(super::rsp::op_vector_arithmetic::VCL {}),
         Box::new(super::rsp::op_vector_arithmetic::VCH {}),
         Box::new(super::rsp::op_vector_arithmetic::VCR {}),
+        Box::new(super::rsp::stresstests_compare::VectorCompareRandomized {}),
         Box::new(super::rsp::op_vsar::VSAR {}),
         Box::new(super::rsp::op_vmacf::VMACFAll {}),
         Box::new(super::rsp::op_vmacf::VMACFH0 {}),
@@ -940,6 +1032,9 @@ fn default_tests() -> Vec<Box<dyn Test>> {
         Box::new(super::rsp::registers::SemaphoreRegisterRSPOnly {}),
         Box::new(super::rsp::registers::SemaphoreRegisterMixed {}),
         Box::new(super::rsp::registers::RSPHaltItselfWithoutBreak {}),
+        Box::new(super::rsp::registers::SignalMailbox {}),
+        Box::new(super::rsp::registers::SetClearSignalFromRSP {}),
+        Box::new(super::rsp::registers::BreakSetsBroke {}),
         Box::new(super::sp_memory::SW {}),
         Box::new(super::sp_memory::SWOutOfBounds {}),
         Box::new(super::sp_memory::SH {}),
@@ -962,6 +1057,8 @@ fn default_tests() -> Vec<Box<dyn Test>> {
         Box::new(super::sp_memory::dma::SPDMAFromIMEMWithOverflowByCount {}),
         Box::new(super::sp_memory::dma::SPDMAMultiRowDMEMRoundtrip {}),
         Box::new(super::sp_memory::dma::SPDMAMultiRowIMEMRoundtrip {}),
+        Box::new(super::sp_memory::rsp_dma::RSPDMAQueued {}),
+        Box::new(super::sp_memory::rsp_dma::RSPDMAStridedMatchesCPU {}),
 
         Box::new(super::tlb::WiredRandom {}),
         Box::new(super::tlb::WiredOutOfBoundsRandom {}),
@@ -1009,6 +1106,8 @@ fn default_tests() -> Vec<Box<dyn Test>> {
         Box::new(super::tlb::exceptions::StoreNonDirty4k {}),
         Box::new(super::tlb::exceptions::StoreNonDirtyAndNonValid4k {}),
         Box::new(super::tlb::exceptions::LWTLBMissTest32 {}),
+        Box::new(super::tlb::vectors::ExceptionVectorSelection {}),
+        Box::new(super::tlb::vectors::BootstrapExceptionVectors {}),
         Box::new(super::tlb64::AllLoads32BitAddress {}),
         Box::new(super::tlb64::AllLoads32BitAddressUncached {}),
         Box::new(super::tlb64::AllLoads0x90 {}),
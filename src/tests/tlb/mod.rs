@@ -15,6 +15,7 @@ use crate::tests::soft_asserts::{soft_assert_eq, soft_assert_greater_or_equal, s
 
 pub mod cross_page_exec;
 pub mod exceptions;
+pub mod vectors;
 
 // TODO: TLBWR
 
@@ -0,0 +1,212 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::arch::asm;
+use arbitrary_int::{u2, u27};
+
+use crate::assembler::{Assembler, GPR};
+use crate::cop0;
+use crate::cop0::{CauseException, make_entry_hi, make_entry_lo, Status, StatusKSU};
+use crate::exception_handler::{BOOTSTRAP_TRAMPOLINE_VECTOR, clear_exception_return_override, ExceptionContext, expect_exception, install_bootstrap_trampoline, set_exception_status_override, uninstall_bootstrap_trampoline};
+use crate::tests::{Level, NamedValue, Test};
+use crate::tests::privilege::{run_mode_program_with_status, setup_program};
+use crate::tests::soft_asserts::soft_assert_eq2;
+
+// Which exception vector a TLB exception uses:
+// - A TLB miss (no matching entry) with EXL=0 uses the TLB refill vector 0x000 in 32 bit mode and the XTLB refill
+//   vector 0x080 in 64 bit mode. The mode is that of the current privilege level (KX in kernel mode, SX in
+//   supervisor mode and UX in user mode), not the segment of the address: A miss in ckseg2 with KX=1 goes to 0x080
+// - A TLB invalid exception (matching entry, but V=0) always uses the general vector 0x180
+// - With EXL=1, a miss also goes to 0x180. ExceptPC isn't updated in that case
+// - With BEV=1, the vectors move to 0xBFC00200/0xBFC00280/0xBFC00380. These are in PIF ROM, which reads back as zero
+//   after boot, so the CPU slides through up to 368 NOPs into the trampoline in PIFRAM. All three vectors end up
+//   there, so the BEV test only checks that the exception reaches PIF space - it can't tell the vectors apart
+
+const ADDRESS: u64 = 0x0000_1000;
+const ADDRESS_KSEG2: u64 = 0xFFFFFFFF_C000_1000;
+
+const VECTOR_TLB_REFILL: u64 = 0xFFFFFFFF_80000000;
+const VECTOR_XTLB_REFILL: u64 = 0xFFFFFFFF_80000080;
+const VECTOR_GENERAL: u64 = 0xFFFFFFFF_80000180;
+
+/// (name, Status, address, TLB entry present but invalid, EXL set before the access, expected vector)
+type KernelCase = (&'static str, Status, u64, bool, bool, u64);
+
+const KERNEL_CASES: [KernelCase; 9] = [
+    ("kernel 32 bit: TLB miss in kuseg", Status::DEFAULT, ADDRESS, false, false, VECTOR_TLB_REFILL),
+    ("kernel 32 bit: TLB miss in kseg2", Status::DEFAULT, ADDRESS_KSEG2, false, false, VECTOR_TLB_REFILL),
+    ("kernel 64 bit (KX): TLB miss in xkuseg", Status::DEFAULT.with_kx(true), ADDRESS, false, false, VECTOR_XTLB_REFILL),
+    ("kernel 64 bit (KX): TLB miss in ckseg2", Status::DEFAULT.with_kx(true), ADDRESS_KSEG2, false, false, VECTOR_XTLB_REFILL),
+    ("kernel with SX and UX but not KX: TLB miss", Status::DEFAULT.with_sx(true).with_ux(true), ADDRESS, false, false, VECTOR_TLB_REFILL),
+    ("kernel 32 bit: TLB invalid", Status::DEFAULT, ADDRESS, true, false, VECTOR_GENERAL),
+    ("kernel 64 bit (KX): TLB invalid", Status::DEFAULT.with_kx(true), ADDRESS, true, false, VECTOR_GENERAL),
+    ("kernel 32 bit: TLB miss with EXL=1", Status::DEFAULT, ADDRESS, false, true, VECTOR_GENERAL),
+    ("kernel 64 bit (KX): TLB miss with EXL=1", Status::DEFAULT.with_kx(true), ADDRESS, false, true, VECTOR_GENERAL),
+];
+
+/// (name, mode, KX, SX, UX, expected vector). The access is a TLB miss at [ADDRESS]
+type UserCase = (&'static str, StatusKSU, bool, bool, bool, u64);
+
+const USER_CASES: [UserCase; 4] = [
+    ("user with KX and SX but not UX: TLB miss", StatusKSU::User, true, true, false, VECTOR_TLB_REFILL),
+    ("user with UX only: TLB miss", StatusKSU::User, false, false, true, VECTOR_XTLB_REFILL),
+    ("supervisor with KX and UX but not SX: TLB miss", StatusKSU::Supervisor, true, false, true, VECTOR_TLB_REFILL),
+    ("supervisor with SX only: TLB miss", StatusKSU::Supervisor, false, true, false, VECTOR_XTLB_REFILL),
+];
+
+/// The two kinds of cases that ExceptionVectorSelection runs
+#[derive(Clone, Copy)]
+enum Case {
+    Kernel(KernelCase),
+    User(UserCase),
+}
+
+/// Loads from address in kernel mode and returns the context of the TLBL exception. If tlb_invalid is set, there's
+/// an invalid TLB entry for the address; otherwise the TLB is empty. If exl is set, Status.EXL is set right before
+/// the load, with ExceptPC pointing to the load
+fn load_in_kernel_mode(status: Status, address: u64, tlb_invalid: bool, exl: bool) -> Result<(ExceptionContext, u64), String> {
+    unsafe {
+        cop0::clear_tlb();
+        if tlb_invalid {
+            cop0::write_tlb(
+                10,
+                0,
+                make_entry_lo(true, false, false, 0, 0),
+                make_entry_lo(true, false, false, 0, 0),
+                make_entry_hi(0, u27::extract_u64(address, 13), u2::extract_u64(address, 62)));
+        }
+        cop0::set_context_64(0);
+        cop0::set_xcontext_64(0);
+        cop0::set_status(status);
+    }
+
+    let mut load_pc = 0u64;
+    let exception_context = expect_exception(CauseException::TLBL, 1, || {
+        unsafe {
+            if exl {
+                asm!("
+                    .set noat
+                    .set noreorder
+                    LD $2, 0 ($3)
+                    LA $4, 2f
+                    DMTC0 $4, $14
+                    MFC0 $5, $12
+                    ORI $5, $5, 2
+                    MTC0 $5, $12
+                    NOP
+                    NOP
+                    2:
+                    LW $0, 0 ($2)
+                    SD $4, 0 ($6)
+                ", in("$3") &address, in("$6") &mut load_pc, out("$2") _, out("$4") _, out("$5") _)
+            } else {
+                asm!("
+                    .set noat
+                    LD $2, 0 ($3)
+                    LW $0, 0 ($2)
+                ", in("$3") &address, out("$2") _)
+            }
+        }
+
+        Ok(())
+    })?;
+
+    Ok((exception_context, load_pc))
+}
+
+fn run_kernel_case(case: KernelCase, bootstrap_vectors: bool) -> Result<(), String> {
+    let (name, status, address, tlb_invalid, exl, expected_vector) = case;
+
+    let (exception_context, load_pc) = if bootstrap_vectors {
+        let saved = install_bootstrap_trampoline();
+        // Return without BEV, so that the next exception goes to the regular handler again
+        set_exception_status_override(Status::DEFAULT.raw_value());
+        let result = load_in_kernel_mode(status.with_tlb_miss_vectors(true), address, tlb_invalid, exl);
+        clear_exception_return_override();
+        uninstall_bootstrap_trampoline(saved);
+        result?
+    } else {
+        load_in_kernel_mode(status, address, tlb_invalid, exl)?
+    };
+
+    if bootstrap_vectors {
+        soft_assert_eq2(exception_context.k0_exception_vector, BOOTSTRAP_TRAMPOLINE_VECTOR, || format!("'{}' with BEV is expected to run into the trampoline in PIFRAM", name))?;
+    } else {
+        soft_assert_eq2(exception_context.k0_exception_vector, expected_vector, || format!("Exception vector for '{}'", name))?;
+    }
+    soft_assert_eq2(exception_context.badvaddr, address, || format!("BadVAddr for '{}'", name))?;
+    if exl {
+        soft_assert_eq2(exception_context.exceptpc, load_pc, || format!("ExceptPC for '{}' should be the value that was set before the exception", name))?;
+    }
+
+    Ok(())
+}
+
+fn run_user_case(case: UserCase) -> Result<(), String> {
+    let (name, mode, kx, sx, ux, expected_vector) = case;
+
+    unsafe {
+        cop0::set_context_64(0);
+        cop0::set_xcontext_64(0);
+    }
+    let program = [
+        Assembler::make_lui(GPR::T0, (ADDRESS >> 16) as u16),
+        Assembler::make_ori(GPR::T0, GPR::T0, ADDRESS as u16),
+        Assembler::make_lb(GPR::T1, 0, GPR::T0),
+        Assembler::make_syscall(0x2a1),
+    ];
+    let (_backing, entry) = setup_program(&program, 0)?;
+    let status = Status::DEFAULT
+        .with_ksu(mode)
+        .with_exl(true)
+        .with_kx(kx)
+        .with_sx(sx)
+        .with_ux(ux);
+    let exception_context = run_mode_program_with_status(status, entry, CauseException::TLBL, 1)?;
+
+    soft_assert_eq2(exception_context.k0_exception_vector, expected_vector, || format!("Exception vector for '{}'", name))?;
+    soft_assert_eq2(exception_context.badvaddr, ADDRESS, || format!("BadVAddr for '{}'", name))?;
+
+    Ok(())
+}
+
+pub struct ExceptionVectorSelection {}
+
+impl Test for ExceptionVectorSelection {
+    fn name(&self) -> &str { "TLB: Exception vector selection (refill, XTLB refill, general)" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        KERNEL_CASES.iter().map(|case| NamedValue::boxed(case.0, Case::Kernel(*case)))
+            .chain(USER_CASES.iter().map(|case| NamedValue::boxed(case.0, Case::User(*case))))
+            .collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        match *NamedValue::get::<Case>(value) {
+            Case::Kernel(case) => run_kernel_case(case, false),
+            Case::User(case) => run_user_case(case),
+        }
+    }
+}
+
+/// Status.BEV moves all vectors into PIF ROM. Which of the vectors was used isn't visible, only that the exception
+/// reached PIF space
+pub struct BootstrapExceptionVectors {}
+
+impl Test for BootstrapExceptionVectors {
+    fn name(&self) -> &str { "TLB: Exceptions with Status.BEV reach PIF space" }
+
+    fn level(&self) -> Level { Level::Weird }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        KERNEL_CASES.iter().map(|case| NamedValue::boxed(case.0, *case)).collect()
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        run_kernel_case(*NamedValue::get::<KernelCase>(value), true)
+    }
+}
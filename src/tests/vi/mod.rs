@@ -0,0 +1,208 @@
+use alloc::boxed::Box;
+use alloc::{format, vec};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+
+use crate::cop0;
+use crate::graphics::vi::{RegisterOffset, Video};
+use crate::mi;
+use crate::tests::{COUNT_PER_MILLISECOND, Level, NamedValue, Test, wait_for};
+use crate::tests::soft_asserts::{soft_assert_eq, soft_assert_eq2};
+
+// Notes:
+// - VI_V_CURRENT counts half-lines. Bit 0 is the field, which toggles every frame when serrate (interlace) is on
+// - VI_V_CURRENT wraps when it reaches VI_V_SYNC
+// - The VI interrupt is raised when VI_V_CURRENT reaches VI_V_INTR. Writing VI_V_CURRENT acknowledges it
+// - VI_CTRL is not tested for masking: Setting bit 5 (vbus_clock_enable) can damage the console
+
+/// A frame takes 16.7ms (NTSC) or 20ms (PAL). Use a generous timeout
+const FRAME_TIMEOUT_MILLISECONDS: u32 = 50;
+
+fn current() -> u32 { Video::register(RegisterOffset::Current) }
+
+fn acknowledge_interrupt() { Video::set_register(RegisterOffset::Current, 0); }
+
+pub struct RegisterMasking {}
+
+impl Test for RegisterMasking {
+    fn name(&self) -> &str { "VI: Register masking" }
+
+    fn level(&self) -> Level { Level::RarelyUsed }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        vec! {
+            NamedValue::boxed("DRAMAddress", (RegisterOffset::DRAMAddress, 0x00FF_FFFFu32)),
+            NamedValue::boxed("HWidth", (RegisterOffset::HWidth, 0x0000_0FFFu32)),
+            NamedValue::boxed("VIntr", (RegisterOffset::VIntr, 0x0000_03FFu32)),
+            NamedValue::boxed("Timing", (RegisterOffset::Timing, 0x3FFF_FFFFu32)),
+            NamedValue::boxed("VSync", (RegisterOffset::VSync, 0x0000_03FFu32)),
+            NamedValue::boxed("HSync", (RegisterOffset::HSync, 0x001F_0FFFu32)),
+            NamedValue::boxed("HSyncLeap", (RegisterOffset::HSyncLeap, 0x0FFF_0FFFu32)),
+            NamedValue::boxed("HVideo", (RegisterOffset::HVideo, 0x03FF_03FFu32)),
+            NamedValue::boxed("VVideo", (RegisterOffset::VVideo, 0x03FF_03FFu32)),
+            NamedValue::boxed("VBurst", (RegisterOffset::VBurst, 0x03FF_03FFu32)),
+            NamedValue::boxed("XScale", (RegisterOffset::XScale, 0x0FFF_0FFFu32)),
+            NamedValue::boxed("YScale", (RegisterOffset::YScale, 0x0FFF_0FFFu32)),
+        }
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let (register, mask) = *NamedValue::get::<(RegisterOffset, u32)>(value);
+        let original = Video::register(register);
+
+        let mut result = Ok(());
+        for written in [0xFFFF_FFFFu32, 0x1234_5678, 0xA5A5_A5A5, 0] {
+            Video::set_register(register, written);
+            let read = Video::register(register);
+            result = soft_assert_eq2(read, written & mask, || format!("{:?} after writing {:#010x}", register, written));
+            if result.is_err() {
+                break;
+            }
+        }
+
+        // The display is still running, so put back what was there
+        Video::set_register(register, original);
+        result
+    }
+}
+
+pub struct CurrentAdvancesAndWraps {}
+
+impl Test for CurrentAdvancesAndWraps {
+    fn name(&self) -> &str { "VI: VI_V_CURRENT advances and wraps at VI_V_SYNC" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        let v_sync = Video::register(RegisterOffset::VSync);
+        let start = cop0::count();
+        let mut previous = current();
+        let mut wraps = 0;
+        let mut max = previous;
+        // Observe a bit more than two frames
+        while cop0::count().wrapping_sub(start) < 2 * FRAME_TIMEOUT_MILLISECONDS * COUNT_PER_MILLISECOND {
+            let value = current();
+            soft_assert_eq2(value < v_sync, true, || format!("VI_V_CURRENT ({:#x}) is expected to be below VI_V_SYNC ({:#x})", value, v_sync))?;
+            if value < previous {
+                wraps += 1;
+            } else if value != previous {
+                // Only whole lines are counted (in half-line units), so the line part advances by 2
+                soft_assert_eq2((value & !1) - (previous & !1), 2, || format!("VI_V_CURRENT was expected to advance by 2 half-lines, but went from {:#x} to {:#x}", previous, value))?;
+            }
+            if value > max {
+                max = value;
+            }
+            previous = value;
+        }
+        soft_assert_eq2(wraps >= 2, true, || format!("VI_V_CURRENT was expected to wrap at least twice, but it wrapped {} times", wraps))?;
+        soft_assert_eq2(max + 4 >= v_sync, true, || format!("VI_V_CURRENT was expected to get close to VI_V_SYNC ({:#x}) before wrapping, but the largest value seen was {:#x}", v_sync, max))?;
+        Ok(())
+    }
+}
+
+pub struct FieldToggles {}
+
+impl Test for FieldToggles {
+    fn name(&self) -> &str { "VI: VI_V_CURRENT field bit (interlaced)" }
+
+    fn level(&self) -> Level { Level::RarelyUsed }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        // Bit 6 of VI_CTRL: serrate. The framebuffer console runs interlaced
+        soft_assert_eq(Video::register(RegisterOffset::Status) & 0x40, 0x40, "VI_CTRL.serrate is expected to be set")?;
+
+        let mut fields = Vec::new();
+        let start = cop0::count();
+        let mut previous = current();
+        let mut field = previous & 1;
+        while fields.len() < 4 {
+            if cop0::count().wrapping_sub(start) > 6 * FRAME_TIMEOUT_MILLISECONDS * COUNT_PER_MILLISECOND {
+                return Err(format!("Time out waiting for four fields. Fields seen: {:?}", fields));
+            }
+            let value = current();
+            if value < previous {
+                fields.push(value & 1);
+                field = value & 1;
+            } else {
+                soft_assert_eq2(value & 1, field, || format!("The field bit is expected to stay constant within a field. VI_V_CURRENT went from {:#x} to {:#x}", previous, value))?;
+            }
+            previous = value;
+        }
+        for i in 1..fields.len() {
+            soft_assert_eq2(fields[i], fields[i - 1] ^ 1, || format!("The field bit is expected to toggle every field. Fields seen: {:?}", fields))?;
+        }
+        Ok(())
+    }
+}
+
+pub struct VIntrRaisesInterrupt {}
+
+impl Test for VIntrRaisesInterrupt {
+    fn name(&self) -> &str { "VI: VI_V_INTR raises interrupt" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> {
+        vec! {
+            Box::new(0x100u32),
+            Box::new(0x40u32),
+            Box::new(0x1F0u32),
+        }
+    }
+
+    fn run(&self, value: &Box<dyn Any>) -> Result<(), String> {
+        let v_intr = *value.downcast_ref::<u32>().unwrap();
+        let original = Video::register(RegisterOffset::VIntr);
+        Video::set_register(RegisterOffset::VIntr, v_intr);
+
+        let result = (|| {
+            // Get a bit before the interrupt line, then acknowledge any interrupt that is still pending
+            wait_for("VI_V_CURRENT to approach VI_V_INTR", FRAME_TIMEOUT_MILLISECONDS, || {
+                let c = current();
+                c + 0x20 <= v_intr && c + 0x30 >= v_intr
+            })?;
+            acknowledge_interrupt();
+            soft_assert_eq(mi::is_vi_interrupt(), false, "MI_INTR.VI after acknowledging (before reaching VI_V_INTR)")?;
+
+            wait_for("MI_INTR.VI", FRAME_TIMEOUT_MILLISECONDS, || mi::is_vi_interrupt())?;
+            let line = current();
+            soft_assert_eq2((line & !1) >= (v_intr & !1) && line <= v_intr + 2, true, || format!("The VI interrupt is expected to be raised when reaching VI_V_INTR={:#x}, but VI_V_CURRENT was {:#x}", v_intr, line))
+        })();
+
+        Video::set_register(RegisterOffset::VIntr, original);
+        acknowledge_interrupt();
+        result
+    }
+}
+
+pub struct CurrentWriteAcknowledges {}
+
+impl Test for CurrentWriteAcknowledges {
+    fn name(&self) -> &str { "VI: Writing VI_V_CURRENT acknowledges interrupt" }
+
+    fn level(&self) -> Level { Level::BasicFunctionality }
+
+    fn values(&self) -> Vec<Box<dyn Any>> { Vec::new() }
+
+    fn run(&self, _value: &Box<dyn Any>) -> Result<(), String> {
+        // The interrupt fires once per frame (VI_V_INTR is set during boot)
+        wait_for("MI_INTR.VI", FRAME_TIMEOUT_MILLISECONDS, || mi::is_vi_interrupt())?;
+        Video::set_register(RegisterOffset::Current, 0x3FF);
+        soft_assert_eq(mi::is_vi_interrupt(), false, "MI_INTR.VI after writing VI_V_CURRENT")?;
+
+        // The written value itself is ignored
+        let v_sync = Video::register(RegisterOffset::VSync);
+        let value = current();
+        soft_assert_eq2(value < v_sync, true, || format!("VI_V_CURRENT ({:#x}) is expected to be unaffected by the write, and therefore below VI_V_SYNC ({:#x})", value, v_sync))?;
+
+        // And it comes back next frame
+        wait_for("MI_INTR.VI (next frame)", FRAME_TIMEOUT_MILLISECONDS, || mi::is_vi_interrupt())?;
+        acknowledge_interrupt();
+        Ok(())
+    }
+}